@@ -1,6 +1,7 @@
 //! Example used for testing for clippy warnings on CI.
 
 fn main() {
+    cargo_emit::error!("MESSAGE");
     cargo_emit::pair!("KEY", "VALUE");
     cargo_emit::rerun_if_changed!("PATH");
     cargo_emit::rerun_if_env_changed!("KEY");
@@ -11,8 +12,12 @@ fn main() {
     cargo_emit::rustc_link_arg!("ARGUMENT");
     cargo_emit::rustc_link_arg_bin!("BINARY" => "ARGUMENT");
     cargo_emit::rustc_link_arg_bins!("ARGUMENT");
+    cargo_emit::rustc_link_arg_tests!("ARGUMENT");
+    cargo_emit::rustc_link_arg_examples!("ARGUMENT");
+    cargo_emit::rustc_link_arg_benches!("ARGUMENT");
+    cargo_emit::rustc_link_arg_cdylib!("ARGUMENT");
     cargo_emit::rustc_link_arg_bin!("BINARY" => "ARGUMENT");
-    cargo_emit::rustc_link_lib!("NAME" => "KIND");
-    cargo_emit::rustc_link_search!("PATH" => "KIND");
+    cargo_emit::rustc_link_lib!("NAME" => "static");
+    cargo_emit::rustc_link_search!("PATH" => "native");
     cargo_emit::warning!("MESSAGE");
 }
@@ -0,0 +1,364 @@
+//! Compile-time validation of literal arguments to the [`cargo-emit`] macros.
+//!
+//! This crate is an implementation detail of the `validate` feature of
+//! [`cargo-emit`], which makes its macros pass their arguments through the
+//! checks defined here. Arguments that are not string literals are only known
+//! at runtime and are left alone, as are the parts of a format string produced
+//! by `{}` placeholders.
+//!
+//! The checks are unit-tested here, and the errors they cause are covered by
+//! `compile_fail` examples in the `validate` module of [`cargo-emit`].
+//!
+//! [`cargo-emit`]: https://docs.rs/cargo-emit
+
+use proc_macro::TokenStream;
+use proc_macro2::{Delimiter, Span, TokenStream as TokenStream2, TokenTree};
+use syn::{Lit, LitStr};
+
+/// Defines a proc macro that runs `$check` on the text of a literal argument.
+macro_rules! checks {
+    ($($(#[$attr:meta])* $name:ident => $check:path;)+) => {$(
+        $(#[$attr])*
+        #[proc_macro]
+        pub fn $name(input: TokenStream) -> TokenStream {
+            validate(input.into(), $check).into()
+        }
+    )+};
+}
+
+checks! {
+    /// Checks the `$key` of `pair!`.
+    key => check_key;
+    /// Checks a free-form `$value`, such as a path or warning message.
+    value => check_value;
+    /// Checks the `$feature` of `rustc_cfg!`.
+    cfg_option => check_cfg_option;
+    /// Checks an environment variable name.
+    env_key => check_env_key;
+    /// Checks the `$name` of `rustc_link_lib!`.
+    link_name => check_link_name;
+    /// Checks the `$kind` of `rustc_link_lib!`.
+    link_kind => check_link_kind;
+    /// Checks the `$kind` of `rustc_link_search!`.
+    search_kind => check_search_kind;
+    /// Checks the `$bin` of `rustc_link_arg_bin!`.
+    bin_name => check_bin_name;
+}
+
+/// Runs `check` on the first argument in `input` if it is a string literal,
+/// returning a `compile_error!` at its span if the check fails.
+fn validate(input: TokenStream2, check: fn(&Text) -> Result<(), String>) -> TokenStream2 {
+    match literal(input) {
+        Some(literal) => match check(&Text::parse(&literal.value())) {
+            Ok(()) => TokenStream2::new(),
+            Err(message) => error(literal.span(), &message),
+        },
+        None => TokenStream2::new(),
+    }
+}
+
+fn error(span: Span, message: &str) -> TokenStream2 {
+    syn::Error::new(span, message).to_compile_error()
+}
+
+/// Returns the string literal that makes up the first comma-separated
+/// argument in `input`, looking through the invisible groups that
+/// `macro_rules!` wraps `$expr` fragments in.
+fn literal(input: TokenStream2) -> Option<LitStr> {
+    let mut tokens: Vec<TokenTree> = input
+        .into_iter()
+        .take_while(|token| !matches!(token, TokenTree::Punct(punct) if punct.as_char() == ','))
+        .collect();
+    loop {
+        match tokens.as_slice() {
+            [TokenTree::Group(group)] if group.delimiter() == Delimiter::None => {
+                tokens = group.stream().into_iter().collect();
+            }
+            [TokenTree::Literal(literal)] => {
+                return match syn::parse2(TokenTree::Literal(literal.clone()).into()) {
+                    Ok(Lit::Str(literal)) => Some(literal),
+                    _ => None,
+                };
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// The text of a literal, which may be a format string.
+#[derive(Debug, PartialEq)]
+struct Text {
+    /// The literal text, with `{}` placeholders removed and `{{`/`}}`
+    /// unescaped.
+    text: String,
+    /// Whether the text contains `{}` placeholders, in which case its full
+    /// contents are only known at runtime.
+    formatted: bool,
+}
+
+impl Text {
+    fn parse(value: &str) -> Self {
+        let mut text = String::with_capacity(value.len());
+        let mut formatted = false;
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    formatted = true;
+                    for c in &mut chars {
+                        if c == '}' {
+                            break;
+                        }
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+        Text { text, formatted }
+    }
+
+    /// Whether the text is known to be empty.
+    fn is_empty(&self) -> bool {
+        self.text.is_empty() && !self.formatted
+    }
+}
+
+fn check_value(value: &Text) -> Result<(), String> {
+    if value.text.contains(['\n', '\r']) {
+        return Err("build script outputs cannot contain newlines".to_owned());
+    }
+    Ok(())
+}
+
+fn check_key(key: &Text) -> Result<(), String> {
+    check_value(key)?;
+    if key.is_empty() {
+        return Err("key cannot be empty".to_owned());
+    }
+    if key.text.contains('=') {
+        return Err("key cannot contain `=`".to_owned());
+    }
+    Ok(())
+}
+
+fn check_cfg_option(cfg: &Text) -> Result<(), String> {
+    check_value(cfg)?;
+    if cfg.formatted {
+        return Ok(());
+    }
+    let (name, value) = match cfg.text.find('=') {
+        Some(eq) => (&cfg.text[..eq], Some(&cfg.text[eq + 1..])),
+        None => (&cfg.text[..], None),
+    };
+    if !is_identifier(name) {
+        return Err(format!(
+            "`{}` is not a valid cfg name; expected an identifier such as `has_feature`",
+            name
+        ));
+    }
+    if let Some(value) = value {
+        let quoted = value.len() >= 2 && value.starts_with('"') && value.ends_with('"');
+        if !quoted || value[1..value.len() - 1].contains('"') {
+            return Err(format!(
+                "cfg value must be a quoted string, as in `{}=\"value\"`",
+                name
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_env_key(key: &Text) -> Result<(), String> {
+    check_key(key)?;
+    if key.text.contains(char::is_whitespace) {
+        return Err("environment variable name cannot contain whitespace".to_owned());
+    }
+    if key.text.contains('\0') {
+        return Err("environment variable name cannot contain NUL".to_owned());
+    }
+    Ok(())
+}
+
+fn check_link_name(name: &Text) -> Result<(), String> {
+    check_value(name)?;
+    if name.is_empty() {
+        return Err("library name cannot be empty".to_owned());
+    }
+    if name.text.contains('=') {
+        return Err(
+            "library name cannot contain `=`; use `$name => $kind` to set the kind".to_owned(),
+        );
+    }
+    Ok(())
+}
+
+const LINK_KINDS: &[&str] = &["dylib", "static", "framework"];
+const LINK_MODIFIERS: &[&str] = &["bundle", "verbatim", "whole-archive", "as-needed"];
+const SEARCH_KINDS: &[&str] = &["dependency", "crate", "native", "framework", "all"];
+
+fn check_link_kind(kind: &Text) -> Result<(), String> {
+    check_value(kind)?;
+    if kind.formatted {
+        return Ok(());
+    }
+    let (name, modifiers) = match kind.text.find(':') {
+        Some(colon) => (&kind.text[..colon], Some(&kind.text[colon + 1..])),
+        None => (&kind.text[..], None),
+    };
+    if !LINK_KINDS.contains(&name) {
+        return Err(unknown("link kind", name, LINK_KINDS));
+    }
+    for modifier in modifiers.into_iter().flat_map(|m| m.split(',')) {
+        let name = modifier.get(1..).unwrap_or_default();
+        if !modifier.starts_with(['+', '-']) || !LINK_MODIFIERS.contains(&name) {
+            return Err(format!(
+                "unknown link modifier `{}`; expected `+` or `-` followed by one of {}",
+                modifier,
+                list(LINK_MODIFIERS)
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_search_kind(kind: &Text) -> Result<(), String> {
+    check_value(kind)?;
+    if kind.formatted || SEARCH_KINDS.contains(&kind.text.as_str()) {
+        return Ok(());
+    }
+    Err(unknown("search kind", &kind.text, SEARCH_KINDS))
+}
+
+fn check_bin_name(bin: &Text) -> Result<(), String> {
+    check_key(bin)?;
+    if bin.text.contains(char::is_whitespace) {
+        return Err("binary name cannot contain whitespace".to_owned());
+    }
+    Ok(())
+}
+
+fn unknown(what: &str, found: &str, expected: &[&str]) -> String {
+    format!(
+        "unknown {} `{}`; expected one of {}",
+        what,
+        found,
+        list(expected)
+    )
+}
+
+fn list(items: &[&str]) -> String {
+    items
+        .iter()
+        .map(|item| format!("`{}`", item))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    name != "_" && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(check: fn(&Text) -> Result<(), String>, value: &str) -> Result<(), String> {
+        check(&Text::parse(value))
+    }
+
+    #[test]
+    fn format_strings() {
+        let text = |text: &str, formatted| Text {
+            text: text.to_owned(),
+            formatted,
+        };
+        assert_eq!(Text::parse("plain"), text("plain", false));
+        assert_eq!(Text::parse("{{a}}"), text("{a}", false));
+        assert_eq!(Text::parse("a{}b{name:?}c"), text("abc", true));
+    }
+
+    #[test]
+    fn literals() {
+        let parse = |input: &str| literal(input.parse().unwrap()).map(|lit| lit.value());
+        assert_eq!(parse(r#""static""#), Some("static".to_owned()));
+        assert_eq!(parse(r#""has_{}", name"#), Some("has_{}".to_owned()));
+        assert_eq!(parse("kind"), None);
+        assert_eq!(parse("1"), None);
+
+        let group = proc_macro2::Group::new(Delimiter::None, r#""dylib""#.parse().unwrap());
+        assert_eq!(
+            literal(TokenTree::Group(group).into()).map(|lit| lit.value()),
+            Some("dylib".to_owned())
+        );
+    }
+
+    #[test]
+    fn link_kinds() {
+        for kind in &[
+            "dylib",
+            "static",
+            "framework",
+            "static:+whole-archive,-bundle",
+            "{}",
+        ] {
+            assert_eq!(check(check_link_kind, kind), Ok(()), "{}", kind);
+        }
+        assert_eq!(
+            check(check_link_kind, "statc"),
+            Err(
+                "unknown link kind `statc`; expected one of `dylib`, `static`, `framework`"
+                    .to_owned()
+            )
+        );
+        assert!(check(check_link_kind, "static:whole-archive").is_err());
+        assert!(check(check_link_kind, "static:+wholearchive").is_err());
+        assert!(check(check_search_kind, "native").is_ok());
+        assert!(check(check_search_kind, "natve").is_err());
+    }
+
+    #[test]
+    fn cfg_options() {
+        for cfg in &["bench", "_private", "feature=\"foo bar\"", "has_{}", "{}"] {
+            assert_eq!(check(check_cfg_option, cfg), Ok(()), "{}", cfg);
+        }
+        for cfg in &[
+            "has feature",
+            "",
+            "1st",
+            "feature=foo",
+            "a=\"b\"c\"",
+            "a\nb",
+        ] {
+            assert!(check(check_cfg_option, cfg).is_err(), "{}", cfg);
+        }
+    }
+
+    #[test]
+    fn keys() {
+        assert!(check(check_env_key, "MY_KEY").is_ok());
+        assert!(check(check_env_key, "{}_DIR").is_ok());
+        assert!(check(check_env_key, "MY KEY").is_err());
+        assert!(check(check_env_key, "KEY=").is_err());
+        assert!(check(check_env_key, "").is_err());
+        assert!(check(check_key, "").is_err());
+        assert!(check(check_key, "{}").is_ok());
+        assert!(check(check_link_name, "ssl").is_ok());
+        assert!(check(check_link_name, "static=ssl").is_err());
+        assert!(check(check_bin_name, "my bin").is_err());
+        assert!(check(check_value, "line\nbreak").is_err());
+        assert!(check(check_value, "").is_ok());
+    }
+}
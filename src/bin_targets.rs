@@ -0,0 +1,507 @@
+//! Checking the binary names given to
+//! [`rustc_link_arg_bin!`](crate::rustc_link_arg_bin) against the package.
+//!
+//! Cargo fails the build if `rustc-link-arg-bin` names a binary target that
+//! does not exist, with an error that does not point at the build script
+//! line responsible. [`bin_targets`] instead reads `Cargo.toml` in
+//! `CARGO_MANIFEST_DIR` and works out the binary targets the way Cargo does:
+//!
+//! - each `[[bin]]` entry;
+//! - `src/main.rs`, named after the package;
+//! - `src/bin/$name.rs` and `src/bin/$name/main.rs`.
+//!
+//! The last two are skipped when `autobins = false`. The link argument for an
+//! unknown binary is then replaced by an [`error!`](crate::error) naming the
+//! known ones, or by a [`warning!`](crate::warning) with
+//! [`OnUnknown::Warn`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! let bins = cargo_emit::bin_targets::bin_targets().unwrap();
+//!
+//! bins.link_arg("hello_world", "-Wl,-z,relro").emit();
+//! ```
+
+use crate::{Directive, LinkArgScope};
+use std::{
+    env, error, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Reads the binary targets of the package in `CARGO_MANIFEST_DIR`.
+pub fn bin_targets() -> Result<BinTargets, Error> {
+    let dir = env::var_os("CARGO_MANIFEST_DIR").ok_or(Error::ManifestDirNotSet)?;
+    BinTargets::read(dir)
+}
+
+/// What [`BinTargets::link_arg`] does for an unknown binary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OnUnknown {
+    /// Emit an error, which fails the build once the build script exits.
+    Error,
+    /// Emit a warning and leave the argument out.
+    Warn,
+}
+
+/// The binary targets of a package, created by [`bin_targets`].
+#[derive(Clone, Debug)]
+pub struct BinTargets {
+    names: Vec<String>,
+    on_unknown: OnUnknown,
+}
+
+impl BinTargets {
+    /// Reads the binary targets of the package whose `Cargo.toml` is in
+    /// `manifest_dir`.
+    pub fn read<P: AsRef<Path>>(manifest_dir: P) -> Result<Self, Error> {
+        let dir = manifest_dir.as_ref();
+        let path = dir.join("Cargo.toml");
+        let manifest = fs::read_to_string(&path).map_err(|error| Error::Io {
+            path: path.clone(),
+            error,
+        })?;
+        let manifest = Manifest::parse(&manifest).map_err(|(line, message)| Error::Parse {
+            path,
+            line,
+            message,
+        })?;
+
+        let mut names = Vec::new();
+        let mut explicit_paths = Vec::new();
+        for bin in &manifest.bins {
+            names.push(bin.name.clone());
+            if let Some(path) = &bin.path {
+                explicit_paths.push(dir.join(path));
+            }
+        }
+        if manifest.autobins {
+            for (name, path) in inferred_bins(dir, &manifest.package) {
+                if !names.contains(&name) && !explicit_paths.contains(&path) {
+                    names.push(name);
+                }
+            }
+        }
+        names.sort();
+
+        Ok(BinTargets {
+            names,
+            on_unknown: OnUnknown::Error,
+        })
+    }
+
+    /// Sets what [`link_arg`](Self::link_arg) does for an unknown binary.
+    ///
+    /// Defaults to [`OnUnknown::Error`].
+    pub fn on_unknown(&mut self, on_unknown: OnUnknown) -> &mut Self {
+        self.on_unknown = on_unknown;
+        self
+    }
+
+    /// Returns the names of the binary targets, sorted.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Returns whether the package has a binary target called `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.iter().any(|bin| bin == name)
+    }
+
+    /// Returns the `rustc-link-arg-bin` directive for `arg`, if the package
+    /// has a binary target called `bin`, or an error or warning otherwise.
+    pub fn link_arg(&self, bin: &str, arg: &str) -> Directive {
+        if self.contains(bin) {
+            return Directive::RustcLinkArg {
+                scope: LinkArgScope::Bin(bin.to_owned()),
+                arg: arg.to_owned(),
+            };
+        }
+
+        let mut message = format!("`rustc-link-arg-bin` names unknown binary `{}`", bin);
+        if self.names.is_empty() {
+            message.push_str(", and the package has no binaries");
+        } else {
+            message.push_str("; expected one of ");
+            for (i, name) in self.names.iter().enumerate() {
+                let sep = if i == 0 { "" } else { ", " };
+                message.push_str(&format!("{}`{}`", sep, name));
+            }
+        }
+        match self.on_unknown {
+            OnUnknown::Error => Directive::Error(message),
+            OnUnknown::Warn => Directive::Warning(message),
+        }
+    }
+}
+
+/// Returns the binaries Cargo infers from the files in `dir`, with their
+/// paths.
+fn inferred_bins(dir: &Path, package: &str) -> Vec<(String, PathBuf)> {
+    let mut bins = Vec::new();
+    let main = dir.join("src/main.rs");
+    if main.is_file() {
+        bins.push((package.to_owned(), main));
+    }
+
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir.join("src/bin")) {
+        Ok(entries) => entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    entries.sort();
+    for path in entries {
+        let (name, path) = if path.is_dir() {
+            (path.file_name(), path.join("main.rs"))
+        } else if path.extension().and_then(|ext| ext.to_str()) == Some("rs") {
+            (path.file_stem(), path.clone())
+        } else {
+            continue;
+        };
+        if let Some(name) = name.and_then(|name| name.to_str()) {
+            if path.is_file() {
+                bins.push((name.to_owned(), path));
+            }
+        }
+    }
+    bins
+}
+
+/// The parts of `Cargo.toml` that decide the binary targets.
+#[derive(Debug, PartialEq)]
+struct Manifest {
+    package: String,
+    autobins: bool,
+    bins: Vec<Bin>,
+}
+
+/// A `[[bin]]` entry.
+#[derive(Debug, PartialEq)]
+struct Bin {
+    name: String,
+    path: Option<String>,
+}
+
+/// The table that the lines of a manifest are in.
+enum Table {
+    Package,
+    Bin,
+    Other,
+}
+
+impl Manifest {
+    /// Parses the few keys that matter, returning the 1-based line number
+    /// and a message on failure.
+    ///
+    /// This is not a full TOML parser: keys are only read as `key = value`
+    /// lines of the `[package]` and `[[bin]]` tables, and other lines are
+    /// skipped.
+    fn parse(manifest: &str) -> Result<Self, (usize, String)> {
+        let mut package = None;
+        let mut autobins = true;
+        let mut bins: Vec<(usize, Option<String>, Option<String>)> = Vec::new();
+        let mut table = Table::Other;
+        let mut multiline_string: Option<&str> = None;
+
+        for (index, line) in manifest.lines().enumerate() {
+            let number = index + 1;
+            if let Some(delimiter) = multiline_string {
+                if line.contains(delimiter) {
+                    multiline_string = None;
+                }
+                continue;
+            }
+
+            let line = line.trim();
+            if line.starts_with("[[") {
+                table = match header(line, "[[", "]]") {
+                    Some("bin") => {
+                        bins.push((number, None, None));
+                        Table::Bin
+                    }
+                    _ => Table::Other,
+                };
+                continue;
+            } else if line.starts_with('[') {
+                table = match header(line, "[", "]") {
+                    Some("package") | Some("project") => Table::Package,
+                    _ => Table::Other,
+                };
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (unquote_key(key.trim()), value.trim()),
+                None => continue,
+            };
+            for delimiter in ["\"\"\"", "'''"] {
+                if value.starts_with(delimiter) && !value[3..].contains(delimiter) {
+                    multiline_string = Some(delimiter);
+                }
+            }
+
+            match (&table, key) {
+                (Table::Package, "name") => package = Some(string(value, number)?),
+                (Table::Package, "autobins") => {
+                    autobins = match strip_comment(value) {
+                        "true" => true,
+                        "false" => false,
+                        _ => return Err((number, "`autobins` must be a boolean".to_owned())),
+                    }
+                }
+                (Table::Bin, "name") => bins.last_mut().unwrap().1 = Some(string(value, number)?),
+                (Table::Bin, "path") => bins.last_mut().unwrap().2 = Some(string(value, number)?),
+                _ => {}
+            }
+        }
+
+        let bins = bins
+            .into_iter()
+            .map(|(number, name, path)| match name {
+                Some(name) => Ok(Bin { name, path }),
+                None => Err((number, "`[[bin]]` has no `name`".to_owned())),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Manifest {
+            package: package.ok_or((1, "`[package]` has no `name`".to_owned()))?,
+            autobins,
+            bins,
+        })
+    }
+}
+
+/// Returns the name of the table in a `[name]` or `[[name]]` header line.
+fn header<'a>(line: &'a str, open: &str, close: &str) -> Option<&'a str> {
+    let line = strip_comment(line);
+    Some(line.strip_prefix(open)?.strip_suffix(close)?.trim())
+}
+
+/// Removes the quotes around a quoted key.
+fn unquote_key(key: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(key) = key
+            .strip_prefix(quote)
+            .and_then(|key| key.strip_suffix(quote))
+        {
+            return key;
+        }
+    }
+    key
+}
+
+/// Removes a trailing `#` comment from an unquoted value.
+fn strip_comment(value: &str) -> &str {
+    value.split('#').next().unwrap_or_default().trim()
+}
+
+/// Parses a basic or literal string value.
+fn string(value: &str, number: usize) -> Result<String, (usize, String)> {
+    let error = || (number, format!("expected a string, found `{}`", value));
+    let mut chars = value.chars();
+    match chars.next() {
+        Some('\'') => {
+            let rest = chars.as_str();
+            let end = rest.find('\'').ok_or_else(error)?;
+            Ok(rest[..end].to_owned())
+        }
+        Some('"') => {
+            let mut string = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => return Ok(string),
+                    '\\' => match chars.next().ok_or_else(error)? {
+                        'n' => string.push('\n'),
+                        't' => string.push('\t'),
+                        c => string.push(c),
+                    },
+                    c => string.push(c),
+                }
+            }
+            Err(error())
+        }
+        _ => Err(error()),
+    }
+}
+
+/// The error returned by [`bin_targets`] and [`BinTargets::read`].
+#[derive(Debug)]
+pub enum Error {
+    /// `CARGO_MANIFEST_DIR` is not set, as happens outside of a build script.
+    ManifestDirNotSet,
+    /// `Cargo.toml` could not be read.
+    Io {
+        /// The manifest being read.
+        path: PathBuf,
+        /// The underlying error.
+        error: io::Error,
+    },
+    /// `Cargo.toml` does not describe the package as expected.
+    Parse {
+        /// The manifest being parsed.
+        path: PathBuf,
+        /// The 1-based line number of the problem.
+        line: usize,
+        /// What went wrong.
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::ManifestDirNotSet => f.write_str("CARGO_MANIFEST_DIR is not set"),
+            Error::Io { path, error } => {
+                write!(f, "could not read `{}`: {}", path.display(), error)
+            }
+            Error::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, manifest: &str, files: &[&str]) -> PathBuf {
+        let dir = crate::temp_dir(name);
+        fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+        for file in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "fn main() {}").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn parse() {
+        let manifest = Manifest::parse(
+            r#"
+            [package]
+            name = "hello" # the package
+            description = """
+            name = "not the package"
+            """
+            autobins = false
+
+            [dependencies]
+            name = "not a bin"
+
+            [[bin]]
+            name = 'server'
+            path = "src/server/main.rs"
+
+            [[ bin ]] # another
+            "name" = "client\"s"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            manifest,
+            Manifest {
+                package: "hello".into(),
+                autobins: false,
+                bins: vec![
+                    Bin {
+                        name: "server".into(),
+                        path: Some("src/server/main.rs".into()),
+                    },
+                    Bin {
+                        name: "client\"s".into(),
+                        path: None,
+                    },
+                ],
+            }
+        );
+
+        assert_eq!(
+            Manifest::parse("[package]\nname = \"a\"\n[[bin]]\npath = \"a.rs\"\n"),
+            Err((3, "`[[bin]]` has no `name`".into()))
+        );
+        assert_eq!(
+            Manifest::parse("[package]\nname = hello\n"),
+            Err((2, "expected a string, found `hello`".into()))
+        );
+    }
+
+    #[test]
+    fn inferred() {
+        let dir = package(
+            "bin-targets-inferred",
+            "[package]\nname = \"hello-world\"\n\n[[bin]]\nname = \"tool\"\npath = \"src/bin/renamed.rs\"\n",
+            &[
+                "src/main.rs",
+                "src/bin/cli.rs",
+                "src/bin/renamed.rs",
+                "src/bin/server/main.rs",
+                "src/bin/notes.txt",
+                "src/bin/empty/lib.rs",
+            ],
+        );
+        let bins = BinTargets::read(&dir).unwrap();
+        assert_eq!(bins.names(), ["cli", "hello-world", "server", "tool"]);
+        assert!(bins.contains("server"));
+        assert!(!bins.contains("renamed"));
+    }
+
+    #[test]
+    fn autobins() {
+        let dir = package(
+            "bin-targets-autobins",
+            "[package]\nname = \"hello\"\nautobins = false\n",
+            &["src/main.rs", "src/bin/cli.rs"],
+        );
+        assert!(BinTargets::read(&dir).unwrap().names().is_empty());
+    }
+
+    #[test]
+    fn link_arg() {
+        let dir = package(
+            "bin-targets-link-arg",
+            "[package]\nname = \"hello_world\"\n",
+            &["src/main.rs", "src/bin/server.rs"],
+        );
+        let mut bins = BinTargets::read(&dir).unwrap();
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                bins.link_arg("hello_world", "-Wall").emit_to(output);
+                bins.link_arg("hello_wrold", "-Wall").emit_to(output);
+                bins.on_unknown(OnUnknown::Warn)
+                    .link_arg("hello_wrold", "-Wall")
+                    .emit_to(output);
+            }),
+            @r###"
+        cargo:rustc-link-arg-bin=hello_world=-Wall
+        cargo::error=`rustc-link-arg-bin` names unknown binary `hello_wrold`; expected one of `hello_world`, `server`
+        cargo:warning=`rustc-link-arg-bin` names unknown binary `hello_wrold`; expected one of `hello_world`, `server`
+        "###
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let dir = crate::temp_dir("bin-targets-errors");
+        let error = BinTargets::read(&dir).unwrap_err();
+        assert!(matches!(error, Error::Io { .. }), "{:?}", error);
+
+        let dir = package("bin-targets-no-bins", "[package]\nname = \"lib\"\n", &[]);
+        insta::assert_snapshot!(
+            BinTargets::read(&dir).unwrap().link_arg("lib", "-s").to_string(),
+            @"cargo::error=`rustc-link-arg-bin` names unknown binary `lib`, and the package has no binaries"
+        );
+    }
+}
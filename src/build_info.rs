@@ -0,0 +1,842 @@
+//! Injecting information about the build into the compiled crate.
+//!
+//! [`Config::emit`] collects the git commit, branch, tag and dirty state, the
+//! build time, the `rustc` version, and the profile, target and features
+//! being built, and passes each of them to the compiler with
+//! [`rustc_env!`](crate::rustc_env) so they can be read with [`env!`]. Each
+//! key below is prefixed with the configured prefix, `BUILD_` by default:
+//!
+//! | Key                 | Value                                      |
+//! |---------------------|--------------------------------------------|
+//! | `GIT_COMMIT`        | full commit hash                           |
+//! | `GIT_COMMIT_SHORT`  | first 7 characters of the commit hash      |
+//! | `GIT_DIRTY`         | `true` or `false`, see below               |
+//! | `GIT_BRANCH`        | current branch, if not detached            |
+//! | `GIT_TAG`           | tag pointing at the commit, if any         |
+//! | `TIMESTAMP`         | build time in seconds since the epoch      |
+//! | `TIMESTAMP_RFC3339` | build time, such as `2021-09-01T12:00:00Z` |
+//! | `RUSTC_VERSION`     | output of `rustc --version`                |
+//! | `PROFILE`           | `debug` or `release`                       |
+//! | `TARGET`            | target triple                              |
+//! | `HOST`              | host triple                                |
+//! | `FEATURES`          | enabled features, comma-separated          |
+//!
+//! Every key is always emitted, with an empty value if the information is not
+//! available, so that `env!` never fails to compile.
+//!
+//! Git information is read from the `.git` directory directly, so neither the
+//! `git` binary nor network access is needed. Cargo is told to rerun the
+//! build script when `HEAD`, the index, the current branch or the tags
+//! change, but not when a work tree file does, so `GIT_DIRTY` is the state as
+//! of the last index update, such as by `git add`, `git commit` or
+//! `git status`. The build time honors [`SOURCE_DATE_EPOCH`] for reproducible
+//! builds.
+//!
+//! Annotated tags are only peeled once packed, such as by `git gc` or
+//! `git pack-refs --all`. If `GIT_TAG` is empty while there are loose tags
+//! that may be annotated tags of `HEAD`, a warning is emitted.
+//!
+//! # Examples
+//!
+//! In `build.rs`:
+//!
+//! ```no_run
+//! cargo_emit::build_info::Config::new().prefix("APP_").emit();
+//! ```
+//!
+//! and then in the crate:
+//!
+//! ```ignore
+//! const VERSION: &str = concat!(
+//!     env!("CARGO_PKG_VERSION"),
+//!     " (",
+//!     env!("APP_GIT_COMMIT_SHORT"),
+//!     ")",
+//! );
+//! ```
+//!
+//! [`SOURCE_DATE_EPOCH`]: https://reproducible-builds.org/specs/source-date-epoch/
+
+use crate::Directive;
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    ffi::OsString,
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Options for collecting build information.
+#[derive(Clone, Debug)]
+pub struct Config {
+    prefix: String,
+    dir: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            prefix: "BUILD_".to_owned(),
+            dir: None,
+        }
+    }
+}
+
+impl Config {
+    /// Creates a configuration with the `BUILD_` prefix that looks for a git
+    /// repository containing `CARGO_MANIFEST_DIR`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the prefix of every emitted key.
+    pub fn prefix(&mut self, prefix: &str) -> &mut Self {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    /// Looks for a git repository containing `dir` instead of
+    /// `CARGO_MANIFEST_DIR`.
+    pub fn dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Collects the build information.
+    pub fn collect(&self) -> BuildInfo {
+        self.collect_with(
+            &env::vars_os()
+                .filter_map(|(k, v)| Some((k.into_string().ok()?, v)))
+                .collect(),
+        )
+    }
+
+    /// Collects the build information and emits it to Cargo.
+    pub fn emit(&self) {
+        self.collect().emit();
+    }
+
+    fn collect_with(&self, vars: &HashMap<String, OsString>) -> BuildInfo {
+        let var = |key: &str| {
+            vars.get(key)
+                .and_then(|value| value.to_str())
+                .map(str::to_owned)
+        };
+
+        let dir = self
+            .dir
+            .clone()
+            .or_else(|| vars.get("CARGO_MANIFEST_DIR").map(PathBuf::from))
+            .or_else(|| env::current_dir().ok());
+
+        let timestamp = var("SOURCE_DATE_EPOCH")
+            .and_then(|epoch| epoch.trim().parse().ok())
+            .unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or_default()
+            });
+
+        let rustc = var("RUSTC").unwrap_or_else(|| "rustc".to_owned());
+        let rustc_version = Command::new(rustc)
+            .arg("--version")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|version| version.trim().to_owned());
+
+        let mut features: Vec<String> = vars
+            .keys()
+            .filter_map(|key| key.strip_prefix("CARGO_FEATURE_"))
+            .map(|feature| feature.to_lowercase())
+            .collect();
+        features.sort();
+
+        BuildInfo {
+            git: dir.and_then(|dir| GitInfo::read(&dir)),
+            timestamp,
+            rustc_version,
+            profile: var("PROFILE"),
+            target: var("TARGET"),
+            host: var("HOST"),
+            features,
+            prefix: self.prefix.clone(),
+        }
+    }
+}
+
+/// Information about a build, collected by [`Config::collect`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// The state of the git repository, if the crate is in one.
+    pub git: Option<GitInfo>,
+    /// The build time in seconds since the Unix epoch, taken from
+    /// `SOURCE_DATE_EPOCH` if set.
+    pub timestamp: u64,
+    /// The output of `rustc --version`.
+    pub rustc_version: Option<String>,
+    /// The value of `PROFILE`.
+    pub profile: Option<String>,
+    /// The value of `TARGET`.
+    pub target: Option<String>,
+    /// The value of `HOST`.
+    pub host: Option<String>,
+    /// The enabled features, lowercase and with `-` replaced by `_`, as
+    /// Cargo reports them through `CARGO_FEATURE_*`.
+    pub features: Vec<String>,
+    prefix: String,
+}
+
+impl BuildInfo {
+    /// Returns the directives that pass this information to the compiler.
+    pub fn directives(&self) -> Vec<Directive> {
+        let mut directives = vec![Directive::RerunIfEnvChanged("SOURCE_DATE_EPOCH".to_owned())];
+
+        if let Some(git) = &self.git {
+            for path in &git.tracked_paths {
+                directives.push(Directive::RerunIfChanged(path.display().to_string()));
+            }
+            if git.tag.is_none() && !git.unpeeled_tags.is_empty() {
+                directives.push(Directive::Warning(format!(
+                    "`{}GIT_TAG` is empty, but the loose tags {} may be annotated tags of \
+                     `HEAD`, which are not peeled until packed by `git pack-refs --all`",
+                    self.prefix,
+                    list_tags(&git.unpeeled_tags),
+                )));
+            }
+        }
+
+        let git = self.git.as_ref();
+        let commit = git.map(|git| git.commit.as_str()).unwrap_or_default();
+        let short = commit.get(..7).unwrap_or(commit);
+        let dirty = git.and_then(|git| git.dirty);
+        let optional = |value: Option<&String>| value.cloned().unwrap_or_default();
+
+        let values = [
+            ("GIT_COMMIT", commit.to_owned()),
+            ("GIT_COMMIT_SHORT", short.to_owned()),
+            (
+                "GIT_DIRTY",
+                dirty.map(|dirty| dirty.to_string()).unwrap_or_default(),
+            ),
+            (
+                "GIT_BRANCH",
+                optional(git.and_then(|git| git.branch.as_ref())),
+            ),
+            ("GIT_TAG", optional(git.and_then(|git| git.tag.as_ref()))),
+            ("TIMESTAMP", self.timestamp.to_string()),
+            ("TIMESTAMP_RFC3339", rfc3339(self.timestamp)),
+            ("RUSTC_VERSION", optional(self.rustc_version.as_ref())),
+            ("PROFILE", optional(self.profile.as_ref())),
+            ("TARGET", optional(self.target.as_ref())),
+            ("HOST", optional(self.host.as_ref())),
+            ("FEATURES", self.features.join(",")),
+        ];
+
+        for (key, value) in values.iter() {
+            directives.push(Directive::RustcEnv {
+                key: format!("{}{}", self.prefix, key),
+                value: value.clone(),
+            });
+        }
+
+        directives
+    }
+
+    /// Emits this information to Cargo.
+    pub fn emit(&self) {
+        for directive in self.directives() {
+            directive.emit();
+        }
+    }
+
+    /// Emits this information to `stream`.
+    pub fn emit_to<W: fmt::Write + ?Sized>(&self, stream: &mut W) {
+        for directive in self.directives() {
+            directive.emit_to(stream);
+        }
+    }
+}
+
+/// The state of a git repository.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GitInfo {
+    /// The full hash of the `HEAD` commit.
+    pub commit: String,
+    /// Whether tracked files differ from the index, if that could be
+    /// determined. Untracked files are not taken into account.
+    ///
+    /// The work tree is not among the [tracked paths](Self::tracked_paths),
+    /// so when emitted this is the state as of the last index update.
+    pub dirty: Option<bool>,
+    /// The current branch, or `None` if `HEAD` is detached.
+    pub branch: Option<String>,
+    /// The tag pointing at `HEAD`. If several do, the one that sorts last.
+    ///
+    /// Annotated tags are only found once packed (e.g. by `git gc`), since
+    /// peeling a loose tag object requires decompressing it.
+    pub tag: Option<String>,
+    /// The loose tags that do not point at `HEAD` themselves, but may be
+    /// annotated tags of it.
+    pub unpeeled_tags: Vec<String>,
+    /// The files that change whenever the commit, branch, tags or index do.
+    /// Work tree files are not included.
+    pub tracked_paths: Vec<PathBuf>,
+}
+
+impl GitInfo {
+    /// Reads the repository containing `dir`, if any.
+    pub fn read(dir: &Path) -> Option<GitInfo> {
+        let (git_dir, work_tree) = find_git_dir(dir)?;
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common) => git_dir.join(common.trim()),
+            Err(_) => git_dir.clone(),
+        };
+        let refs = Refs::read(&git_dir, &common_dir);
+
+        let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+        let head = head.trim();
+        let mut tracked_paths = vec![git_dir.join("HEAD")];
+
+        let (commit, branch) = match head.strip_prefix("ref:") {
+            Some(name) => {
+                let name = name.trim();
+                let loose = common_dir.join(name);
+                if loose.is_file() {
+                    tracked_paths.push(loose);
+                }
+                let branch = name.strip_prefix("refs/heads/").unwrap_or(name);
+                (refs.resolve(name)?, Some(branch.to_owned()))
+            }
+            None => (head.to_owned(), None),
+        };
+
+        let tag = refs
+            .tags
+            .iter()
+            .filter(|(_, target)| **target == commit)
+            .map(|(name, _)| name.clone())
+            .max();
+        let mut unpeeled_tags: Vec<String> = refs
+            .tags
+            .iter()
+            .filter(|(name, target)| *target != commit && refs.loose_tags.contains(name))
+            .map(|(name, _)| name.clone())
+            .collect();
+        unpeeled_tags.sort();
+
+        let index = git_dir.join("index");
+        let dirty = if commit.len() == 40 {
+            fs::read(&index)
+                .ok()
+                .and_then(|index| is_dirty(&index, &work_tree))
+        } else {
+            None
+        };
+
+        for path in [
+            index,
+            common_dir.join("packed-refs"),
+            common_dir.join("refs/tags"),
+        ] {
+            if path.exists() {
+                tracked_paths.push(path);
+            }
+        }
+
+        Some(GitInfo {
+            commit,
+            dirty,
+            branch,
+            tag,
+            unpeeled_tags,
+            tracked_paths,
+        })
+    }
+}
+
+/// Lists the first few of `tags` for a warning.
+fn list_tags(tags: &[String]) -> String {
+    let mut list = tags
+        .iter()
+        .take(3)
+        .map(|tag| format!("`{}`", tag))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if tags.len() > 3 {
+        list.push_str(&format!(" and {} more", tags.len() - 3));
+    }
+    list
+}
+
+/// Returns the git directory and work tree for the repository containing
+/// `dir`, following `gitdir:` links used by worktrees and submodules.
+fn find_git_dir(dir: &Path) -> Option<(PathBuf, PathBuf)> {
+    for work_tree in dir.ancestors() {
+        let dot_git = work_tree.join(".git");
+        if dot_git.is_dir() {
+            return Some((dot_git, work_tree.to_owned()));
+        }
+        if let Ok(contents) = fs::read_to_string(&dot_git) {
+            let git_dir = contents.trim().strip_prefix("gitdir:")?.trim();
+            return Some((work_tree.join(git_dir), work_tree.to_owned()));
+        }
+    }
+    None
+}
+
+/// The loose and packed refs of a repository.
+struct Refs {
+    git_dir: PathBuf,
+    common_dir: PathBuf,
+    packed: HashMap<String, String>,
+    /// Tag names and the commits they point to.
+    tags: Vec<(String, String)>,
+    /// The names of the tags in `tags` that were read from loose refs, and
+    /// so may point at a tag object instead.
+    loose_tags: HashSet<String>,
+}
+
+impl Refs {
+    fn read(git_dir: &Path, common_dir: &Path) -> Refs {
+        let mut packed = HashMap::new();
+        let mut tags = HashMap::new();
+
+        if let Ok(contents) = fs::read_to_string(common_dir.join("packed-refs")) {
+            let mut last_tag: Option<String> = None;
+            for line in contents.lines() {
+                if let Some(peeled) = line.strip_prefix('^') {
+                    if let Some(tag) = &last_tag {
+                        tags.insert(tag.clone(), peeled.trim().to_owned());
+                    }
+                    continue;
+                }
+                last_tag = None;
+                let mut parts = line.splitn(2, ' ');
+                if let (Some(hash), Some(name)) = (parts.next(), parts.next()) {
+                    if hash.starts_with('#') {
+                        continue;
+                    }
+                    if let Some(tag) = name.strip_prefix("refs/tags/") {
+                        tags.insert(tag.to_owned(), hash.to_owned());
+                        last_tag = Some(tag.to_owned());
+                    }
+                    packed.insert(name.to_owned(), hash.to_owned());
+                }
+            }
+        }
+
+        let mut loose_tags = HashSet::new();
+        let tags_dir = common_dir.join("refs/tags");
+        let mut stack = vec![tags_dir.clone()];
+        while let Some(dir) = stack.pop() {
+            for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    stack.push(path);
+                } else if let (Ok(hash), Ok(name)) =
+                    (fs::read_to_string(&path), path.strip_prefix(&tags_dir))
+                {
+                    let name = name.to_string_lossy().replace('\\', "/");
+                    tags.insert(name.clone(), hash.trim().to_owned());
+                    loose_tags.insert(name);
+                }
+            }
+        }
+
+        Refs {
+            git_dir: git_dir.to_owned(),
+            common_dir: common_dir.to_owned(),
+            packed,
+            tags: tags.into_iter().collect(),
+            loose_tags,
+        }
+    }
+
+    /// Resolves the ref `name` to a commit hash, following symbolic refs.
+    fn resolve(&self, name: &str) -> Option<String> {
+        let mut name = name.to_owned();
+        for _ in 0..8 {
+            let loose = [self.git_dir.join(&name), self.common_dir.join(&name)]
+                .iter()
+                .find_map(|path| fs::read_to_string(path).ok());
+            let value = match loose {
+                Some(value) => value.trim().to_owned(),
+                None => return self.packed.get(&name).cloned(),
+            };
+            match value.strip_prefix("ref:") {
+                Some(target) => name = target.trim().to_owned(),
+                None => return Some(value),
+            }
+        }
+        None
+    }
+}
+
+/// Compares the files recorded in a git index with the work tree.
+///
+/// Returns `None` if the index uses an unsupported format.
+fn is_dirty(index: &[u8], work_tree: &Path) -> Option<bool> {
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let bytes = index.get(offset..offset + 4)?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    if index.get(..4)? != b"DIRC" {
+        return None;
+    }
+    let version = read_u32(4)?;
+    if !(2..=4).contains(&version) {
+        return None;
+    }
+    let count = read_u32(8)?;
+
+    let mut offset = 12;
+    let mut path = Vec::new();
+    for _ in 0..count {
+        let start = offset;
+        let mtime = (read_u32(start + 8)?, read_u32(start + 12)?);
+        let mode = read_u32(start + 24)?;
+        let size = read_u32(start + 36)?;
+        let hash = index.get(start + 40..start + 60)?;
+        let flags = u16::from_be_bytes([*index.get(start + 60)?, *index.get(start + 61)?]);
+        offset = start + 62;
+
+        let mut skip = flags & 0x8000 != 0;
+        if flags & 0x4000 != 0 {
+            let extended = u16::from_be_bytes([*index.get(offset)?, *index.get(offset + 1)?]);
+            skip |= extended & 0x4000 != 0;
+            offset += 2;
+        }
+
+        if version == 4 {
+            let (strip, len) = read_varint(index.get(offset..)?)?;
+            offset += len;
+            path.truncate(path.len().checked_sub(strip)?);
+        } else {
+            path.clear();
+        }
+        let end = offset + index.get(offset..)?.iter().position(|&b| b == 0)?;
+        path.extend_from_slice(&index[offset..end]);
+        offset = end + 1;
+        if version != 4 {
+            // Entries are padded with NULs to a multiple of 8 bytes.
+            offset = start + (end - start + 8) / 8 * 8;
+        }
+
+        // Submodules and skipped entries are not compared.
+        if skip || mode & 0o170000 == 0o160000 {
+            continue;
+        }
+
+        let file = work_tree.join(String::from_utf8_lossy(&path).as_ref());
+        let metadata = match fs::symlink_metadata(&file) {
+            Ok(metadata) => metadata,
+            Err(_) => return Some(true),
+        };
+        if metadata.len() as u32 != size {
+            return Some(true);
+        }
+        if file_mtime(&metadata) == Some(mtime) {
+            continue;
+        }
+        let contents = if metadata.file_type().is_symlink() {
+            fs::read_link(&file)
+                .ok()?
+                .to_string_lossy()
+                .into_owned()
+                .into_bytes()
+        } else {
+            fs::read(&file).ok()?
+        };
+        if blob_hash(&contents)[..] != *hash {
+            return Some(true);
+        }
+    }
+
+    Some(false)
+}
+
+/// Reads the offset-encoded varint used by index version 4, returning the
+/// value and the number of bytes it took.
+fn read_varint(bytes: &[u8]) -> Option<(usize, usize)> {
+    let mut value = 0usize;
+    for (i, &byte) in bytes.iter().enumerate() {
+        value = (value << 7) | (byte & 0x7f) as usize;
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+        value += 1;
+    }
+    None
+}
+
+fn file_mtime(metadata: &fs::Metadata) -> Option<(u32, u32)> {
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((mtime.as_secs() as u32, mtime.subsec_nanos()))
+}
+
+/// Returns the git object hash of a blob with `contents`.
+fn blob_hash(contents: &[u8]) -> [u8; 20] {
+    let mut data = format!("blob {}\0", contents.len()).into_bytes();
+    data.extend_from_slice(contents);
+    sha1(&data)
+}
+
+/// A minimal SHA-1, which is all git object hashes need.
+pub(crate) fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [
+        0x6745_2301,
+        0xEFCD_AB89,
+        0x98BA_DCFE,
+        0x1032_5476,
+        0xC3D2_E1F0,
+    ];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (chunk, h) in digest.chunks_mut(4).zip(h.iter()) {
+        chunk.copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+/// Formats seconds since the Unix epoch as an RFC 3339 UTC timestamp.
+fn rfc3339(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let seconds = timestamp % 86_400;
+
+    // Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn sha1_vectors() {
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(&sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(&blob_hash(b"hello\n")),
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(1_630_497_600), "2021-09-01T12:00:00Z");
+    }
+
+    #[test]
+    fn repository() {
+        let dir = crate::temp_dir("build-info-repository");
+        let git = dir.join(".git");
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        fs::create_dir_all(git.join("refs/heads/feature")).unwrap();
+        fs::create_dir_all(git.join("refs/tags")).unwrap();
+        fs::write(git.join("HEAD"), "ref: refs/heads/feature/x\n").unwrap();
+        fs::write(git.join("refs/heads/feature/x"), format!("{}\n", commit)).unwrap();
+        fs::write(git.join("refs/tags/v1.0.0"), format!("{}\n", commit)).unwrap();
+        fs::write(
+            git.join("packed-refs"),
+            format!(
+                "# pack-refs with: peeled fully-peeled sorted\n\
+                 ffffffffffffffffffffffffffffffffffffffff refs/tags/v0.9.0\n\
+                 eeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee refs/tags/v1.1.0\n\
+                 ^{}\n",
+                commit
+            ),
+        )
+        .unwrap();
+
+        let info = GitInfo::read(&dir.join("src")).unwrap();
+        assert_eq!(info.commit, commit);
+        assert_eq!(info.branch.as_deref(), Some("feature/x"));
+        assert_eq!(info.tag.as_deref(), Some("v1.1.0"));
+        assert_eq!(info.dirty, None);
+
+        fs::write(git.join("HEAD"), format!("{}\n", commit)).unwrap();
+        let info = GitInfo::read(&dir).unwrap();
+        assert_eq!(info.branch, None);
+    }
+
+    #[test]
+    fn unpeeled_tags() {
+        let dir = crate::temp_dir("build-info-unpeeled-tags");
+        let git = dir.join(".git");
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        fs::create_dir_all(git.join("refs/tags/release")).unwrap();
+        fs::write(git.join("HEAD"), format!("{}\n", commit)).unwrap();
+        for (i, tag) in ["v1", "v2", "v3", "release/v4"].iter().enumerate() {
+            fs::write(git.join("refs/tags").join(tag), format!("{:040}\n", i)).unwrap();
+        }
+
+        let vars: HashMap<String, OsString> = [("RUSTC", "/nonexistent/rustc")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), OsString::from(v)))
+            .collect();
+        let info = Config::new().dir(&dir).collect_with(&vars);
+        let git_info = info.git.as_ref().unwrap();
+        assert_eq!(git_info.tag, None);
+        assert_eq!(git_info.unpeeled_tags, ["release/v4", "v1", "v2", "v3"]);
+        let warnings: Vec<String> = info
+            .directives()
+            .iter()
+            .filter(|directive| matches!(directive, Directive::Warning(_)))
+            .map(ToString::to_string)
+            .collect();
+        insta::assert_snapshot!(
+            warnings.join("\n"),
+            @"cargo:warning=`BUILD_GIT_TAG` is empty, but the loose tags `release/v4`, `v1`, `v2` \
+              and 1 more may be annotated tags of `HEAD`, which are not peeled until packed by \
+              `git pack-refs --all`"
+        );
+
+        fs::write(git.join("refs/tags/v1"), format!("{}\n", commit)).unwrap();
+        let info = Config::new().dir(&dir).collect_with(&vars);
+        assert_eq!(info.git.as_ref().unwrap().tag.as_deref(), Some("v1"));
+        assert!(!info
+            .directives()
+            .iter()
+            .any(|directive| matches!(directive, Directive::Warning(_))));
+    }
+
+    #[test]
+    fn dirty() {
+        let dir = crate::temp_dir("build-info-dirty");
+        fs::write(dir.join("a.txt"), "hello\n").unwrap();
+
+        // A version 2 index with a single entry for `a.txt`, whose recorded
+        // mtime never matches so that its contents are always hashed.
+        let mut index = b"DIRC".to_vec();
+        index.extend_from_slice(&2u32.to_be_bytes());
+        index.extend_from_slice(&1u32.to_be_bytes());
+        index.extend_from_slice(&[0; 24]);
+        index.extend_from_slice(&0o100644u32.to_be_bytes());
+        index.extend_from_slice(&[0; 8]);
+        index.extend_from_slice(&6u32.to_be_bytes());
+        index.extend_from_slice(&blob_hash(b"hello\n"));
+        index.extend_from_slice(&5u16.to_be_bytes());
+        // The path is NUL-padded so the 67-byte entry takes 72 bytes.
+        index.extend_from_slice(b"a.txt\0\0\0\0\0");
+
+        assert_eq!(is_dirty(&index, &dir), Some(false));
+
+        fs::write(dir.join("a.txt"), "HELLO\n").unwrap();
+        assert_eq!(is_dirty(&index, &dir), Some(true));
+
+        fs::remove_file(dir.join("a.txt")).unwrap();
+        assert_eq!(is_dirty(&index, &dir), Some(true));
+
+        assert_eq!(is_dirty(b"DIRC\0\0\0\x05", &dir), None);
+    }
+
+    #[test]
+    fn emitted_keys() {
+        let vars: HashMap<String, OsString> = [
+            ("SOURCE_DATE_EPOCH", "1630497600"),
+            ("PROFILE", "release"),
+            ("TARGET", "x86_64-unknown-linux-gnu"),
+            ("CARGO_FEATURE_DEFAULT", "1"),
+            ("CARGO_FEATURE_SERDE_JSON", "1"),
+            ("RUSTC", "/nonexistent/rustc"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), OsString::from(v)))
+        .collect();
+
+        let dir = crate::temp_dir("build-info-emitted-keys");
+        let info = Config::new().prefix("APP_").dir(&dir).collect_with(&vars);
+
+        insta::assert_snapshot!(
+            crate::capture_output(|output| info.emit_to(output)),
+            @"cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH\n\
+              cargo:rustc-env=APP_GIT_COMMIT=\n\
+              cargo:rustc-env=APP_GIT_COMMIT_SHORT=\n\
+              cargo:rustc-env=APP_GIT_DIRTY=\n\
+              cargo:rustc-env=APP_GIT_BRANCH=\n\
+              cargo:rustc-env=APP_GIT_TAG=\n\
+              cargo:rustc-env=APP_TIMESTAMP=1630497600\n\
+              cargo:rustc-env=APP_TIMESTAMP_RFC3339=2021-09-01T12:00:00Z\n\
+              cargo:rustc-env=APP_RUSTC_VERSION=\n\
+              cargo:rustc-env=APP_PROFILE=release\n\
+              cargo:rustc-env=APP_TARGET=x86_64-unknown-linux-gnu\n\
+              cargo:rustc-env=APP_HOST=\n\
+              cargo:rustc-env=APP_FEATURES=default,serde_json\n"
+        );
+    }
+}
@@ -0,0 +1,376 @@
+//! Generating a Rust module of typed constants into `OUT_DIR`.
+//!
+//! [`rustc_env!`](crate::rustc_env) can only pass strings, which are read back
+//! with [`env!`] and have to be parsed at runtime. [`Constants`] instead
+//! writes typed `pub const` items into `$OUT_DIR/$name.rs`, ready to be
+//! [`include!`]d:
+//!
+//! | Method                      | Generated type            |
+//! |-----------------------------|---------------------------|
+//! | [`bool`](Constants::bool)   | `bool`                    |
+//! | [`int`](Constants::int)     | `u8`, `i32`, `usize`, ... |
+//! | [`str`](Constants::str)     | `&str`                    |
+//! | [`bytes`](Constants::bytes) | `&[u8]`                   |
+//! | [`strs`](Constants::strs)   | `&[&str]`                 |
+//!
+//! The file is only rewritten if its contents change, so that its timestamp
+//! does not force the crate to be rebuilt. Each value can optionally also be
+//! mirrored as a `rustc_env!` line.
+//!
+//! # Examples
+//!
+//! In `build.rs`:
+//!
+//! ```no_run
+//! use cargo_emit::constants::Constants;
+//!
+//! Constants::new("config")
+//!     .bool("HAS_SIMD", true)
+//!     .int("MAX_THREADS", 16u32)
+//!     .str("VENDOR", "acme")
+//!     .strs("BACKENDS", &["gl", "vulkan"])
+//!     .write()
+//!     .unwrap();
+//! ```
+//!
+//! and then in the crate:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/config.rs"));
+//!
+//! fn threads() -> u32 {
+//!     MAX_THREADS
+//! }
+//! ```
+
+use crate::Directive;
+use std::{
+    env,
+    fmt::{self, Write as _},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A builder for a file of `pub const` items.
+#[derive(Clone, Debug)]
+pub struct Constants {
+    name: String,
+    out_dir: Option<PathBuf>,
+    mirror_env: bool,
+    items: Vec<(String, Value)>,
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Bool(bool),
+    Int(&'static str, String),
+    Str(String),
+    Bytes(Vec<u8>),
+    Strs(Vec<String>),
+}
+
+impl Constants {
+    /// Creates an empty file that will be written to `$OUT_DIR/$name.rs`.
+    pub fn new(name: &str) -> Self {
+        Constants {
+            name: name.to_owned(),
+            out_dir: None,
+            mirror_env: false,
+            items: Vec::new(),
+        }
+    }
+
+    /// Writes into `dir` instead of `OUT_DIR`.
+    pub fn out_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.out_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Whether to also emit each value with [`rustc_env!`](crate::rustc_env).
+    ///
+    /// Byte arrays are emitted as lowercase hex, and string lists are joined
+    /// with commas.
+    pub fn mirror_env(&mut self, mirror_env: bool) -> &mut Self {
+        self.mirror_env = mirror_env;
+        self
+    }
+
+    /// Adds a `bool` constant.
+    pub fn bool(&mut self, name: &str, value: bool) -> &mut Self {
+        self.push(name, Value::Bool(value))
+    }
+
+    /// Adds an integer constant of the same type as `value`.
+    pub fn int<T: Integer>(&mut self, name: &str, value: T) -> &mut Self {
+        self.push(name, Value::Int(T::TYPE, value.to_string()))
+    }
+
+    /// Adds a `&str` constant.
+    pub fn str(&mut self, name: &str, value: &str) -> &mut Self {
+        self.push(name, Value::Str(value.to_owned()))
+    }
+
+    /// Adds a `&[u8]` constant.
+    pub fn bytes(&mut self, name: &str, value: &[u8]) -> &mut Self {
+        self.push(name, Value::Bytes(value.to_owned()))
+    }
+
+    /// Adds a `&[&str]` constant.
+    pub fn strs<S: AsRef<str>>(&mut self, name: &str, value: &[S]) -> &mut Self {
+        let value = value.iter().map(|s| s.as_ref().to_owned()).collect();
+        self.push(name, Value::Strs(value))
+    }
+
+    fn push(&mut self, name: &str, value: Value) -> &mut Self {
+        self.items.push((name.to_owned(), value));
+        self
+    }
+
+    /// Returns the generated Rust source.
+    pub fn source(&self) -> String {
+        let mut source = String::from("// @generated by cargo-emit. Do not edit.\n");
+        for (name, value) in &self.items {
+            let _ = match value {
+                Value::Bool(value) => writeln!(source, "pub const {}: bool = {};", name, value),
+                Value::Int(ty, value) => {
+                    writeln!(source, "pub const {}: {} = {};", name, ty, value)
+                }
+                Value::Str(value) => writeln!(source, "pub const {}: &str = {:?};", name, value),
+                Value::Bytes(value) => {
+                    writeln!(source, "pub const {}: &[u8] = &{:?};", name, value)
+                }
+                Value::Strs(value) => {
+                    writeln!(source, "pub const {}: &[&str] = &{:?};", name, value)
+                }
+            };
+        }
+        source
+    }
+
+    /// Returns the [`rustc_env!`](crate::rustc_env) directives that mirror
+    /// each value, or nothing if [`mirror_env`](Self::mirror_env) is not set.
+    pub fn directives(&self) -> Vec<Directive> {
+        if !self.mirror_env {
+            return Vec::new();
+        }
+        self.items
+            .iter()
+            .map(|(name, value)| Directive::RustcEnv {
+                key: name.clone(),
+                value: match value {
+                    Value::Bool(value) => value.to_string(),
+                    Value::Int(_, value) | Value::Str(value) => value.clone(),
+                    Value::Bytes(value) => value.iter().map(|b| format!("{:02x}", b)).collect(),
+                    Value::Strs(value) => value.join(","),
+                },
+            })
+            .collect()
+    }
+
+    /// Writes the file if its contents changed, then emits any mirrored
+    /// values to Cargo.
+    ///
+    /// Returns the path of the file. Fails with
+    /// [`InvalidInput`](io::ErrorKind::InvalidInput) if a name is not an
+    /// identifier, is a keyword or is used twice.
+    pub fn write(&self) -> io::Result<PathBuf> {
+        let path = self.write_file()?;
+        for directive in self.directives() {
+            directive.emit();
+        }
+        Ok(path)
+    }
+
+    /// Writes the file if its contents changed, then emits any mirrored
+    /// values to `stream`.
+    ///
+    /// Returns the path of the file. Fails like [`write`](Self::write).
+    pub fn write_to<W: fmt::Write + ?Sized>(&self, stream: &mut W) -> io::Result<PathBuf> {
+        let path = self.write_file()?;
+        for directive in self.directives() {
+            directive.emit_to(stream);
+        }
+        Ok(path)
+    }
+
+    fn write_file(&self) -> io::Result<PathBuf> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+        for (i, (name, value)) in self.items.iter().enumerate() {
+            if !is_identifier(name) || KEYWORDS.contains(&name.as_str()) {
+                return Err(invalid(format!("`{}` is not a valid constant name", name)));
+            }
+            if self.items[..i].iter().any(|(other, _)| other == name) {
+                return Err(invalid(format!("`{}` is defined more than once", name)));
+            }
+            let has_newline = match value {
+                Value::Str(value) => value.contains('\n'),
+                Value::Strs(values) => values.iter().any(|value| value.contains('\n')),
+                _ => false,
+            };
+            if self.mirror_env && has_newline {
+                return Err(invalid(format!(
+                    "`{}` contains a newline, which cannot be mirrored with `rustc-env`",
+                    name
+                )));
+            }
+        }
+
+        let dir = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => env::var_os("OUT_DIR")
+                .map(PathBuf::from)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is not set"))?,
+        };
+
+        let path = dir.join(format!("{}.rs", self.name));
+        write_if_changed(&path, self.source().as_bytes())?;
+        Ok(path)
+    }
+}
+
+/// Writes `contents` to `path` unless it already holds exactly that, so that
+/// its modification time only changes when its contents do.
+pub(crate) fn write_if_changed(path: &Path, contents: &[u8]) -> io::Result<bool> {
+    if fs::read(path).ok().as_deref() == Some(contents) {
+        return Ok(false);
+    }
+    fs::write(path, contents)?;
+    Ok(true)
+}
+
+/// The keywords of every edition, which cannot name a constant.
+const KEYWORDS: &[&str] = &[
+    "Self", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if",
+    "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    name != "_" && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A primitive integer type that can be written with [`Constants::int`].
+///
+/// This trait is sealed and cannot be implemented outside of this crate.
+pub trait Integer: fmt::Display + private::Sealed {
+    /// The name of the type, such as `u32`.
+    const TYPE: &'static str;
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+macro_rules! impl_integer {
+    ($($ty:ident)+) => {$(
+        impl private::Sealed for $ty {}
+        impl Integer for $ty {
+            const TYPE: &'static str = stringify!($ty);
+        }
+    )+};
+}
+
+impl_integer!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Constants {
+        let mut constants = Constants::new("sample");
+        constants
+            .bool("ENABLED", true)
+            .int("COUNT", 3u8)
+            .int("OFFSET", -42i64)
+            .str("NAME", "say \"hi\"")
+            .bytes("MAGIC", &[0xca, 0xfe])
+            .strs("LIST", &["a", "b"]);
+        constants
+    }
+
+    #[test]
+    fn source() {
+        insta::assert_snapshot!(
+            sample().source(),
+            @r###"
+        // @generated by cargo-emit. Do not edit.
+        pub const ENABLED: bool = true;
+        pub const COUNT: u8 = 3;
+        pub const OFFSET: i64 = -42;
+        pub const NAME: &str = "say \"hi\"";
+        pub const MAGIC: &[u8] = &[202, 254];
+        pub const LIST: &[&str] = &["a", "b"];
+        "###
+        );
+    }
+
+    #[test]
+    fn mirror_env() {
+        let dir = crate::temp_dir("constants-mirror-env");
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                sample().out_dir(&dir).mirror_env(true).write_to(output).unwrap();
+            }),
+            @r###"
+        cargo:rustc-env=ENABLED=true
+        cargo:rustc-env=COUNT=3
+        cargo:rustc-env=OFFSET=-42
+        cargo:rustc-env=NAME=say "hi"
+        cargo:rustc-env=MAGIC=cafe
+        cargo:rustc-env=LIST=a,b
+        "###
+        );
+    }
+
+    #[test]
+    fn unchanged_file_is_not_rewritten() {
+        let dir = crate::temp_dir("constants-unchanged");
+        let path = dir.join("sample.rs");
+
+        let mut constants = sample();
+        constants.out_dir(&dir);
+        assert_eq!(constants.write_to(&mut String::new()).unwrap(), path);
+        assert!(!write_if_changed(&path, constants.source().as_bytes()).unwrap());
+
+        constants.bool("ANOTHER", false);
+        assert!(write_if_changed(&path, constants.source().as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn invalid_names() {
+        let dir = crate::temp_dir("constants-invalid");
+        for name in &[
+            "", "_", "1ST", "MY KEY", "A-B", "type", "Self", "async", "gen",
+        ] {
+            let error = Constants::new("invalid")
+                .out_dir(&dir)
+                .bool(name, true)
+                .write_to(&mut String::new())
+                .unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let dir = crate::temp_dir("constants-duplicate");
+        let error = Constants::new("duplicate")
+            .out_dir(&dir)
+            .bool("ENABLED", true)
+            .int("COUNT", 1u8)
+            .str("ENABLED", "yes")
+            .write_to(&mut String::new())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(error.to_string(), "`ENABLED` is defined more than once");
+        assert!(!dir.join("duplicate.rs").exists());
+    }
+}
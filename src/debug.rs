@@ -0,0 +1,173 @@
+//! Mirroring emitted lines for debugging, controlled by `CARGO_EMIT_DEBUG`.
+
+use crate::Location;
+use std::{
+    env,
+    ffi::OsString,
+    fmt,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// The environment variable that enables mirroring.
+pub(crate) const VAR: &str = "CARGO_EMIT_DEBUG";
+
+/// The name of the log file in `OUT_DIR`.
+pub(crate) const LOG_FILE_NAME: &str = "cargo-emit.log";
+
+/// The log file of this run, and the path it was created at.
+static LOG: Mutex<Option<(PathBuf, File)>> = Mutex::new(None);
+
+/// Mirrors the emitted `line`, written by the macro call at `location`, if
+/// `CARGO_EMIT_DEBUG` is set to anything but `0`.
+///
+/// The line is written to stderr, and if the variable is `log`, also to
+/// `$OUT_DIR/cargo-emit.log`. Returns whether the variable is `warn`, in which
+/// case the line is followed by a warning with its location.
+pub(crate) fn mirror(location: &Location, line: &fmt::Arguments) -> bool {
+    let mode = match mode() {
+        Some(mode) if !mode.is_empty() && mode != "0" => mode,
+        _ => return false,
+    };
+
+    let mut text = String::new();
+    let _ = write_entry(&mut text, location, line);
+    eprint!("[cargo-emit] {}", text);
+
+    if mode == "log" {
+        if let Some(out_dir) = env::var_os("OUT_DIR") {
+            let _ = log(&Path::new(&out_dir).join(LOG_FILE_NAME), &text);
+        }
+    }
+    mode == "warn"
+}
+
+#[cfg(not(test))]
+fn mode() -> Option<OsString> {
+    env::var_os(VAR)
+}
+
+#[cfg(test)]
+fn mode() -> Option<OsString> {
+    tests::MODE
+        .with(|mode| mode.borrow().clone())
+        .or_else(|| env::var_os(VAR))
+}
+
+fn write_entry<W: fmt::Write>(
+    stream: &mut W,
+    location: &Location,
+    line: &fmt::Arguments,
+) -> fmt::Result {
+    writeln!(stream, "{}: {}", location, line)
+}
+
+/// Appends `text` to the log at `path`, replacing the log of a previous run.
+fn log(path: &Path, text: &str) -> io::Result<()> {
+    let mut log = LOG.lock().unwrap_or_else(|error| error.into_inner());
+    let file = match &mut *log {
+        Some((log_path, file)) if log_path == path => file,
+        log => &mut log.insert((path.to_owned(), File::create(path)?)).1,
+    };
+    file.write_all(text.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::BuildEnv, Directive};
+    use std::{cell::RefCell, fs};
+
+    thread_local! {
+        /// Overrides `CARGO_EMIT_DEBUG` on this thread, so that a test can
+        /// enable a mode without affecting the tests running alongside it.
+        pub(super) static MODE: RefCell<Option<OsString>> = const { RefCell::new(None) };
+    }
+
+    #[test]
+    fn entry() {
+        let mut text = String::new();
+        write_entry(
+            &mut text,
+            &Location::new("build.rs", 12, "build_script_build"),
+            &format_args!("cargo:rustc-cfg={}", "ssl"),
+        )
+        .unwrap();
+        assert_eq!(
+            text,
+            "build.rs:12 in build_script_build: cargo:rustc-cfg=ssl\n"
+        );
+    }
+
+    #[test]
+    fn log_file() {
+        let output = BuildEnv::new().env(VAR, "log").run(|| {
+            crate::rustc_cfg!("debug_log_test");
+            crate::rustc_link_lib!(
+                "debug_log_test" => "static",
+            );
+        });
+
+        // Other tests may emit while the variable is set.
+        let log = fs::read_to_string(output.out_dir.join(LOG_FILE_NAME)).unwrap();
+        let log: Vec<&str> = log
+            .lines()
+            .filter(|line| line.contains("debug_log_test"))
+            .collect();
+        let line = line!();
+        assert_eq!(
+            log,
+            [
+                format!(
+                    "src/debug.rs:{} in cargo_emit::debug::tests: \
+                     cargo:rustc-cfg=debug_log_test",
+                    line - 12
+                ),
+                format!(
+                    "src/debug.rs:{} in cargo_emit::debug::tests: \
+                     cargo:rustc-link-lib=static=debug_log_test",
+                    line - 11
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn warn() {
+        MODE.with(|mode| *mode.borrow_mut() = Some("warn".into()));
+        let mut stream = String::new();
+        let line = line!();
+        let output = BuildEnv::new().run(|| {
+            crate::rustc_cfg!(to: stream, "debug_warn_test");
+            crate::rustc_cfg!("debug_warn_test");
+        });
+        MODE.with(|mode| *mode.borrow_mut() = None);
+
+        // The warning only goes to Cargo, not to the `to:` stream.
+        assert_eq!(stream, "cargo:rustc-cfg=debug_warn_test\n");
+        let first = Location::new(file!(), line + 2, module_path!());
+        let second = Location::new(file!(), line + 3, module_path!());
+        let annotation =
+            |location: Location| Directive::Warning(format!("# emitted at {}", location));
+        assert_eq!(
+            output.directives,
+            [
+                annotation(first),
+                Directive::RustcCfg("debug_warn_test".to_owned()),
+                annotation(second),
+            ]
+        );
+        assert_eq!(output.locations, [Some(first), Some(second), Some(second)]);
+    }
+
+    #[test]
+    fn disabled() {
+        let dir = crate::temp_dir("debug-disabled");
+        BuildEnv::new().env(VAR, "0").out_dir(&dir).run(|| {
+            crate::rustc_cfg!("debug_disabled_test");
+        });
+        assert!(!dir.join(LOG_FILE_NAME).exists());
+    }
+}
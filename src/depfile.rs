@@ -0,0 +1,200 @@
+//! Writing the paths a build script depends on as a Makefile depfile.
+//!
+//! Every path emitted with [`rerun_if_changed!`](crate::rerun_if_changed) by
+//! this crate is collected over the whole run. [`Depfile`] writes them in the
+//! format understood by Make and Ninja:
+//!
+//! ```text
+//! /path/to/out/bindings.rs: /path/to/crate/wrapper.h /path/to/crate/my\ headers/a.h
+//! ```
+//!
+//! Relative paths are resolved against `CARGO_MANIFEST_DIR`, as Cargo does.
+//! Spaces and `#` are escaped with `\`, and `$` is written as `$$`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cargo_emit::depfile::Depfile;
+//!
+//! cargo_emit::rerun_if_changed!("wrapper.h", "build.rs");
+//!
+//! // Writes to `$OUT_DIR/cargo-emit.d`.
+//! Depfile::new("bindings.rs").write().unwrap();
+//! ```
+
+use crate::constants::write_if_changed;
+use std::{
+    env,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// The name of the file in `OUT_DIR` that is written by default.
+pub const DEFAULT_FILE_NAME: &str = "cargo-emit.d";
+
+/// Returns every path emitted with
+/// [`rerun_if_changed!`](crate::rerun_if_changed) so far, in order and
+/// without duplicates, as given.
+///
+/// Only paths emitted to stdout by this crate are included, not those emitted
+/// to a custom stream with `to:`.
+pub fn tracked_paths() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for path in crate::output::rerun_paths() {
+        let path = PathBuf::from(path);
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// A builder for a depfile listing the [tracked paths](tracked_paths).
+#[derive(Clone, Debug)]
+pub struct Depfile {
+    target: PathBuf,
+    path: Option<PathBuf>,
+    deps: Vec<PathBuf>,
+}
+
+impl Depfile {
+    /// Creates a depfile for `target`, the output that depends on the tracked
+    /// paths.
+    ///
+    /// A relative `target` is resolved against `OUT_DIR`.
+    pub fn new<P: AsRef<Path>>(target: P) -> Self {
+        Depfile {
+            target: target.as_ref().to_owned(),
+            path: None,
+            deps: Vec::new(),
+        }
+    }
+
+    /// Writes to `path` instead of `$OUT_DIR/cargo-emit.d`.
+    pub fn path<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.path = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Adds a dependency that was not emitted with `rerun_if_changed!`.
+    pub fn dep<P: AsRef<Path>>(&mut self, dep: P) -> &mut Self {
+        self.deps.push(dep.as_ref().to_owned());
+        self
+    }
+
+    /// Returns the contents of the depfile.
+    pub fn contents(&self) -> String {
+        self.contents_with(&tracked_paths(), |key| env::var_os(key))
+    }
+
+    fn contents_with<F>(&self, tracked: &[PathBuf], var: F) -> String
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let resolve = |path: &Path, base: &str| match var(base) {
+            Some(dir) if path.is_relative() => Path::new(&dir).join(path),
+            _ => path.to_owned(),
+        };
+
+        let mut contents = escape(&resolve(&self.target, "OUT_DIR"));
+        contents.push(':');
+        let mut deps: Vec<PathBuf> = Vec::new();
+        for dep in tracked.iter().chain(&self.deps) {
+            let dep = resolve(dep, "CARGO_MANIFEST_DIR");
+            if !deps.contains(&dep) {
+                contents.push(' ');
+                contents.push_str(&escape(&dep));
+                deps.push(dep);
+            }
+        }
+        contents.push('\n');
+        contents
+    }
+
+    /// Writes the depfile if its contents changed.
+    ///
+    /// Returns the path of the depfile.
+    pub fn write(&self) -> io::Result<PathBuf> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => env::var_os("OUT_DIR")
+                .map(|dir| Path::new(&dir).join(DEFAULT_FILE_NAME))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is not set"))?,
+        };
+        write_if_changed(&path, self.contents().as_bytes())?;
+        Ok(path)
+    }
+}
+
+/// Escapes `path` for use in a depfile.
+fn escape(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            ' ' | '#' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '$' => escaped.push_str("$$"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contents() {
+        let var = |key: &str| match key {
+            "OUT_DIR" => Some("/out".into()),
+            "CARGO_MANIFEST_DIR" => Some("/my crate".into()),
+            _ => None,
+        };
+        let tracked = [
+            PathBuf::from("wrapper.h"),
+            PathBuf::from("/usr/include/$weird#name.h"),
+            PathBuf::from("wrapper.h"),
+        ];
+        insta::assert_snapshot!(
+            Depfile::new("bindings.rs")
+                .dep("/my crate/wrapper.h")
+                .dep("build.rs")
+                .contents_with(&tracked, var),
+            @r###"/out/bindings.rs: /my\ crate/wrapper.h /usr/include/$$weird\#name.h /my\ crate/build.rs"###
+        );
+    }
+
+    #[test]
+    fn tracks_emitted_paths() {
+        crate::output::capture(|| {
+            crate::rerun_if_changed!("depfile-test/a.h", "depfile-test/b.h");
+            crate::Directive::RerunIfChanged("depfile-test/a.h".into()).emit();
+        });
+        let tracked: Vec<PathBuf> = tracked_paths()
+            .into_iter()
+            .filter(|path| path.starts_with("depfile-test"))
+            .collect();
+        assert_eq!(
+            tracked,
+            [Path::new("depfile-test/a.h"), Path::new("depfile-test/b.h")]
+        );
+    }
+
+    #[test]
+    fn write() {
+        let dir = crate::temp_dir("depfile");
+        let path = Depfile::new(dir.join("bindings.rs"))
+            .path(dir.join("bindings.d"))
+            .write()
+            .unwrap();
+        assert_eq!(path, dir.join("bindings.d"));
+        assert!(std::fs::read_to_string(path)
+            .unwrap()
+            .starts_with(&format!("{}:", dir.join("bindings.rs").display())));
+    }
+}
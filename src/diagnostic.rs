@@ -0,0 +1,232 @@
+use crate::Directive;
+use alloc::{borrow::ToOwned, format, string::String, vec::Vec};
+use core::fmt;
+
+/// Whether a [`Diagnostic`] fails the build.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Severity {
+    /// Rendered as `cargo:warning` lines.
+    Warning,
+    /// Rendered as `cargo::error` lines, which fail the build.
+    Error,
+}
+
+/// A message with notes, help and code snippets attached, rendered as
+/// consecutive lines that Cargo shows together.
+///
+/// Cargo shows each `cargo:warning` line as its own warning, so a long
+/// explanation in a single [`warning!`](crate::warning) is hard to read. A
+/// diagnostic puts its message on the first line and each attachment on
+/// lines of its own, indented like the diagnostics of `rustc`. Attachments
+/// spanning several lines are aligned with their first line.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use cargo_emit::Diagnostic;
+///
+/// Diagnostic::new("could not find OpenSSL")
+///     .note("pkg-config did not find `openssl.pc`")
+///     .help("install `libssl-dev`, or set `OPENSSL_DIR` to its prefix:")
+///     .snippet("OPENSSL_DIR=/opt/openssl cargo build")
+///     .emit();
+/// # }
+/// ```
+///
+/// emits:
+///
+/// ```text
+/// cargo:warning=could not find OpenSSL
+/// cargo:warning=  = note: pkg-config did not find `openssl.pc`
+/// cargo:warning=  = help: install `libssl-dev`, or set `OPENSSL_DIR` to its prefix:
+/// cargo:warning=  |
+/// cargo:warning=  | OPENSSL_DIR=/opt/openssl cargo build
+/// cargo:warning=  |
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    severity: Severity,
+    message: String,
+    attachments: Vec<Attachment>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Attachment {
+    Note(String),
+    Help(String),
+    Snippet(String),
+}
+
+impl Diagnostic {
+    /// Creates a warning with `message`.
+    pub fn new<M: fmt::Display>(message: M) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: format!("{}", message),
+            attachments: Vec::new(),
+        }
+    }
+
+    /// Sets whether the diagnostic fails the build.
+    ///
+    /// Defaults to [`Severity::Warning`].
+    pub fn severity(&mut self, severity: Severity) -> &mut Self {
+        self.severity = severity;
+        self
+    }
+
+    /// Attaches a `note:` with context about the message.
+    pub fn note<T: fmt::Display>(&mut self, note: T) -> &mut Self {
+        self.attachments.push(Attachment::Note(format!("{}", note)));
+        self
+    }
+
+    /// Attaches a `help:` on how to resolve the message.
+    pub fn help<T: fmt::Display>(&mut self, help: T) -> &mut Self {
+        self.attachments.push(Attachment::Help(format!("{}", help)));
+        self
+    }
+
+    /// Attaches a snippet of code or a command, shown verbatim.
+    pub fn snippet<T: fmt::Display>(&mut self, snippet: T) -> &mut Self {
+        self.attachments
+            .push(Attachment::Snippet(format!("{}", snippet)));
+        self
+    }
+
+    /// Returns the text of each line, without the `cargo:` prefix.
+    fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.message.lines().map(ToOwned::to_owned).collect();
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        for attachment in &self.attachments {
+            match attachment {
+                Attachment::Note(text) => labeled(&mut lines, "note", text),
+                Attachment::Help(text) => labeled(&mut lines, "help", text),
+                Attachment::Snippet(code) => {
+                    lines.push("  |".to_owned());
+                    for line in code.lines() {
+                        lines.push(match line {
+                            "" => "  |".to_owned(),
+                            line => format!("  | {}", line),
+                        });
+                    }
+                    lines.push("  |".to_owned());
+                }
+            }
+        }
+        lines
+    }
+
+    /// Returns one [`Warning`](Directive::Warning) or
+    /// [`Error`](Directive::Error) per line.
+    pub fn directives(&self) -> Vec<Directive> {
+        self.lines()
+            .into_iter()
+            .map(|line| match self.severity {
+                Severity::Warning => Directive::Warning(line),
+                Severity::Error => Directive::Error(line),
+            })
+            .collect()
+    }
+
+    /// Emits the diagnostic to Cargo.
+    #[cfg(feature = "std")]
+    pub fn emit(&self) {
+        for directive in self.directives() {
+            directive.emit();
+        }
+    }
+
+    /// Emits the diagnostic to `stream`.
+    pub fn emit_to<W: fmt::Write + ?Sized>(&self, stream: &mut W) {
+        for directive in self.directives() {
+            directive.emit_to(stream);
+        }
+    }
+}
+
+/// Adds `text` as `  = $label: $text`, aligning its other lines with the
+/// first.
+fn labeled(lines: &mut Vec<String>, label: &str, text: &str) {
+    let prefix = format!("  = {}: ", label);
+    let mut text_lines = text.lines();
+    lines.push(
+        format!("{}{}", prefix, text_lines.next().unwrap_or(""))
+            .trim_end()
+            .to_owned(),
+    );
+    for line in text_lines {
+        lines.push(match line {
+            "" => String::new(),
+            line => format!("{:width$}{}", "", line, width = prefix.len()),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(diagnostic: &Diagnostic) -> String {
+        crate::capture_output(|output| diagnostic.emit_to(output))
+    }
+
+    #[test]
+    fn attachments() {
+        insta::assert_snapshot!(
+            render(
+                Diagnostic::new("could not find OpenSSL")
+                    .note("pkg-config did not find `openssl.pc`")
+                    .help("install `libssl-dev`, or set `OPENSSL_DIR` to its prefix:")
+                    .snippet("OPENSSL_DIR=/opt/openssl \\\n    cargo build")
+            ),
+            @r###"
+        cargo:warning=could not find OpenSSL
+        cargo:warning=  = note: pkg-config did not find `openssl.pc`
+        cargo:warning=  = help: install `libssl-dev`, or set `OPENSSL_DIR` to its prefix:
+        cargo:warning=  |
+        cargo:warning=  | OPENSSL_DIR=/opt/openssl \
+        cargo:warning=  |     cargo build
+        cargo:warning=  |
+        "###
+        );
+    }
+
+    #[test]
+    fn multiple_lines() {
+        insta::assert_snapshot!(
+            render(
+                Diagnostic::new("first\nsecond")
+                    .note("searched:\n/usr/lib\n\n/usr/local/lib")
+                    .help("")
+            ),
+            @r###"
+        cargo:warning=first
+        cargo:warning=second
+        cargo:warning=  = note: searched:
+        cargo:warning=          /usr/lib
+        cargo:warning=
+        cargo:warning=          /usr/local/lib
+        cargo:warning=  = help:
+        "###
+        );
+    }
+
+    #[test]
+    fn error() {
+        insta::assert_snapshot!(
+            render(
+                Diagnostic::new(format_args!("`{}` is not a directory", "FOO_LIB_DIR"))
+                    .severity(Severity::Error)
+                    .help("unset it to use the system library")
+            ),
+            @r###"
+        cargo::error=`FOO_LIB_DIR` is not a directory
+        cargo::error=  = help: unset it to use the system library
+        "###
+        );
+    }
+}
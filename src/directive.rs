@@ -0,0 +1,906 @@
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::{fmt, str::FromStr};
+
+/// A single [build script output], as a typed value.
+///
+/// Each variant corresponds to one of this crate's macros, or to the
+/// `rustc_link_arg*!` family for [`RustcLinkArg`](Directive::RustcLinkArg),
+/// and renders to the exact same `cargo:` line through its
+/// [`Display`](fmt::Display) implementation.
+///
+/// With the `serde` feature, a directive is serialized with its Cargo key and
+/// value, such as `{"key":"rustc-cfg","value":"has_ssl"}` or
+/// `{"key":"rustc-link-lib","value":{"name":"ssl","kind":"static"}}`. Link
+/// arguments all use the `rustc-link-arg` key, with their [`LinkArgScope`] in
+/// the value.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use cargo_emit::{Directive, LinkKind};
+///
+/// let directive = Directive::RustcLinkLib {
+///     name: "ssl".into(),
+///     kind: Some(LinkKind::Static),
+///     modifiers: Vec::new(),
+/// };
+///
+/// assert_eq!(directive.to_string(), "cargo:rustc-link-lib=static=ssl");
+///
+/// directive.emit();
+/// # }
+/// ```
+///
+/// [build script output]: https://doc.rust-lang.org/cargo/reference/build-scripts.html#outputs-of-the-build-script
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "key", content = "value", rename_all = "kebab-case")
+)]
+pub enum Directive {
+    /// `cargo::error=$message`
+    Error(String),
+    /// `cargo:$key=$value`, as emitted by [`pair!`](crate::pair).
+    Pair {
+        /// The metadata key.
+        key: String,
+        /// The metadata value.
+        value: String,
+    },
+    /// `cargo:rerun-if-changed=$path`
+    RerunIfChanged(String),
+    /// `cargo:rerun-if-env-changed=$key`
+    RerunIfEnvChanged(String),
+    /// `cargo:rustc-cfg=$feature`
+    RustcCfg(String),
+    /// `cargo:rustc-env=$key=$value`
+    RustcEnv {
+        /// The environment variable name.
+        key: String,
+        /// The environment variable value.
+        value: String,
+    },
+    /// `cargo:rustc-flags=$flags`
+    RustcFlags(String),
+    /// `cargo:rustc-link-arg=$arg`, or `cargo:rustc-link-arg-$scope=$arg` for
+    /// the targets given by `scope`.
+    RustcLinkArg {
+        /// The targets that the argument is passed for.
+        scope: LinkArgScope,
+        /// The linker argument.
+        arg: String,
+    },
+    /// `cargo:rustc-link-lib=[$kind[:$modifiers]=]$name`
+    RustcLinkLib {
+        /// The name of the library, without any `lib` prefix or extension.
+        name: String,
+        /// How the library should be linked, if not left up to the compiler.
+        kind: Option<LinkKind>,
+        /// The modifiers of `kind`, such as `+whole-archive`.
+        ///
+        /// Cargo only accepts modifiers after a kind, so they are left out
+        /// when `kind` is `None`.
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Vec::is_empty")
+        )]
+        modifiers: Vec<LinkModifier>,
+    },
+    /// `cargo:rustc-link-search=[$kind=]$path`
+    RustcLinkSearch {
+        /// The directory to search.
+        path: String,
+        /// What the directory should be searched for, if not everything.
+        kind: Option<SearchKind>,
+    },
+    /// `cargo:warning=$message`
+    Warning(String),
+}
+
+impl Directive {
+    /// Returns the key that Cargo uses to identify this directive.
+    pub fn key(&self) -> &str {
+        match self {
+            Directive::Error(_) => "error",
+            Directive::Pair { key, .. } => key,
+            Directive::RerunIfChanged(_) => "rerun-if-changed",
+            Directive::RerunIfEnvChanged(_) => "rerun-if-env-changed",
+            Directive::RustcCfg(_) => "rustc-cfg",
+            Directive::RustcEnv { .. } => "rustc-env",
+            Directive::RustcFlags(_) => "rustc-flags",
+            Directive::RustcLinkArg { scope, .. } => scope.key(),
+            Directive::RustcLinkLib { .. } => "rustc-link-lib",
+            Directive::RustcLinkSearch { .. } => "rustc-link-search",
+            Directive::Warning(_) => "warning",
+        }
+    }
+
+    /// Parses a single line of build script output, such as
+    /// `cargo:rustc-cfg=foo`.
+    ///
+    /// Returns `None` if `line` is not a `cargo:` directive. Values that do not
+    /// fit their typed variant, such as a `rustc-link-lib` with an unknown
+    /// kind or modifier, are returned as [`Directive::Pair`] so that they still render to
+    /// the same line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cargo_emit::{Directive, LinkKind};
+    ///
+    /// assert_eq!(
+    ///     Directive::parse("cargo:rustc-link-lib=static=ssl"),
+    ///     Some(Directive::RustcLinkLib {
+    ///         name: "ssl".into(),
+    ///         kind: Some(LinkKind::Static),
+    ///         modifiers: Vec::new(),
+    ///     }),
+    /// );
+    /// ```
+    pub fn parse(line: &str) -> Option<Directive> {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let (key, value, new_syntax) = match line.strip_prefix("cargo::") {
+            Some(line) => line.split_once('=').map(|(k, v)| (k, v, true))?,
+            None => line
+                .strip_prefix("cargo:")?
+                .split_once('=')
+                .map(|(k, v)| (k, v, false))?,
+        };
+        let value = value.to_owned();
+
+        let pair = |key: &str, value: String| Directive::Pair {
+            key: key.to_owned(),
+            value,
+        };
+
+        let directive = match key {
+            "error" if new_syntax => Directive::Error(value),
+            "metadata" if new_syntax => match value.split_once('=') {
+                Some((key, value)) => pair(key, value.to_owned()),
+                None => pair(key, value),
+            },
+            "rerun-if-changed" => Directive::RerunIfChanged(value),
+            "rerun-if-env-changed" => Directive::RerunIfEnvChanged(value),
+            "rustc-cdylib-link-arg" => Directive::RustcLinkArg {
+                scope: LinkArgScope::Cdylib { legacy: true },
+                arg: value,
+            },
+            "rustc-cfg" => Directive::RustcCfg(value),
+            "rustc-flags" => Directive::RustcFlags(value),
+            "warning" => Directive::Warning(value),
+            "rustc-env" => match value.split_once('=') {
+                Some((key, value)) => Directive::RustcEnv {
+                    key: key.to_owned(),
+                    value: value.to_owned(),
+                },
+                None => pair(key, value),
+            },
+            "rustc-link-arg-bin" => match value.split_once('=') {
+                Some((bin, arg)) => Directive::RustcLinkArg {
+                    scope: LinkArgScope::Bin(bin.to_owned()),
+                    arg: arg.to_owned(),
+                },
+                None => pair(key, value),
+            },
+            "rustc-link-lib" => match value.split_once('=') {
+                None => Directive::RustcLinkLib {
+                    name: value,
+                    kind: None,
+                    modifiers: Vec::new(),
+                },
+                Some((kind, name)) => {
+                    let (kind, modifiers) = match kind.split_once(':') {
+                        Some((kind, modifiers)) => {
+                            (kind, modifiers.split(',').map(str::parse).collect())
+                        }
+                        None => (kind, Ok(Vec::new())),
+                    };
+                    match (kind.parse(), modifiers) {
+                        (Ok(kind), Ok(modifiers)) => Directive::RustcLinkLib {
+                            name: name.to_owned(),
+                            kind: Some(kind),
+                            modifiers,
+                        },
+                        _ => pair(key, value),
+                    }
+                }
+            },
+            "rustc-link-search" => match value.split_once('=') {
+                Some((kind, path)) => match kind.parse() {
+                    Ok(kind) => Directive::RustcLinkSearch {
+                        path: path.to_owned(),
+                        kind: Some(kind),
+                    },
+                    Err(_) => Directive::RustcLinkSearch {
+                        path: value,
+                        kind: None,
+                    },
+                },
+                None => Directive::RustcLinkSearch {
+                    path: value,
+                    kind: None,
+                },
+            },
+            _ => match LinkArgScope::from_key(key) {
+                Some(scope) => Directive::RustcLinkArg { scope, arg: value },
+                None => pair(key, value),
+            },
+        };
+        Some(directive)
+    }
+
+    /// Emits this directive to Cargo.
+    #[cfg(feature = "std")]
+    pub fn emit(&self) {
+        use std::io::Write as _;
+        writeln!(crate::__private::stdout(), "{}", self).unwrap();
+    }
+
+    /// Emits this directive to `stream`.
+    pub fn emit_to<W: fmt::Write + ?Sized>(&self, stream: &mut W) {
+        writeln!(stream, "{}", self).unwrap();
+    }
+
+    /// Returns the prefix of this directive's line, which is `cargo::` for
+    /// the directives that only exist in the newer syntax.
+    fn prefix(&self) -> &'static str {
+        match self {
+            Directive::Error(_) => "cargo::",
+            _ => "cargo:",
+        }
+    }
+}
+
+impl fmt::Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}={}", self.prefix(), self.key(), Value(self))
+    }
+}
+
+/// Renders the part of a directive after `cargo:$key=`.
+struct Value<'a>(&'a Directive);
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Directive::Pair { value, .. } => f.write_str(value),
+            Directive::Error(value)
+            | Directive::RerunIfChanged(value)
+            | Directive::RerunIfEnvChanged(value)
+            | Directive::RustcCfg(value)
+            | Directive::RustcFlags(value)
+            | Directive::Warning(value) => f.write_str(value),
+            Directive::RustcEnv { key, value } => write!(f, "{}={}", key, value),
+            Directive::RustcLinkArg { scope, arg } => match scope {
+                LinkArgScope::Bin(bin) => write!(f, "{}={}", bin, arg),
+                _ => f.write_str(arg),
+            },
+            Directive::RustcLinkLib {
+                name,
+                kind,
+                modifiers,
+            } => match kind {
+                Some(kind) => {
+                    write!(f, "{}", kind)?;
+                    for (i, modifier) in modifiers.iter().enumerate() {
+                        let sep = if i == 0 { ":" } else { "," };
+                        write!(f, "{}{}", sep, modifier)?;
+                    }
+                    write!(f, "={}", name)
+                }
+                None => f.write_str(name),
+            },
+            Directive::RustcLinkSearch { path, kind } => match kind {
+                Some(kind) => write!(f, "{}={}", kind, path),
+                None => f.write_str(path),
+            },
+        }
+    }
+}
+
+/// How a library passed to [`rustc_link_lib!`](crate::rustc_link_lib) should
+/// be linked.
+///
+/// This implements [`Display`](fmt::Display), so it can be passed directly as
+/// the `$kind` of the macro:
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use cargo_emit::LinkKind;
+///
+/// cargo_emit::rustc_link_lib!("ruby" => LinkKind::Static);
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum LinkKind {
+    /// `dylib`: a dynamic library.
+    Dylib,
+    /// `static`: a static library.
+    Static,
+    /// `framework`: a macOS framework.
+    Framework,
+}
+
+impl LinkKind {
+    /// Returns the name Cargo uses for this kind.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LinkKind::Dylib => "dylib",
+            LinkKind::Static => "static",
+            LinkKind::Framework => "framework",
+        }
+    }
+}
+
+impl fmt::Display for LinkKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LinkKind {
+    type Err = UnknownKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dylib" => Ok(LinkKind::Dylib),
+            "static" => Ok(LinkKind::Static),
+            "framework" => Ok(LinkKind::Framework),
+            _ => Err(UnknownKind(s.to_owned())),
+        }
+    }
+}
+
+/// A modifier of a [`LinkKind`], enabled with `+` or disabled with `-`,
+/// such as `+whole-archive`.
+///
+/// # Examples
+///
+/// ```
+/// use cargo_emit::{Directive, LinkKind, LinkModifier};
+///
+/// let directive = Directive::RustcLinkLib {
+///     name: "foo".into(),
+///     kind: Some(LinkKind::Static),
+///     modifiers: vec![LinkModifier::WholeArchive(true), LinkModifier::Bundle(false)],
+/// };
+///
+/// assert_eq!(
+///     directive.to_string(),
+///     "cargo:rustc-link-lib=static:+whole-archive,-bundle=foo"
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum LinkModifier {
+    /// `bundle`: whether a static library is packed into the `rlib`.
+    Bundle(bool),
+    /// `whole-archive`: whether every object of a static library is linked.
+    WholeArchive(bool),
+    /// `as-needed`: whether a dynamic library is only linked if it is used.
+    AsNeeded(bool),
+    /// `verbatim`: whether the name is a file name, without a `lib` prefix or
+    /// extension added.
+    Verbatim(bool),
+}
+
+impl LinkModifier {
+    /// Returns the name Cargo uses for this modifier, without its sign.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LinkModifier::Bundle(_) => "bundle",
+            LinkModifier::WholeArchive(_) => "whole-archive",
+            LinkModifier::AsNeeded(_) => "as-needed",
+            LinkModifier::Verbatim(_) => "verbatim",
+        }
+    }
+
+    /// Returns whether the modifier is enabled with `+`.
+    pub fn is_enabled(self) -> bool {
+        match self {
+            LinkModifier::Bundle(enabled)
+            | LinkModifier::WholeArchive(enabled)
+            | LinkModifier::AsNeeded(enabled)
+            | LinkModifier::Verbatim(enabled) => enabled,
+        }
+    }
+}
+
+impl fmt::Display for LinkModifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.is_enabled() { "+" } else { "-" };
+        write!(f, "{}{}", sign, self.as_str())
+    }
+}
+
+impl FromStr for LinkModifier {
+    type Err = UnknownKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let enabled = match s.get(..1) {
+            Some("+") => true,
+            Some("-") => false,
+            _ => return Err(UnknownKind(s.to_owned())),
+        };
+        match &s[1..] {
+            "bundle" => Ok(LinkModifier::Bundle(enabled)),
+            "whole-archive" => Ok(LinkModifier::WholeArchive(enabled)),
+            "as-needed" => Ok(LinkModifier::AsNeeded(enabled)),
+            "verbatim" => Ok(LinkModifier::Verbatim(enabled)),
+            _ => Err(UnknownKind(s.to_owned())),
+        }
+    }
+}
+
+/// What a directory passed to
+/// [`rustc_link_search!`](crate::rustc_link_search) should be searched for.
+///
+/// This implements [`Display`](fmt::Display), so it can be passed directly as
+/// the `$kind` of the macro:
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use cargo_emit::SearchKind;
+///
+/// cargo_emit::rustc_link_search!("path/to/ruby/lib/" => SearchKind::Native);
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum SearchKind {
+    /// `dependency`: only search for transitive dependencies.
+    Dependency,
+    /// `crate`: only search for this crate's direct dependencies.
+    Crate,
+    /// `native`: only search for native libraries.
+    Native,
+    /// `framework`: only search for macOS frameworks.
+    Framework,
+    /// `all`: search for everything.
+    All,
+}
+
+impl SearchKind {
+    /// Returns the name Cargo uses for this kind.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SearchKind::Dependency => "dependency",
+            SearchKind::Crate => "crate",
+            SearchKind::Native => "native",
+            SearchKind::Framework => "framework",
+            SearchKind::All => "all",
+        }
+    }
+}
+
+impl fmt::Display for SearchKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SearchKind {
+    type Err = UnknownKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dependency" => Ok(SearchKind::Dependency),
+            "crate" => Ok(SearchKind::Crate),
+            "native" => Ok(SearchKind::Native),
+            "framework" => Ok(SearchKind::Framework),
+            "all" => Ok(SearchKind::All),
+            _ => Err(UnknownKind(s.to_owned())),
+        }
+    }
+}
+
+/// The targets that a [`Directive::RustcLinkArg`] is passed to the linker
+/// for.
+///
+/// Each scope corresponds to one of the `rustc_link_arg*!` macros.
+///
+/// With the `serde` feature, a scope is serialized by name, such as `"bins"`,
+/// or as `{"bin":"$name"}` for a single binary and
+/// `{"cdylib":{"legacy":$legacy}}` for `cdylib` targets.
+///
+/// # Examples
+///
+/// ```
+/// use cargo_emit::{Directive, LinkArgScope};
+///
+/// let directive = Directive::RustcLinkArg {
+///     scope: LinkArgScope::Bin("server".into()),
+///     arg: "-Wl,-z,relro".into(),
+/// };
+///
+/// assert_eq!(
+///     directive.to_string(),
+///     "cargo:rustc-link-arg-bin=server=-Wl,-z,relro"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum LinkArgScope {
+    /// Every target, as with [`rustc_link_arg!`](crate::rustc_link_arg).
+    All,
+    /// Binary targets, as with
+    /// [`rustc_link_arg_bins!`](crate::rustc_link_arg_bins).
+    Bins,
+    /// The binary target with the given name, as with
+    /// [`rustc_link_arg_bin!`](crate::rustc_link_arg_bin).
+    Bin(String),
+    /// Test targets, as with
+    /// [`rustc_link_arg_tests!`](crate::rustc_link_arg_tests).
+    Tests,
+    /// Example targets, as with
+    /// [`rustc_link_arg_examples!`](crate::rustc_link_arg_examples).
+    Examples,
+    /// Benchmark targets, as with
+    /// [`rustc_link_arg_benches!`](crate::rustc_link_arg_benches).
+    Benches,
+    /// `cdylib` targets, as with
+    /// [`rustc_link_arg_cdylib!`](crate::rustc_link_arg_cdylib), or with
+    /// [`rustc_cdylib_link_arg!`](crate::rustc_cdylib_link_arg) if `legacy`.
+    Cdylib {
+        /// Whether the argument is rendered with the older
+        /// `rustc-cdylib-link-arg` key, which Cargo treats the same.
+        legacy: bool,
+    },
+}
+
+impl LinkArgScope {
+    /// Returns the key that Cargo uses for link arguments in this scope.
+    pub fn key(&self) -> &'static str {
+        match self {
+            LinkArgScope::All => "rustc-link-arg",
+            LinkArgScope::Bins => "rustc-link-arg-bins",
+            LinkArgScope::Bin(_) => "rustc-link-arg-bin",
+            LinkArgScope::Tests => "rustc-link-arg-tests",
+            LinkArgScope::Examples => "rustc-link-arg-examples",
+            LinkArgScope::Benches => "rustc-link-arg-benches",
+            LinkArgScope::Cdylib { legacy: false } => "rustc-link-arg-cdylib",
+            LinkArgScope::Cdylib { legacy: true } => "rustc-cdylib-link-arg",
+        }
+    }
+
+    /// Returns the scope for `key`, unless it is `rustc-link-arg-bin`, whose
+    /// value includes the name of the binary.
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "rustc-link-arg" => Some(LinkArgScope::All),
+            "rustc-link-arg-bins" => Some(LinkArgScope::Bins),
+            "rustc-link-arg-tests" => Some(LinkArgScope::Tests),
+            "rustc-link-arg-examples" => Some(LinkArgScope::Examples),
+            "rustc-link-arg-benches" => Some(LinkArgScope::Benches),
+            "rustc-link-arg-cdylib" => Some(LinkArgScope::Cdylib { legacy: false }),
+            _ => None,
+        }
+    }
+}
+
+/// The error returned when parsing a [`LinkKind`], [`LinkModifier`] or
+/// [`SearchKind`] fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownKind(String);
+
+impl fmt::Display for UnknownKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown kind `{}`", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnknownKind {}
+
+/// The place in the source where a macro emitted a directive.
+///
+/// Every macro captures its [`file!`], [`line!`] and [`module_path!`], so
+/// that a directive can be traced back to the call that emitted it, even
+/// across helper crates. Locations are shown by the `CARGO_EMIT_DEBUG`
+/// mirror, recorded in `testing::BuildOutput::locations`, and with the
+/// `serde` feature, recorded by the `json_lines` module.
+///
+/// # Examples
+///
+/// ```
+/// use cargo_emit::Location;
+///
+/// let location = Location::new("build.rs", 12, "build_script_build");
+/// assert_eq!(location.to_string(), "build.rs:12 in build_script_build");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Location {
+    file: &'static str,
+    line: u32,
+    module_path: &'static str,
+}
+
+impl Location {
+    /// Creates a location, as captured by the macros.
+    pub const fn new(file: &'static str, line: u32, module_path: &'static str) -> Self {
+        Location {
+            file,
+            line,
+            module_path,
+        }
+    }
+
+    /// Returns the path of the source file, as given by [`file!`].
+    pub fn file(&self) -> &'static str {
+        self.file
+    }
+
+    /// Returns the line number of the macro call.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// Returns the path of the module containing the macro call.
+    pub fn module_path(&self) -> &'static str {
+        self.module_path
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{} in {}", self.file, self.line, self.module_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_lib() {
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                Directive::RustcLinkLib {
+                    name: "NAME".into(),
+                    kind: None,
+                    modifiers: Vec::new(),
+                }
+                .emit_to(output);
+                Directive::RustcLinkLib {
+                    name: "NAME".into(),
+                    kind: Some(LinkKind::Static),
+                    modifiers: Vec::new(),
+                }
+                .emit_to(output);
+            }),
+            @"cargo:rustc-link-lib=NAME\n\
+              cargo:rustc-link-lib=static=NAME\n"
+        );
+    }
+
+    #[test]
+    fn link_modifiers() {
+        let directives = [
+            Directive::RustcLinkLib {
+                name: "NAME".into(),
+                kind: Some(LinkKind::Static),
+                modifiers: vec![LinkModifier::WholeArchive(true)],
+            },
+            Directive::RustcLinkLib {
+                name: "NAME".into(),
+                kind: Some(LinkKind::Dylib),
+                modifiers: vec![LinkModifier::AsNeeded(false), LinkModifier::Verbatim(true)],
+            },
+        ];
+        let output = crate::capture_output(|output| {
+            for directive in &directives {
+                directive.emit_to(output);
+            }
+        });
+        insta::assert_snapshot!(
+            output,
+            @"cargo:rustc-link-lib=static:+whole-archive=NAME\n\
+              cargo:rustc-link-lib=dylib:-as-needed,+verbatim=NAME\n"
+        );
+        let parsed: Vec<Directive> = output.lines().filter_map(Directive::parse).collect();
+        assert_eq!(parsed, directives);
+
+        for line in [
+            "cargo:rustc-link-lib=static:whole-archive=NAME",
+            "cargo:rustc-link-lib=static:+export-symbols=NAME",
+            "cargo:rustc-link-lib=:+verbatim=NAME",
+        ] {
+            assert_eq!(
+                Directive::parse(line),
+                Some(Directive::Pair {
+                    key: "rustc-link-lib".into(),
+                    value: line["cargo:rustc-link-lib=".len()..].into(),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn link_search() {
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                Directive::RustcLinkSearch {
+                    path: "PATH".into(),
+                    kind: Some(SearchKind::Framework),
+                }
+                .emit_to(output);
+            }),
+            @"cargo:rustc-link-search=framework=PATH\n"
+        );
+    }
+
+    #[test]
+    fn matches_macros() {
+        let directives = [
+            Directive::Pair {
+                key: "KEY".into(),
+                value: "VALUE".into(),
+            },
+            Directive::RustcEnv {
+                key: "KEY".into(),
+                value: "VALUE".into(),
+            },
+            Directive::RustcLinkArg {
+                scope: LinkArgScope::All,
+                arg: "ARG".into(),
+            },
+            Directive::RustcLinkArg {
+                scope: LinkArgScope::Bin("BIN".into()),
+                arg: "ARG".into(),
+            },
+            Directive::RustcLinkArg {
+                scope: LinkArgScope::Bins,
+                arg: "ARG".into(),
+            },
+            Directive::RustcLinkArg {
+                scope: LinkArgScope::Tests,
+                arg: "ARG".into(),
+            },
+            Directive::RustcLinkArg {
+                scope: LinkArgScope::Examples,
+                arg: "ARG".into(),
+            },
+            Directive::RustcLinkArg {
+                scope: LinkArgScope::Benches,
+                arg: "ARG".into(),
+            },
+            Directive::RustcLinkArg {
+                scope: LinkArgScope::Cdylib { legacy: false },
+                arg: "ARG".into(),
+            },
+            Directive::RustcLinkArg {
+                scope: LinkArgScope::Cdylib { legacy: true },
+                arg: "ARG".into(),
+            },
+            Directive::Warning("MESSAGE".into()),
+            Directive::Error("MESSAGE".into()),
+        ];
+
+        let expected = crate::capture_output(|output| {
+            crate::pair!(to: output, "KEY", "VALUE");
+            crate::rustc_env!(to: output, "KEY", "VALUE");
+            crate::rustc_link_arg!(to: output, "ARG");
+            crate::rustc_link_arg_bin!(to: output, "BIN" => "ARG");
+            crate::rustc_link_arg_bins!(to: output, "ARG");
+            crate::rustc_link_arg_tests!(to: output, "ARG");
+            crate::rustc_link_arg_examples!(to: output, "ARG");
+            crate::rustc_link_arg_benches!(to: output, "ARG");
+            crate::rustc_link_arg_cdylib!(to: output, "ARG");
+            crate::rustc_cdylib_link_arg!(to: output, "ARG");
+            crate::warning!(to: output, "MESSAGE");
+            crate::error!(to: output, "MESSAGE");
+        });
+
+        let actual = crate::capture_output(|output| {
+            for directive in &directives {
+                directive.emit_to(output);
+            }
+        });
+
+        assert_eq!(actual, expected);
+        let parsed: Vec<Directive> = expected.lines().filter_map(Directive::parse).collect();
+        assert_eq!(parsed, directives);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Directive::parse("cargo::error=MESSAGE\n"),
+            Some(Directive::Error("MESSAGE".into()))
+        );
+        assert_eq!(
+            Directive::parse("cargo:error=MESSAGE"),
+            Some(Directive::Pair {
+                key: "error".into(),
+                value: "MESSAGE".into(),
+            })
+        );
+        assert_eq!(
+            Directive::parse("cargo::metadata=KEY=VALUE"),
+            Some(Directive::Pair {
+                key: "KEY".into(),
+                value: "VALUE".into(),
+            })
+        );
+        assert_eq!(
+            Directive::parse("cargo::rustc-cfg=FEATURE"),
+            Some(Directive::RustcCfg("FEATURE".into()))
+        );
+        assert_eq!(Directive::parse("not a directive"), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let directives = [
+            Directive::RustcCfg("has_ssl".into()),
+            Directive::RustcLinkLib {
+                name: "ssl".into(),
+                kind: Some(LinkKind::Static),
+                modifiers: Vec::new(),
+            },
+            Directive::RustcLinkSearch {
+                path: "/opt/lib".into(),
+                kind: None,
+            },
+            Directive::RustcLinkLib {
+                name: "foo".into(),
+                kind: Some(LinkKind::Static),
+                modifiers: vec![
+                    LinkModifier::WholeArchive(true),
+                    LinkModifier::Bundle(false),
+                ],
+            },
+            Directive::RustcLinkArg {
+                scope: LinkArgScope::Tests,
+                arg: "-lgcov".into(),
+            },
+            Directive::RustcLinkArg {
+                scope: LinkArgScope::Bin("server".into()),
+                arg: "-s".into(),
+            },
+            Directive::RustcLinkArg {
+                scope: LinkArgScope::Cdylib { legacy: true },
+                arg: "-s".into(),
+            },
+        ];
+        let json = serde_json::to_string(&directives).unwrap();
+        insta::assert_snapshot!(
+            json,
+            @r###"[{"key":"rustc-cfg","value":"has_ssl"},{"key":"rustc-link-lib","value":{"name":"ssl","kind":"static"}},{"key":"rustc-link-search","value":{"path":"/opt/lib","kind":null}},{"key":"rustc-link-lib","value":{"name":"foo","kind":"static","modifiers":[{"whole-archive":true},{"bundle":false}]}},{"key":"rustc-link-arg","value":{"scope":"tests","arg":"-lgcov"}},{"key":"rustc-link-arg","value":{"scope":{"bin":"server"},"arg":"-s"}},{"key":"rustc-link-arg","value":{"scope":{"cdylib":{"legacy":true}},"arg":"-s"}}]"###
+        );
+        assert_eq!(
+            serde_json::from_str::<Vec<Directive>>(&json).unwrap(),
+            directives
+        );
+    }
+
+    #[test]
+    fn kind_round_trip() {
+        for kind in [LinkKind::Dylib, LinkKind::Static, LinkKind::Framework] {
+            assert_eq!(kind.as_str().parse(), Ok(kind));
+        }
+        assert!("KIND".parse::<SearchKind>().is_err());
+    }
+}
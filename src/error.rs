@@ -0,0 +1,87 @@
+/// Tells Cargo to print the formatted `error` message and fail the build.
+///
+/// This is equivalent to:
+///
+/// ```
+/// println!("cargo::error=$args");
+/// ```
+///
+/// Unlike the other macros, this uses the `cargo::` prefix, which Cargo reads
+/// since 1.77, for packages whose `rust-version` is at least 1.77. Cargo
+/// 1.84+ shows the message as an error, while earlier versions reject the
+/// line as unknown, which fails the build as well. The build script keeps
+/// running, so that it can report every error before the build fails.
+///
+/// # Examples
+///
+/// Useful for reporting a misconfiguration that the build cannot recover
+/// from.
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// if std::env::var_os("OPENSSL_DIR").is_none() {
+///     cargo_emit::error!("`OPENSSL_DIR` must be set when cross-compiling");
+/// }
+/// # }
+/// ```
+///
+/// or, in case you want it to emit to a custom stream:
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// let mut stdout = std::io::stdout();
+/// if std::env::var_os("OPENSSL_DIR").is_none() {
+///     cargo_emit::error!(
+///         to: stdout,
+///         "`OPENSSL_DIR` must be set when cross-compiling",
+///     );
+/// }
+/// # }
+/// ```
+///
+/// Assuming you're building `my-crate`, you will see:
+///
+/// ```sh
+/// $ cargo build
+///    Compiling my-crate v0.1.0 (/path/to/my-crate)
+/// error: `OPENSSL_DIR` must be set when cross-compiling
+/// ```
+#[macro_export]
+macro_rules! error {
+    // The key starts with `:` so that `pair!` writes `cargo::error=`.
+    (to: $stream:expr, $($args:tt)+) => {
+        $crate::pair!(to: $stream, ":error", $($args)+)
+    };
+    ($($args:tt)+) => {
+        $crate::error!(to: $crate::__private::stdout(), $($args)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn single_literal() {
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                crate::error!(
+                    to: output,
+                    "ERROR"
+                );
+            }),
+            @"cargo::error=ERROR\n"
+        );
+    }
+
+    #[test]
+    fn single_formatted_by_index() {
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                crate::error!(
+                    to: output,
+                    "{}", "ERROR"
+                );
+            }),
+            @"cargo::error=ERROR\n"
+        );
+    }
+}
@@ -0,0 +1,553 @@
+//! Adding symbols to the exports of a `cdylib`.
+//!
+//! A `cdylib` exports every `#[no_mangle] pub extern` function of the crate
+//! and its dependencies, and hides everything else, including the functions
+//! of C libraries linked into it statically. [`Exports`] lists further
+//! symbols to export, such as the API of a bundled C library, writes them into
+//! `OUT_DIR` in the format of the target's linker, and passes the file with
+//! [`rustc_cdylib_link_arg!`](crate::rustc_cdylib_link_arg):
+//!
+//! | Target                | File                 | Flag                                |
+//! |-----------------------|----------------------|-------------------------------------|
+//! | ELF, such as Linux    | GNU version script   | `-Wl,--version-script=$path`        |
+//! | Apple                 | exported symbol list | `-Wl,-exported_symbols_list,$path`  |
+//!
+//! On other targets, such as Windows and WebAssembly, nothing is written and
+//! a warning is emitted.
+//!
+//! This only adds to the exports, and is meant for C symbols: it cannot make
+//! the exports of a `cdylib` a curated list, as every symbol that `rustc`
+//! exports stays exported, whether it is listed or not. See
+//! [Limitations](#limitations).
+//!
+//! # Limitations
+//!
+//! This cannot hide symbols that `rustc` exports. `rustc` passes a version
+//! script or symbol list of its own for every `cdylib`, which marks each
+//! `#[no_mangle] pub extern` function as exported, and the linker combines it
+//! with the one written here, so that a symbol is exported if either file
+//! exports it. To keep a Rust function out of the exports, do not make it
+//! `#[no_mangle] pub`.
+//!
+//! For the same reason, [version nodes](Exports::version) only apply to
+//! symbols that `rustc` does not export itself, as its own script already
+//! assigns those to its unversioned node.
+//!
+//! # Examples
+//!
+//! Exporting the API of a bundled C library:
+//!
+//! ```no_run
+//! use cargo_emit::exports::Exports;
+//!
+//! Exports::new("foo")
+//!     .version("FOO_1.0", &["foo_open", "foo_close"])
+//!     .version("FOO_1.1", &["foo_reset"])
+//!     .write()
+//!     .unwrap();
+//! ```
+//!
+//! writes `$OUT_DIR/foo.map` on Linux:
+//!
+//! ```text
+//! /* @generated by cargo-emit. Do not edit. */
+//! FOO_1.0 {
+//!   global:
+//!     foo_open;
+//!     foo_close;
+//!   local: *;
+//! };
+//!
+//! FOO_1.1 {
+//!   global:
+//!     foo_reset;
+//! } FOO_1.0;
+//! ```
+
+use crate::{constants::write_if_changed, Directive, LinkArgScope};
+use std::{
+    env,
+    ffi::OsString,
+    fmt::{self, Write as _},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// A builder for the symbols added to the exports of a `cdylib`.
+///
+/// The symbols `rustc` exports itself stay exported regardless of this list.
+#[derive(Clone, Debug)]
+pub struct Exports {
+    name: String,
+    out_dir: Option<PathBuf>,
+    nodes: Vec<Node>,
+}
+
+/// A group of symbols, with the version that they were introduced in, if
+/// any.
+#[derive(Clone, Debug)]
+struct Node {
+    version: Option<String>,
+    symbols: Vec<String>,
+}
+
+/// The format of the file that a target's linker takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    VersionScript,
+    SymbolList,
+}
+
+impl Exports {
+    /// Creates an empty list that will be written to `$OUT_DIR/$name.map` or
+    /// `$OUT_DIR/$name.exp`, depending on the target.
+    pub fn new(name: &str) -> Self {
+        Exports {
+            name: name.to_owned(),
+            out_dir: None,
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Writes into `dir` instead of `OUT_DIR`.
+    pub fn out_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.out_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Exports `symbol` without a version.
+    ///
+    /// Unversioned symbols cannot be combined with [versions](Self::version).
+    pub fn symbol(&mut self, symbol: &str) -> &mut Self {
+        match self.nodes.first_mut() {
+            Some(node) if node.version.is_none() => node.symbols.push(symbol.to_owned()),
+            _ => self.nodes.insert(
+                0,
+                Node {
+                    version: None,
+                    symbols: vec![symbol.to_owned()],
+                },
+            ),
+        }
+        self
+    }
+
+    /// Exports each of `symbols` without a version.
+    pub fn symbols<I>(&mut self, symbols: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        for symbol in symbols {
+            self.symbol(symbol.as_ref());
+        }
+        self
+    }
+
+    /// Exports `symbols` in the version node `version`, which inherits from
+    /// the version added before it.
+    ///
+    /// The node only takes effect for symbols that `rustc` does not export
+    /// itself; see the [module documentation](mod@crate::exports).
+    pub fn version<S: AsRef<str>>(&mut self, version: &str, symbols: &[S]) -> &mut Self {
+        self.nodes.push(Node {
+            version: Some(version.to_owned()),
+            symbols: symbols.iter().map(|s| s.as_ref().to_owned()).collect(),
+        });
+        self
+    }
+
+    /// Returns the GNU version script for the symbols.
+    pub fn version_script(&self) -> String {
+        let mut script = String::from("/* @generated by cargo-emit. Do not edit. */\n");
+        let mut previous: Option<&str> = None;
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i > 0 {
+                script.push('\n');
+            }
+            match &node.version {
+                Some(version) => {
+                    let _ = writeln!(script, "{} {{", version);
+                }
+                None => script.push_str("{\n"),
+            }
+            script.push_str("  global:\n");
+            for symbol in &node.symbols {
+                let _ = writeln!(script, "    {};", symbol);
+            }
+            if i == 0 {
+                script.push_str("  local: *;\n");
+            }
+            match previous {
+                Some(previous) => {
+                    let _ = writeln!(script, "}} {};", previous);
+                }
+                None => script.push_str("};\n"),
+            }
+            previous = node.version.as_deref();
+        }
+        script
+    }
+
+    /// Returns the exported symbol list for Apple's linker, in which symbols
+    /// have a leading `_`.
+    pub fn symbol_list(&self) -> String {
+        let mut list = String::new();
+        for node in &self.nodes {
+            for symbol in &node.symbols {
+                let _ = writeln!(list, "_{}", symbol);
+            }
+        }
+        list
+    }
+
+    /// Writes the file for the target if its contents changed, then emits the
+    /// link argument, or a warning if the target is not supported.
+    ///
+    /// Returns the path of the file, if one was written.
+    pub fn write(&self) -> io::Result<Option<PathBuf>> {
+        let (path, directives) = self.write_with(|key| env::var_os(key))?;
+        for directive in directives {
+            directive.emit();
+        }
+        Ok(path)
+    }
+
+    /// Writes the file for the target if its contents changed, then emits the
+    /// link argument, or a warning if the target is not supported, to
+    /// `stream`.
+    ///
+    /// Returns the path of the file, if one was written.
+    pub fn write_to<W: fmt::Write + ?Sized>(&self, stream: &mut W) -> io::Result<Option<PathBuf>> {
+        let (path, directives) = self.write_with(|key| env::var_os(key))?;
+        for directive in directives {
+            directive.emit_to(stream);
+        }
+        Ok(path)
+    }
+
+    fn write_with<F>(&self, var: F) -> io::Result<(Option<PathBuf>, Vec<Directive>)>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        self.check()
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidInput, message))?;
+
+        let format = match Format::for_target(&var) {
+            Some(format) => format,
+            None => {
+                let os = var("CARGO_CFG_TARGET_OS")
+                    .and_then(|os| os.into_string().ok())
+                    .unwrap_or_else(|| env::consts::OS.to_owned());
+                let warning = Directive::Warning(format!(
+                    "adding to the exports of `{}` is not supported on target OS `{}`",
+                    self.name, os
+                ));
+                return Ok((None, vec![warning]));
+            }
+        };
+
+        let dir = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => var("OUT_DIR")
+                .map(PathBuf::from)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is not set"))?,
+        };
+        let (path, contents, arg) = match format {
+            Format::VersionScript => {
+                let path = dir.join(format!("{}.map", self.name));
+                let arg = format!("-Wl,--version-script={}", path.display());
+                (path, self.version_script(), arg)
+            }
+            Format::SymbolList => {
+                let path = dir.join(format!("{}.exp", self.name));
+                let arg = format!("-Wl,-exported_symbols_list,{}", path.display());
+                (path, self.symbol_list(), arg)
+            }
+        };
+        write_if_changed(&path, contents.as_bytes())?;
+        let scope = LinkArgScope::Cdylib { legacy: true };
+        Ok((Some(path), vec![Directive::RustcLinkArg { scope, arg }]))
+    }
+
+    /// Returns a description of the first problem with the symbols.
+    fn check(&self) -> Result<(), String> {
+        let versioned = self.nodes.iter().filter(|node| node.version.is_some());
+        if self.nodes.len() > 1 && versioned.count() < self.nodes.len() {
+            return Err("unversioned symbols cannot be combined with versions".to_owned());
+        }
+        for (i, node) in self.nodes.iter().enumerate() {
+            if let Some(version) = &node.version {
+                if !is_name(version) {
+                    return Err(format!("`{}` is not a valid version name", version));
+                }
+                if self.nodes[..i]
+                    .iter()
+                    .any(|node| node.version.as_ref() == Some(version))
+                {
+                    return Err(format!("version `{}` is defined twice", version));
+                }
+            }
+            for symbol in &node.symbols {
+                if !is_name(symbol) {
+                    return Err(format!("`{}` is not a valid symbol name", symbol));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Format {
+    /// Determines the format from the `CARGO_CFG_TARGET_*` variables Cargo
+    /// sets for build scripts, falling back to the host outside of one.
+    fn for_target<F>(var: &F) -> Option<Format>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let string = |key: &str| var(key).and_then(|value| value.into_string().ok());
+        let vendor = string("CARGO_CFG_TARGET_VENDOR").unwrap_or_else(|| {
+            let vendor = if cfg!(target_vendor = "apple") {
+                "apple"
+            } else {
+                ""
+            };
+            vendor.to_owned()
+        });
+        let family = string("CARGO_CFG_TARGET_FAMILY").unwrap_or_else(|| {
+            let family = if cfg!(unix) { "unix" } else { "" };
+            family.to_owned()
+        });
+        let is_family = |name: &str| family.split(',').any(|family| family == name);
+
+        if vendor == "apple" {
+            Some(Format::SymbolList)
+        } else if is_family("unix") && !is_family("wasm") {
+            Some(Format::VersionScript)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns whether `name` can be written in a version script or symbol list
+/// as is.
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | '@'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process::Command};
+
+    fn target(vendor: &'static str, family: &'static str) -> impl Fn(&str) -> Option<OsString> {
+        move |key| match key {
+            "CARGO_CFG_TARGET_OS" => Some("windows".into()),
+            "CARGO_CFG_TARGET_VENDOR" => Some(vendor.into()),
+            "CARGO_CFG_TARGET_FAMILY" => Some(family.into()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn version_script() {
+        insta::assert_snapshot!(
+            Exports::new("foo").symbols(["foo_open", "foo_close"]).version_script(),
+            @r###"
+        /* @generated by cargo-emit. Do not edit. */
+        {
+          global:
+            foo_open;
+            foo_close;
+          local: *;
+        };
+        "###
+        );
+        insta::assert_snapshot!(
+            Exports::new("foo")
+                .version("FOO_1.0", &["foo_open", "foo_close"])
+                .version("FOO_1.1", &["foo_reset"])
+                .version("FOO_2.0", &["foo_open2"])
+                .version_script(),
+            @r###"
+        /* @generated by cargo-emit. Do not edit. */
+        FOO_1.0 {
+          global:
+            foo_open;
+            foo_close;
+          local: *;
+        };
+
+        FOO_1.1 {
+          global:
+            foo_reset;
+        } FOO_1.0;
+
+        FOO_2.0 {
+          global:
+            foo_open2;
+        } FOO_1.1;
+        "###
+        );
+    }
+
+    #[test]
+    fn write() {
+        let dir = crate::temp_dir("exports");
+        let mut exports = Exports::new("foo");
+        exports
+            .out_dir(&dir)
+            .version("FOO_1.0", &["foo_open"])
+            .version("FOO_1.1", &["foo_reset"]);
+
+        let (path, directives) = exports.write_with(target("unknown", "unix")).unwrap();
+        let path = path.unwrap();
+        assert_eq!(path, dir.join("foo.map"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), exports.version_script());
+        assert_eq!(
+            directives,
+            [Directive::RustcLinkArg {
+                scope: LinkArgScope::Cdylib { legacy: true },
+                arg: format!("-Wl,--version-script={}", path.display()),
+            }]
+        );
+
+        let (path, directives) = exports.write_with(target("apple", "unix")).unwrap();
+        let path = path.unwrap();
+        assert_eq!(path, dir.join("foo.exp"));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "_foo_open\n_foo_reset\n"
+        );
+        assert_eq!(
+            directives,
+            [Directive::RustcLinkArg {
+                scope: LinkArgScope::Cdylib { legacy: true },
+                arg: format!("-Wl,-exported_symbols_list,{}", path.display()),
+            }]
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        let (path, directives) = Exports::new("foo")
+            .symbol("foo_open")
+            .write_with(target("pc", "windows"))
+            .unwrap();
+        assert_eq!(path, None);
+        insta::assert_snapshot!(
+            directives[0],
+            @"cargo:warning=adding to the exports of `foo` is not supported on target OS `windows`"
+        );
+        assert_eq!(Format::for_target(&target("unknown", "unix,wasm")), None);
+    }
+
+    #[test]
+    fn check() {
+        let error = |exports: &mut Exports| exports.check().unwrap_err();
+        insta::assert_snapshot!(
+            error(Exports::new("foo").version("FOO_1.0", &["a"]).symbol("b")),
+            @"unversioned symbols cannot be combined with versions"
+        );
+        insta::assert_snapshot!(
+            error(Exports::new("foo").version("FOO_1.0", &["a"]).version("FOO_1.0", &["b"])),
+            @"version `FOO_1.0` is defined twice"
+        );
+        insta::assert_snapshot!(
+            error(Exports::new("foo").symbols(["a", "b; local: *"])),
+            @"`b; local: *` is not a valid symbol name"
+        );
+        assert!(Exports::new("foo").symbols(["a", "b"]).check().is_ok());
+    }
+
+    /// Links a `cdylib` with a bundled C library and returns its dynamic
+    /// symbols, or `None` if the tools are not installed.
+    fn link(dir: &Path, exports: &mut Exports) -> Option<String> {
+        let run = |command: &mut Command| {
+            let output = command.current_dir(dir).output().ok()?;
+            assert!(
+                output.status.success(),
+                "{:?} failed:\n{}",
+                command,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            Some(output)
+        };
+        fs::write(
+            dir.join("c.c"),
+            "void c_api(void) {}\nvoid c_internal(void) {}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("lib.rs"),
+            "extern \"C\" { fn c_api(); }\n\
+             #[no_mangle] pub extern \"C\" fn foo_open() { unsafe { c_api() } }\n\
+             #[no_mangle] pub extern \"C\" fn foo_hidden() {}\n",
+        )
+        .unwrap();
+        run(Command::new("cc").args(["-c", "-fPIC", "c.c", "-o", "c.o"]))?;
+        run(Command::new("ar").args(["rcs", "libcpart.a", "c.o"]))?;
+
+        let (_, directives) = exports
+            .out_dir(dir)
+            .write_with(target("unknown", "unix"))
+            .unwrap();
+        let mut rustc = Command::new(env::var_os("RUSTC").unwrap_or_else(|| "rustc".into()));
+        rustc.args([
+            "--crate-type",
+            "cdylib",
+            "-L",
+            ".",
+            "-l",
+            "static:+whole-archive=cpart",
+        ]);
+        for directive in directives {
+            if let Directive::RustcLinkArg {
+                scope: LinkArgScope::Cdylib { .. },
+                arg,
+            } = directive
+            {
+                rustc.arg(format!("-Clink-arg={}", arg));
+            }
+        }
+        run(rustc.args(["lib.rs", "-o", "libfoo.so"]))?;
+        let nm = run(Command::new("nm").args(["-D", "--defined-only", "libfoo.so"]))?;
+        let mut symbols: Vec<String> = String::from_utf8_lossy(&nm.stdout)
+            .lines()
+            .filter_map(|line| line.split_whitespace().nth(2))
+            .filter(|symbol| {
+                ["c_", "foo_"]
+                    .iter()
+                    .any(|prefix| symbol.starts_with(prefix))
+            })
+            .map(ToOwned::to_owned)
+            .collect();
+        symbols.sort();
+        Some(symbols.join(" "))
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn linked() {
+        let dir = crate::temp_dir("exports-linked");
+        let symbols = match link(&dir, Exports::new("foo").symbol("c_api")) {
+            Some(symbols) => symbols,
+            None => return,
+        };
+        // `foo_hidden` stays exported, as `rustc` exports it itself.
+        assert_eq!(symbols, "c_api foo_hidden foo_open");
+
+        let symbols = link(
+            &dir,
+            Exports::new("foo").version("FOO_1.0", &["c_api", "foo_open"]),
+        );
+        assert_eq!(
+            symbols.as_deref(),
+            Some("c_api@@FOO_1.0 foo_hidden foo_open")
+        );
+    }
+}
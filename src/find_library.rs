@@ -0,0 +1,467 @@
+//! Finding a prebuilt native library and deciding how to link it.
+//!
+//! [`find_library`] looks for `libfoo.a`, `libfoo.so` and friends using the
+//! naming convention of the target being built, picks between static and
+//! dynamic linking according to a [`Policy`], and returns a [`Library`] that
+//! can emit the matching [`rustc_link_search!`](crate::rustc_link_search) and
+//! [`rustc_link_lib!`](crate::rustc_link_lib) lines.
+//!
+//! The policy can be overridden by whoever runs the build through the
+//! `$NAME_STATIC` environment variable, where `$NAME` is the library name in
+//! uppercase with `-` and `.` replaced by `_`:
+//!
+//! - `FOO_STATIC=1` (or any other value but `0`) prefers the static library.
+//! - `FOO_STATIC=0` prefers the dynamic library.
+//!
+//! On MSVC, `foo.lib` may be a static library or the import library of a
+//! DLL, which the linker handles alike, so its kind is left to the compiler.
+//!
+//! When no directories are given, `$NAME_PREFIX/lib` and `$NAME_PREFIX/lib64`
+//! are searched. Cargo is told to rerun the build script if either variable
+//! changes.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! let library = cargo_emit::find_library("foo")
+//!     .prefix("/opt/foo")
+//!     .dir("/usr/local/lib")
+//!     .find()
+//!     .unwrap();
+//!
+//! library.emit();
+//! ```
+
+use crate::{Directive, LinkKind, SearchKind};
+use std::{
+    env, error,
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Starts looking for the native library `name`, as it would be passed to
+/// `-l`.
+///
+/// See the [module documentation](mod@crate::find_library) for details.
+pub fn find_library(name: &str) -> FindLibrary {
+    FindLibrary {
+        name: name.to_owned(),
+        dirs: Vec::new(),
+        policy: Policy::PreferDynamic,
+    }
+}
+
+/// Whether to prefer a static or a dynamic library when both are found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Policy {
+    /// Link `libfoo.a` if it exists, falling back to the dynamic library.
+    PreferStatic,
+    /// Link `libfoo.so` if it exists, falling back to the static library.
+    PreferDynamic,
+}
+
+/// A search for a native library, created by [`find_library`].
+#[derive(Clone, Debug)]
+pub struct FindLibrary {
+    name: String,
+    dirs: Vec<PathBuf>,
+    policy: Policy,
+}
+
+impl FindLibrary {
+    /// Searches `dir`, after any directories added before it.
+    pub fn dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.dirs.push(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Searches `prefix/lib` and `prefix/lib64`, after any directories added
+    /// before them.
+    pub fn prefix<P: AsRef<Path>>(&mut self, prefix: P) -> &mut Self {
+        let prefix = prefix.as_ref();
+        self.dirs.push(prefix.join("lib"));
+        self.dirs.push(prefix.join("lib64"));
+        self
+    }
+
+    /// Sets the policy used when `$NAME_STATIC` is not set.
+    ///
+    /// Defaults to [`Policy::PreferDynamic`].
+    pub fn policy(&mut self, policy: Policy) -> &mut Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Searches for the library.
+    pub fn find(&self) -> Result<Library, Error> {
+        self.find_with(|key| env::var_os(key))
+    }
+
+    fn find_with<F>(&self, var: F) -> Result<Library, Error>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let static_var = env_var_name(&self.name, "STATIC");
+        let prefix_var = env_var_name(&self.name, "PREFIX");
+
+        let policy = match var(&static_var) {
+            Some(value) if value == "0" => Policy::PreferDynamic,
+            Some(_) => Policy::PreferStatic,
+            None => self.policy,
+        };
+
+        let mut dirs = self.dirs.clone();
+        if dirs.is_empty() {
+            if let Some(prefix) = var(&prefix_var) {
+                let prefix = PathBuf::from(prefix);
+                dirs.push(prefix.join("lib"));
+                dirs.push(prefix.join("lib64"));
+            }
+        }
+
+        let convention = Convention::for_target(&var);
+        let kinds = match policy {
+            Policy::PreferStatic => [LinkKind::Static, LinkKind::Dylib],
+            Policy::PreferDynamic => [LinkKind::Dylib, LinkKind::Static],
+        };
+
+        for &kind in &kinds {
+            for dir in &dirs {
+                for (file_name, kind) in convention.file_names(&self.name, kind) {
+                    let path = dir.join(file_name);
+                    if path.is_file() {
+                        return Ok(Library {
+                            name: self.name.clone(),
+                            dir: dir.clone(),
+                            path,
+                            kind,
+                            env_vars: vec![static_var, prefix_var],
+                        });
+                    }
+                }
+            }
+        }
+
+        Err(Error::NotFound {
+            name: self.name.clone(),
+            searched: dirs,
+        })
+    }
+}
+
+/// A native library found by [`FindLibrary::find`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Library {
+    /// The name of the library, as passed to `-l`.
+    pub name: String,
+    /// The directory the library was found in.
+    pub dir: PathBuf,
+    /// The path of the library file itself.
+    pub path: PathBuf,
+    /// Whether the library file is static or dynamic, or `None` if its name
+    /// does not tell, like `foo.lib` on MSVC.
+    pub kind: Option<LinkKind>,
+    /// Every environment variable that was consulted.
+    pub env_vars: Vec<String>,
+}
+
+impl Library {
+    /// Returns the directives needed to link this library, in order.
+    pub fn directives(&self) -> Vec<Directive> {
+        let mut directives: Vec<Directive> = self
+            .env_vars
+            .iter()
+            .map(|var| Directive::RerunIfEnvChanged(var.clone()))
+            .collect();
+        directives.push(Directive::RustcLinkSearch {
+            path: self.dir.display().to_string(),
+            kind: Some(SearchKind::Native),
+        });
+        directives.push(Directive::RustcLinkLib {
+            name: self.name.clone(),
+            kind: self.kind,
+            modifiers: Vec::new(),
+        });
+        directives
+    }
+
+    /// Emits the directives needed to link this library to Cargo.
+    pub fn emit(&self) {
+        for directive in self.directives() {
+            directive.emit();
+        }
+    }
+
+    /// Emits the directives needed to link this library to `stream`.
+    pub fn emit_to<W: fmt::Write + ?Sized>(&self, stream: &mut W) {
+        for directive in self.directives() {
+            directive.emit_to(stream);
+        }
+    }
+}
+
+/// The error returned by [`FindLibrary::find`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Neither a static nor a dynamic library was found.
+    NotFound {
+        /// The library that was searched for.
+        name: String,
+        /// The directories that were searched.
+        searched: Vec<PathBuf>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFound { name, searched } => {
+                write!(f, "library `{}` was not found", name)?;
+                if searched.is_empty() {
+                    return f.write_str(" since no directories were given");
+                }
+                for (i, dir) in searched.iter().enumerate() {
+                    let sep = if i == 0 { " in " } else { ", " };
+                    write!(f, "{}{}", sep, dir.display())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// How library files are named on a target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Convention {
+    Msvc,
+    WindowsGnu,
+    Apple,
+    Unix,
+}
+
+impl Convention {
+    /// Determines the convention from the `CARGO_CFG_TARGET_*` variables Cargo
+    /// sets for build scripts, falling back to the host outside of one.
+    fn for_target<F>(var: &F) -> Convention
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let os = var("CARGO_CFG_TARGET_OS");
+        let os = os
+            .as_ref()
+            .and_then(|os| os.to_str())
+            .unwrap_or(env::consts::OS);
+        let target_env = var("CARGO_CFG_TARGET_ENV");
+        let target_env = target_env.as_ref().and_then(|env| env.to_str()).unwrap_or(
+            if cfg!(target_env = "msvc") {
+                "msvc"
+            } else {
+                ""
+            },
+        );
+
+        match (os, target_env) {
+            ("windows", "msvc") => Convention::Msvc,
+            ("windows", _) => Convention::WindowsGnu,
+            ("macos", _) | ("ios", _) | ("tvos", _) | ("watchos", _) | ("visionos", _) => {
+                Convention::Apple
+            }
+            _ => Convention::Unix,
+        }
+    }
+
+    /// Returns the file names a library of `kind` may have, most common first,
+    /// with the kind each name implies.
+    fn file_names(self, name: &str, kind: LinkKind) -> Vec<(String, Option<LinkKind>)> {
+        let patterns: &[(&str, &str)] = match (self, kind) {
+            // `libfoo.lib` is not listed, as `rustc` would look for `foo.lib`.
+            (Convention::Msvc, LinkKind::Static) => &[("", ".lib")],
+            (Convention::Msvc, _) => &[("", ".dll.lib"), ("", ".lib")],
+            (Convention::WindowsGnu, LinkKind::Static) => &[("lib", ".a")],
+            (Convention::WindowsGnu, _) => &[("lib", ".dll.a"), ("", ".dll")],
+            (Convention::Apple, LinkKind::Static) => &[("lib", ".a")],
+            (Convention::Apple, _) => &[("lib", ".dylib"), ("lib", ".tbd")],
+            (Convention::Unix, LinkKind::Static) => &[("lib", ".a")],
+            (Convention::Unix, _) => &[("lib", ".so")],
+        };
+        patterns
+            .iter()
+            .map(|&(prefix, suffix)| {
+                // A bare `foo.lib` is either a static or an import library.
+                let implied = match (self, prefix, suffix) {
+                    (Convention::Msvc, "", ".lib") => None,
+                    _ => Some(kind),
+                };
+                (format!("{}{}{}", prefix, name, suffix), implied)
+            })
+            .collect()
+    }
+}
+
+/// Returns the conventional environment variable for `name`, such as
+/// `FOO_BAR_STATIC` for `foo-bar`.
+pub(crate) fn env_var_name(name: &str, suffix: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '-' | '.' => '_',
+            c => c.to_ascii_uppercase(),
+        })
+        .collect();
+    format!("{}_{}", name, suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn vars<'a>(pairs: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<OsString> + 'a {
+        move |key| {
+            pairs
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| OsString::from(v))
+        }
+    }
+
+    const LINUX: [(&str, &str); 2] = [
+        ("CARGO_CFG_TARGET_OS", "linux"),
+        ("CARGO_CFG_TARGET_ENV", "gnu"),
+    ];
+
+    #[test]
+    fn prefers_dynamic() {
+        let dir = crate::temp_dir("find-library-dynamic");
+        fs::write(dir.join("libfoo.a"), "").unwrap();
+        fs::write(dir.join("libfoo.so"), "").unwrap();
+
+        let library = find_library("foo")
+            .dir(&dir)
+            .find_with(vars(&LINUX))
+            .unwrap();
+        assert_eq!(library.kind, Some(LinkKind::Dylib));
+
+        insta::assert_snapshot!(
+            crate::capture_output(|output| library.emit_to(output))
+                .replace(&dir.display().to_string(), "$DIR"),
+            @"cargo:rerun-if-env-changed=FOO_STATIC\n\
+              cargo:rerun-if-env-changed=FOO_PREFIX\n\
+              cargo:rustc-link-search=native=$DIR\n\
+              cargo:rustc-link-lib=dylib=foo\n"
+        );
+    }
+
+    #[test]
+    fn static_env_var() {
+        let dir = crate::temp_dir("find-library-static");
+        fs::write(dir.join("libfoo-bar.a"), "").unwrap();
+        fs::write(dir.join("libfoo-bar.so"), "").unwrap();
+
+        let find = |policy, value| {
+            let mut pairs = LINUX.to_vec();
+            if let Some(value) = value {
+                pairs.push(("FOO_BAR_STATIC", value));
+            }
+            find_library("foo-bar")
+                .dir(&dir)
+                .policy(policy)
+                .find_with(vars(&pairs))
+                .unwrap()
+                .kind
+        };
+
+        assert_eq!(find(Policy::PreferStatic, None), Some(LinkKind::Static));
+        assert_eq!(
+            find(Policy::PreferDynamic, Some("1")),
+            Some(LinkKind::Static)
+        );
+        assert_eq!(find(Policy::PreferStatic, Some("0")), Some(LinkKind::Dylib));
+    }
+
+    #[test]
+    fn prefix_env_var() {
+        let prefix = crate::temp_dir("find-library-prefix");
+        fs::create_dir(prefix.join("lib64")).unwrap();
+        fs::write(prefix.join("lib64").join("libfoo.a"), "").unwrap();
+
+        let mut pairs = LINUX.to_vec();
+        let prefix_str = prefix.display().to_string();
+        pairs.push(("FOO_PREFIX", &prefix_str));
+
+        let library = find_library("foo").find_with(vars(&pairs)).unwrap();
+        assert_eq!(library.kind, Some(LinkKind::Static));
+        assert_eq!(library.dir, prefix.join("lib64"));
+    }
+
+    #[test]
+    fn naming_conventions() {
+        let dir = crate::temp_dir("find-library-conventions");
+        fs::write(dir.join("foo.lib"), "").unwrap();
+        fs::write(dir.join("libbar.dylib"), "").unwrap();
+
+        let msvc = [
+            ("CARGO_CFG_TARGET_OS", "windows"),
+            ("CARGO_CFG_TARGET_ENV", "msvc"),
+        ];
+        let library = find_library("foo")
+            .dir(&dir)
+            .find_with(vars(&msvc))
+            .unwrap();
+        assert_eq!(library.kind, None);
+
+        let macos = [
+            ("CARGO_CFG_TARGET_OS", "macos"),
+            ("CARGO_CFG_TARGET_ENV", ""),
+        ];
+        let library = find_library("bar")
+            .dir(&dir)
+            .find_with(vars(&macos))
+            .unwrap();
+        assert_eq!(library.kind, Some(LinkKind::Dylib));
+
+        let error = find_library("bar")
+            .dir(&dir)
+            .find_with(vars(&LINUX))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("library `bar` was not found in {}", dir.display())
+        );
+    }
+
+    #[test]
+    fn msvc_import_library() {
+        let dir = crate::temp_dir("find-library-import");
+        fs::write(dir.join("foo.lib"), "").unwrap();
+        fs::write(dir.join("foo.dll"), "").unwrap();
+        fs::write(dir.join("bar.dll.lib"), "").unwrap();
+        fs::write(dir.join("libbaz.lib"), "").unwrap();
+
+        let msvc = [
+            ("CARGO_CFG_TARGET_OS", "windows"),
+            ("CARGO_CFG_TARGET_ENV", "msvc"),
+        ];
+        let find = |name| find_library(name).dir(&dir).find_with(vars(&msvc));
+
+        let library = find("foo").unwrap();
+        assert_eq!(library.kind, None);
+        assert_eq!(library.path, dir.join("foo.lib"));
+        insta::assert_snapshot!(
+            crate::capture_output(|output| library.emit_to(output))
+                .replace(&dir.display().to_string(), "$DIR"),
+            @"cargo:rerun-if-env-changed=FOO_STATIC\n\
+              cargo:rerun-if-env-changed=FOO_PREFIX\n\
+              cargo:rustc-link-search=native=$DIR\n\
+              cargo:rustc-link-lib=foo\n"
+        );
+
+        assert_eq!(find("bar").unwrap().kind, Some(LinkKind::Dylib));
+        // `rustc` would look for `baz.lib`, so `libbaz.lib` cannot be linked
+        // by name.
+        assert!(find("baz").is_err());
+    }
+}
@@ -0,0 +1,341 @@
+//! Translating raw compiler/linker flag strings into typed directives.
+//!
+//! Tools like `llvm-config --ldflags --libs` or `sdl2-config --libs` print a
+//! single string of mixed flags, which
+//! [`rustc_flags!`](crate::rustc_flags) can only pass through if it consists
+//! solely of `-l` and `-L`. [`directives`] instead splits such a string the
+//! way a shell would and maps each flag to the directive Cargo understands:
+//!
+//! | Flag                            | Directive                              |
+//! |---------------------------------|----------------------------------------|
+//! | `-L $path`                      | `rustc-link-search=native=$path`       |
+//! | `-F $path`                      | `rustc-link-search=framework=$path`    |
+//! | `-l $name`                      | `rustc-link-lib=$name`                 |
+//! | `-l $name` after `-Wl,-Bstatic` | `rustc-link-lib=static=$name`          |
+//! | `-framework $name`              | `rustc-link-lib=framework=$name`       |
+//! | `-I`, `-D`, `-U`, `-isystem`    | none, since they do not affect linking |
+//! | anything else                   | `rustc-link-arg=$flag`                 |
+//!
+//! Flags passed through as `rustc-link-arg` that are not known linker inputs
+//! are also reported with a `warning`, so that surprises show up in the
+//! `cargo build` output.
+//!
+//! # Examples
+//!
+//! ```
+//! let ldflags = "-L/usr/lib/llvm/lib -lLLVM-17 -Wl,-Bstatic -lz -Wl,-Bdynamic";
+//! cargo_emit::flags::emit(ldflags);
+//! ```
+//!
+//! or, in case you want it to emit to a custom stream:
+//!
+//! ```
+//! let mut output = String::new();
+//! cargo_emit::flags::emit_to("-lSDL2 -framework Cocoa", &mut output);
+//!
+//! assert_eq!(
+//!     output,
+//!     "cargo:rustc-link-lib=SDL2\n\
+//!      cargo:rustc-link-lib=framework=Cocoa\n",
+//! );
+//! ```
+
+use crate::{Directive, LinkArgScope, LinkKind, SearchKind};
+use std::fmt;
+
+/// Driver flags that are passed to the linker as-is without a warning.
+const KNOWN_LINK_FLAGS: [&str; 8] = [
+    "-pthread",
+    "-rdynamic",
+    "-static",
+    "-static-libgcc",
+    "-static-libstdc++",
+    "-nostdlib",
+    "-nodefaultlibs",
+    "-nostartfiles",
+];
+
+/// Preprocessor flags that take a value and have no effect on linking.
+const PREPROCESSOR_FLAGS: [&str; 4] = ["-isystem", "-I", "-D", "-U"];
+
+/// Translates `flags` into the directives Cargo needs, in order.
+pub fn directives(flags: &str) -> Vec<Directive> {
+    let words = split(flags);
+    let mut words = words.iter();
+    let mut directives = Vec::new();
+    let mut statik = false;
+
+    while let Some(word) = words.next() {
+        if word == "-framework" {
+            match words.next() {
+                Some(name) => directives.push(Directive::RustcLinkLib {
+                    name: name.clone(),
+                    kind: Some(LinkKind::Framework),
+                    modifiers: Vec::new(),
+                }),
+                None => directives.push(missing_value(word)),
+            }
+        } else if word == "-Xlinker" {
+            match words.next() {
+                Some(arg) => linker_args(&[arg.as_str()], &mut statik, &mut directives),
+                None => directives.push(missing_value(word)),
+            }
+        } else if let Some(args) = word.strip_prefix("-Wl,") {
+            let args: Vec<&str> = args.split(',').collect();
+            linker_args(&args, &mut statik, &mut directives);
+        } else if word.starts_with("-L") {
+            if let Some(path) = flag_value(word, "-L", &mut words) {
+                directives.push(search(path, SearchKind::Native));
+            } else {
+                directives.push(missing_value(word));
+            }
+        } else if word.starts_with("-F") {
+            if let Some(path) = flag_value(word, "-F", &mut words) {
+                directives.push(search(path, SearchKind::Framework));
+            } else {
+                directives.push(missing_value(word));
+            }
+        } else if word.starts_with("-l:") {
+            // `-l:file` names a file verbatim, which only the linker
+            // understands.
+            directives.push(Directive::RustcLinkArg {
+                scope: LinkArgScope::All,
+                arg: word.clone(),
+            });
+        } else if word.starts_with("-l") {
+            if let Some(name) = flag_value(word, "-l", &mut words) {
+                directives.push(Directive::RustcLinkLib {
+                    name,
+                    kind: if statik { Some(LinkKind::Static) } else { None },
+                    modifiers: Vec::new(),
+                });
+            } else {
+                directives.push(missing_value(word));
+            }
+        } else if let Some(prefix) = PREPROCESSOR_FLAGS.iter().find(|p| word.starts_with(*p)) {
+            flag_value(word, prefix, &mut words);
+        } else {
+            if !is_known_link_input(word) {
+                directives.push(Directive::Warning(format!(
+                    "unrecognized flag `{}` is passed to the linker as-is",
+                    word
+                )));
+            }
+            directives.push(Directive::RustcLinkArg {
+                scope: LinkArgScope::All,
+                arg: word.clone(),
+            });
+        }
+    }
+
+    directives
+}
+
+/// Translates `flags` and emits the resulting directives to Cargo.
+pub fn emit(flags: &str) {
+    for directive in directives(flags) {
+        directive.emit();
+    }
+}
+
+/// Translates `flags` and emits the resulting directives to `stream`.
+pub fn emit_to<W: fmt::Write + ?Sized>(flags: &str, stream: &mut W) {
+    for directive in directives(flags) {
+        directive.emit_to(stream);
+    }
+}
+
+/// Handles arguments meant for the linker itself, as given by `-Wl,` or
+/// `-Xlinker`.
+///
+/// `-Bstatic` and `-Bdynamic` toggle how subsequent `-l` flags are linked, so
+/// they are turned into `static` link kinds rather than passed through.
+/// Everything else is passed through unchanged.
+fn linker_args(args: &[&str], statik: &mut bool, directives: &mut Vec<Directive>) {
+    let mut passthrough = Vec::new();
+    for &arg in args {
+        match arg {
+            "-Bstatic" | "-dn" | "-non_shared" => *statik = true,
+            "-Bdynamic" | "-dy" | "-call_shared" => *statik = false,
+            arg => passthrough.push(arg),
+        }
+    }
+    if !passthrough.is_empty() {
+        directives.push(Directive::RustcLinkArg {
+            scope: LinkArgScope::All,
+            arg: format!("-Wl,{}", passthrough.join(",")),
+        });
+    }
+}
+
+fn search(path: String, kind: SearchKind) -> Directive {
+    Directive::RustcLinkSearch {
+        path,
+        kind: Some(kind),
+    }
+}
+
+fn missing_value(flag: &str) -> Directive {
+    Directive::Warning(format!(
+        "flag `{}` is missing its value and was ignored",
+        flag
+    ))
+}
+
+/// Returns whether `word` is something linkers are known to accept, such as
+/// an object file, an archive, or a common compiler driver flag.
+fn is_known_link_input(word: &str) -> bool {
+    if !word.starts_with('-') {
+        let file_name = word.rsplit(['/', '\\']).next().unwrap_or(word);
+        return [".a", ".o", ".obj", ".lib", ".so", ".dylib", ".tbd"]
+            .iter()
+            .any(|ext| file_name.ends_with(ext))
+            || file_name.contains(".so.");
+    }
+    KNOWN_LINK_FLAGS.contains(&word) || word.starts_with("-fuse-ld=")
+}
+
+/// Splits `s` into words the way a POSIX shell would, honoring quotes and
+/// backslash escapes.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(
+///     cargo_emit::flags::split(r#"-L"/opt/my lib" -l'foo bar' a\ b"#),
+///     ["-L/opt/my lib", "-lfoo bar", "a b"],
+/// );
+/// ```
+pub fn split(s: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                word.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some(c @ '"') | Some(c @ '\\') | Some(c @ '$') => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => word.push('\\'),
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    words.extend(word);
+    words
+}
+
+/// Returns the value of `flag` if it starts with `prefix`, taking the next
+/// word if the value is not attached (e.g. `-L dir` instead of `-Ldir`).
+pub(crate) fn flag_value<'a, I>(flag: &str, prefix: &str, rest: &mut I) -> Option<String>
+where
+    I: Iterator<Item = &'a String>,
+{
+    let value = flag.strip_prefix(prefix)?;
+    if value.is_empty() {
+        rest.next().cloned()
+    } else {
+        Some(value.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn llvm_config() {
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                super::emit_to(
+                    "-L/usr/lib/llvm-17/lib -lLLVM-17 -lrt -ldl -lm -lz -ltinfo -lxml2",
+                    output,
+                );
+            }),
+            @"cargo:rustc-link-search=native=/usr/lib/llvm-17/lib\n\
+              cargo:rustc-link-lib=LLVM-17\n\
+              cargo:rustc-link-lib=rt\n\
+              cargo:rustc-link-lib=dl\n\
+              cargo:rustc-link-lib=m\n\
+              cargo:rustc-link-lib=z\n\
+              cargo:rustc-link-lib=tinfo\n\
+              cargo:rustc-link-lib=xml2\n"
+        );
+    }
+
+    #[test]
+    fn sdl2_config() {
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                super::emit_to(
+                    "-L/usr/local/lib -lSDL2 -F /Library/Frameworks -framework Cocoa \
+                     -Wl,-rpath,/usr/local/lib -pthread -D_THREAD_SAFE -I /usr/include/SDL2",
+                    output,
+                );
+            }),
+            @"cargo:rustc-link-search=native=/usr/local/lib\n\
+              cargo:rustc-link-lib=SDL2\n\
+              cargo:rustc-link-search=framework=/Library/Frameworks\n\
+              cargo:rustc-link-lib=framework=Cocoa\n\
+              cargo:rustc-link-arg=-Wl,-rpath,/usr/local/lib\n\
+              cargo:rustc-link-arg=-pthread\n"
+        );
+    }
+
+    #[test]
+    fn static_state() {
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                super::emit_to(
+                    "-lfoo -Wl,-Bstatic -lbar -Xlinker -Bdynamic -lbaz \
+                     -Wl,-Bstatic,--whole-archive -lqux",
+                    output,
+                );
+            }),
+            @"cargo:rustc-link-lib=foo\n\
+              cargo:rustc-link-lib=static=bar\n\
+              cargo:rustc-link-lib=baz\n\
+              cargo:rustc-link-arg=-Wl,--whole-archive\n\
+              cargo:rustc-link-lib=static=qux\n"
+        );
+    }
+
+    #[test]
+    fn unrecognized() {
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                super::emit_to("/opt/lib/libfoo.a libbar.so.1 -flto -l", output);
+            }),
+            @"cargo:rustc-link-arg=/opt/lib/libfoo.a\n\
+              cargo:rustc-link-arg=libbar.so.1\n\
+              cargo:warning=unrecognized flag `-flto` is passed to the linker as-is\n\
+              cargo:rustc-link-arg=-flto\n\
+              cargo:warning=flag `-l` is missing its value and was ignored\n"
+        );
+    }
+
+    #[test]
+    fn split_quotes() {
+        assert_eq!(
+            super::split(r#" -I"a \"b\"" 'c d'e  \$f "#),
+            [r#"-Ia "b""#, "c de", "$f"]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Recording emitted directives as JSON lines.
+//!
+//! Once [installed](JsonLines::install), every directive emitted by this
+//! crate's macros and types is also written to a file as one JSON object per
+//! line, in the format described in [`Directive`]. Tools can then read what a
+//! build script decided without parsing its `cargo:` output:
+//!
+//! ```text
+//! {"key":"rustc-link-search","value":{"path":"/usr/lib","kind":"native"},"location":{"file":"build.rs","line":4,"module_path":"build_script_build"}}
+//! {"key":"rustc-link-lib","value":{"name":"ssl","kind":null},"location":{"file":"build.rs","line":5,"module_path":"build_script_build"}}
+//! ```
+//!
+//! Directives emitted by a macro include the [`Location`] of the call, while
+//! those emitted with [`Directive::emit`] have no `location`.
+//!
+//! Directives are still emitted to Cargo as usual.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cargo_emit::json_lines::JsonLines;
+//!
+//! // Writes to `$OUT_DIR/cargo-emit.jsonl`.
+//! JsonLines::new().install().unwrap();
+//!
+//! cargo_emit::rustc_link_lib!("ssl");
+//! ```
+
+use crate::{Directive, Location};
+use serde::Serialize;
+use std::{
+    env,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// The name of the file in `OUT_DIR` that is written by default.
+pub const DEFAULT_FILE_NAME: &str = "cargo-emit.jsonl";
+
+/// A builder for recording emitted directives to a JSON lines file.
+#[derive(Clone, Debug, Default)]
+pub struct JsonLines {
+    path: Option<PathBuf>,
+}
+
+impl JsonLines {
+    /// Creates a builder that writes to `$OUT_DIR/cargo-emit.jsonl`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes to `path` instead.
+    pub fn path<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.path = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Creates the file, replacing any previous contents, and records every
+    /// directive emitted from now on, on any thread.
+    ///
+    /// Returns the path of the file.
+    pub fn install(&self) -> io::Result<PathBuf> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => env::var_os("OUT_DIR")
+                .map(|dir| Path::new(&dir).join(DEFAULT_FILE_NAME))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is not set"))?,
+        };
+        let file = Mutex::new(File::create(&path)?);
+        crate::output::observe(move |line, location| {
+            if let Some(json) = to_json(line, location) {
+                let mut file = file.lock().unwrap_or_else(|error| error.into_inner());
+                let _ = writeln!(file, "{}", json);
+            }
+        });
+        Ok(path)
+    }
+}
+
+/// A line of the file.
+#[derive(Serialize)]
+struct Record {
+    #[serde(flatten)]
+    directive: Directive,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<Location>,
+}
+
+/// Returns the JSON for `line`, if it is a directive.
+fn to_json(line: &str, location: Option<Location>) -> Option<String> {
+    let directive = Directive::parse(line)?;
+    serde_json::to_string(&Record {
+        directive,
+        location,
+    })
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn to_json() {
+        insta::assert_snapshot!(
+            super::to_json("cargo:rustc-link-search=native=/usr/lib", None).unwrap(),
+            @r###"{"key":"rustc-link-search","value":{"path":"/usr/lib","kind":"native"}}"###
+        );
+        insta::assert_snapshot!(
+            super::to_json(
+                "cargo:rustc-cfg=ssl",
+                Some(Location::new("build.rs", 12, "build_script_build"))
+            )
+            .unwrap(),
+            @r###"{"key":"rustc-cfg","value":"ssl","location":{"file":"build.rs","line":12,"module_path":"build_script_build"}}"###
+        );
+        assert_eq!(super::to_json("not a directive", None), None);
+    }
+
+    #[test]
+    fn install() {
+        let dir = crate::temp_dir("json-lines");
+        let path = JsonLines::new()
+            .path(dir.join("directives.jsonl"))
+            .install()
+            .unwrap();
+
+        let line = line!();
+        let output = crate::output::capture(|| {
+            crate::rustc_cfg!("json_lines_test");
+            crate::Directive::RustcEnv {
+                key: "JSON_LINES_TEST".into(),
+                value: "1".into(),
+            }
+            .emit();
+        });
+        assert_eq!(
+            output,
+            "cargo:rustc-cfg=json_lines_test\ncargo:rustc-env=JSON_LINES_TEST=1\n"
+        );
+
+        // Other tests may emit directives concurrently.
+        let contents = fs::read_to_string(path).unwrap();
+        let lines: Vec<&str> = contents
+            .lines()
+            .filter(|line| line.contains("JSON_LINES_TEST") || line.contains("json_lines_test"))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                format!(
+                    "{{\"key\":\"rustc-cfg\",\"value\":\"json_lines_test\",\
+                     \"location\":{{\"file\":\"src/json_lines.rs\",\"line\":{},\
+                     \"module_path\":\"cargo_emit::json_lines::tests\"}}}}",
+                    line + 2
+                ),
+                "{\"key\":\"rustc-env\",\"value\":{\"key\":\"JSON_LINES_TEST\",\"value\":\"1\"}}"
+                    .to_owned(),
+            ]
+        );
+    }
+}
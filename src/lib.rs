@@ -14,8 +14,8 @@
 //!         <img src="https://travis-ci.com/nvzqz/cargo-emit.svg?branch=master"
 //!              alt="Build Status">
 //!     </a>
-//!     <img src="https://img.shields.io/badge/rustc-^1.37.0-blue.svg"
-//!          alt="rustc ^1.37.0">
+//!     <img src="https://img.shields.io/badge/rustc-^1.77.0-blue.svg"
+//!          alt="rustc ^1.77.0">
 //!     <br><br>
 //! </div>
 //!
@@ -47,19 +47,82 @@
 //! and something like this to your [`build.rs`]:
 //!
 //! ```
+//! # #[cfg(feature = "std")] {
 //! # let should_warn = true;
 //! if should_warn {
 //!     cargo_emit::warning!("(C-3PO voice) We're doomed");
 //! }
+//! # }
 //! ```
 //!
 //! **Note:** This library is meant to be used with [Rust 2018 edition][2018],
 //! so that `cargo_emit::` can be used to prefix macro calls.
 //!
+//! # Features
+//!
+//! - `std` (enabled by default): emitting to stdout, and the modules that read
+//!   the environment or filesystem. Without it, the crate is `#![no_std]` and
+//!   only needs `alloc`: the macros must be given a [`core::fmt::Write`]
+//!   stream with `to:`, and [`Directive`] can be parsed and rendered but not
+//!   emitted to stdout.
+//!
+//! - `validate`: checks string literal arguments to the macros at compile
+//!   time, such as the `$kind` of [`rustc_link_lib!`], the syntax of
+//!   [`rustc_cfg!`] names, and [`rustc_env!`] keys. Mistakes are reported at
+//!   the argument:
+//!
+//!   ```text
+//!   error: unknown link kind `statc`; expected one of `dylib`, `static`, `framework`
+//!    --> build.rs:2:41
+//!     |
+//!   2 |     cargo_emit::rustc_link_lib!("ssl" => "statc");
+//!     |                                         ^^^^^^^
+//!   ```
+//!
+//!   This requires Rust 1.60+.
+//!
+//! - `log`: a [`log`](https://docs.rs/log) logger that emits records as
+//!   warnings, in the `logger` module.
+//!
+//! - `serde`: `Serialize` and `Deserialize` for [`Directive`], and with `std`,
+//!   recording every emitted directive to a JSON lines file, in the
+//!   `json_lines` module.
+//!
+//! - `tracing`: a [`tracing`](https://docs.rs/tracing) layer that emits events
+//!   as warnings and errors, or writes them to a file in `OUT_DIR`, in the
+//!   `trace` module.
+//!
+//! # Debugging
+//!
+//! Setting `CARGO_EMIT_DEBUG=1` in the environment of `cargo build` mirrors
+//! every line emitted by the macros to stderr, along with the [`Location`] of
+//! the macro call:
+//!
+//! ```text
+//! [cargo-emit] build.rs:12 in build_script_build: cargo:rustc-link-lib=static=ssl
+//! ```
+//!
+//! Cargo only shows the stderr of a build script with `-vv` or when it fails.
+//! With `CARGO_EMIT_DEBUG=log`, the lines are also written to
+//! `$OUT_DIR/cargo-emit.log`. With `CARGO_EMIT_DEBUG=warn`, each line is also
+//! followed by a warning with its location, which Cargo always shows. The
+//! warning is written to stdout even for lines written to a `to:` stream:
+//!
+//! ```text
+//! warning: my-crate@0.1.0: # emitted at build.rs:12 in build_script_build
+//! ```
+//!
+//! Since Cargo does not rerun build scripts when this variable changes, touch
+//! `build.rs` after setting it.
+//!
 //! # Compatibility
 //!
-//! This crate is compatible with Rust 1.31+ in order to use the
-//! `$crate::macro!` feature introduced in [Rust 2018][2018].
+//! This crate is compatible with Rust 1.77+, the first release whose Cargo
+//! reads `cargo::` lines, which [`error!`] relies on to fail the build. Older
+//! versions of Cargo ignore them. The newest versions of the
+//! dependencies behind the optional features may need a newer Rust, in which
+//! case Cargo 1.84+ picks older versions that fit the `rust-version` of your
+//! package when `resolver = "3"` is set.
 //!
 //! # Examples
 //!
@@ -92,7 +155,12 @@
     html_logo_url = "https://raw.githubusercontent.com/nvzqz/cargo-emit/assets/logo.svg?sanitize=true"
 )]
 #![deny(missing_docs)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
+extern crate alloc;
+
+/// `cargo::error=$message`
+mod error;
 /// `cargo:$key=$value`
 mod pair;
 /// `cargo:rerun-if-changed=$path`
@@ -109,10 +177,18 @@ mod rustc_env;
 mod rustc_flags;
 /// `cargo:rustc-link-arg=$arg`
 mod rustc_link_arg;
+/// `cargo:rustc-link-arg-benches=$arg`
+mod rustc_link_arg_benches;
 /// `cargo:rustc-link-arg-bin=$bin=$arg`
 mod rustc_link_arg_bin;
 /// `cargo:rustc-link-arg-bins=$arg`
 mod rustc_link_arg_bins;
+/// `cargo:rustc-link-arg-cdylib=$arg`
+mod rustc_link_arg_cdylib;
+/// `cargo:rustc-link-arg-examples=$arg`
+mod rustc_link_arg_examples;
+/// `cargo:rustc-link-arg-tests=$arg`
+mod rustc_link_arg_tests;
 /// `cargo:rustc-link-lib=$kind=$name`
 mod rustc_link_lib;
 /// `cargo:rustc-link-search=$kind=$path`
@@ -120,6 +196,99 @@ mod rustc_link_search;
 /// `cargo:warning=$message`
 mod warning;
 
+mod diagnostic;
+pub use diagnostic::{Diagnostic, Severity};
+
+mod directive;
+pub use directive::{
+    Directive, LinkArgScope, LinkKind, LinkModifier, Location, SearchKind, UnknownKind,
+};
+
+#[cfg(feature = "std")]
+pub mod bin_targets;
+#[cfg(feature = "std")]
+pub mod build_info;
+#[cfg(feature = "std")]
+pub mod constants;
+#[cfg(feature = "std")]
+pub mod depfile;
+#[cfg(feature = "std")]
+pub mod exports;
+#[cfg(feature = "std")]
+pub mod find_library;
+#[cfg(feature = "std")]
+pub use find_library::find_library;
+#[cfg(feature = "std")]
+pub mod flags;
+#[cfg(all(feature = "serde", feature = "std"))]
+pub mod json_lines;
+#[cfg(feature = "std")]
+pub mod link_options;
+#[cfg(feature = "std")]
+pub mod link_plan;
+#[cfg(feature = "std")]
+pub mod linker_script;
+#[cfg(feature = "log")]
+pub mod logger;
+#[cfg(feature = "std")]
+pub mod pkg_config;
+#[cfg(feature = "std")]
+pub mod rpath;
+#[cfg(feature = "std")]
+pub mod sys_lib;
+#[cfg(feature = "std")]
+pub mod target_env_var;
+#[cfg(feature = "std")]
+pub use target_env_var::target_env_var;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(feature = "tracing")]
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod track_content;
+#[cfg(feature = "std")]
+pub use track_content::track_content;
+
+#[cfg(feature = "std")]
+mod debug;
+#[cfg(feature = "std")]
+mod output;
+mod validate;
+
+/// Items used by the macros. Not public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use core::fmt::Write as FmtWrite;
+
+    #[cfg(feature = "std")]
+    pub use crate::output::{stdout, Emitting, Stdout};
+    #[cfg(feature = "std")]
+    pub use std::io::Write as IoWrite;
+
+    /// Stands in for `std::io::Write` in `pair!` without `std`.
+    #[cfg(not(feature = "std"))]
+    pub trait IoWrite {}
+
+    /// Stands in for `output::Emitting` without `std`, where lines are never
+    /// mirrored or recorded.
+    #[cfg(not(feature = "std"))]
+    pub struct Emitting(());
+
+    #[cfg(not(feature = "std"))]
+    impl Emitting {
+        #[inline]
+        pub fn new(_location: &crate::Location, _line: &core::fmt::Arguments) -> Self {
+            Emitting(())
+        }
+
+        #[inline]
+        pub fn annotate(&self) {}
+    }
+
+    #[cfg(feature = "validate")]
+    pub use cargo_emit_macros as validate;
+}
+
 #[cfg(test)]
 fn capture_output<F>(f: F) -> String
 where
@@ -129,3 +298,11 @@ where
     f(&mut output);
     output
 }
+
+#[cfg(all(test, feature = "std"))]
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("cargo-emit-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,510 @@
+//! Passing common options to the target's linker without knowing its syntax.
+//!
+//! Each [`LinkOption`] is rendered for the [`LinkerFlavor`] of the target,
+//! which is worked out from `CARGO_CFG_TARGET_ENV` and
+//! `CARGO_CFG_TARGET_VENDOR`, and for UEFI, whose targets use `lld-link`,
+//! `CARGO_CFG_TARGET_OS`:
+//!
+//! | Option                                   | GNU ld and lld        | ld64                  | MSVC `link.exe`  |
+//! |------------------------------------------|-----------------------|-----------------------|------------------|
+//! | [`Rpath`](LinkOption::Rpath)             | `-rpath=$path`        | `-rpath $path`        | unsupported      |
+//! | [`Soname`](LinkOption::Soname)           | `-soname=$name`       | `-install_name $name` | unsupported      |
+//! | [`AsNeeded`](LinkOption::AsNeeded)       | `--as-needed`         | `-dead_strip_dylibs`  | unsupported      |
+//! | [`GcSections`](LinkOption::GcSections)   | `--gc-sections`       | `-dead_strip`         | `/OPT:REF`       |
+//! | [`NoUndefined`](LinkOption::NoUndefined) | `--no-undefined`      | `-undefined error`    | always on        |
+//! | [`BuildId`](LinkOption::BuildId)         | `--build-id`          | always on             | unsupported      |
+//! | [`StackSize`](LinkOption::StackSize)     | `-zstack-size=$bytes` | `-stack_size $bytes`  | `/STACK:$bytes`  |
+//! | [`Entry`](LinkOption::Entry)             | `--entry=$symbol`     | `-e _$symbol`         | `/ENTRY:$symbol` |
+//!
+//! Most targets link through a compiler driver such as `cc`, or `emcc` for
+//! Emscripten, in which case the arguments are wrapped as `-Wl,$arg,...`, or
+//! as `-Xlinker $arg` pairs if an argument contains a comma. Bare-metal, UEFI
+//! and other WebAssembly targets run the linker directly. Options that the
+//! linker does not support are left out with a warning.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cargo_emit::link_options::{LinkOption, LinkOptions};
+//!
+//! LinkOptions::new()
+//!     .option(LinkOption::GcSections(true))
+//!     .option(LinkOption::AsNeeded(true))
+//!     .emit();
+//! ```
+//!
+//! emits on Linux:
+//!
+//! ```text
+//! cargo:rustc-link-arg=-Wl,--gc-sections
+//! cargo:rustc-link-arg=-Wl,--as-needed
+//! ```
+
+use crate::{Directive, LinkArgScope};
+use std::{
+    env,
+    ffi::OsString,
+    fmt::{self, Write as _},
+};
+
+/// The command-line syntax of a linker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinkerFlavor {
+    /// GNU `ld`, `gold`, and LLVM's `ld.lld` and `wasm-ld`.
+    Gnu,
+    /// Apple's `ld64`, and LLVM's `ld64.lld`.
+    Ld64,
+    /// Microsoft's `link.exe`, and LLVM's `lld-link`.
+    Msvc,
+}
+
+impl LinkerFlavor {
+    /// Determines the flavor from the `CARGO_CFG_TARGET_*` variables Cargo
+    /// sets for build scripts, falling back to the host outside of one.
+    pub fn for_target() -> Self {
+        Target::with(|key| env::var_os(key)).flavor
+    }
+}
+
+/// A linker option, rendered by [`LinkOptions`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LinkOption {
+    /// Adds a directory to the runtime library search path.
+    Rpath(String),
+    /// Sets the name that dependents of a shared library record, which is its
+    /// install name on Apple targets.
+    Soname(String),
+    /// Whether to only depend on the shared libraries whose symbols are used.
+    AsNeeded(bool),
+    /// Whether to remove unused sections.
+    GcSections(bool),
+    /// Fails the link if a shared library has undefined symbols.
+    NoUndefined,
+    /// Records a unique build ID in the binary.
+    BuildId,
+    /// Sets the stack size, in bytes, of the main thread on Windows and Apple
+    /// targets, and of the whole program on WebAssembly.
+    ///
+    /// Other ELF targets only record the size in the `PT_GNU_STACK` header.
+    /// glibc ignores it, as the main thread gets the `ulimit -s` stack, and
+    /// musl only uses it as the default for threads not spawned by
+    /// `std::thread`, which picks its own size.
+    StackSize(u64),
+    /// Sets the symbol where execution starts.
+    Entry(String),
+}
+
+impl LinkOption {
+    /// Returns the arguments for `flavor`, which may be empty if the option
+    /// is already the linker's behavior, or `None` if it is not supported.
+    pub fn args(&self, flavor: LinkerFlavor) -> Option<Vec<String>> {
+        use LinkOption::*;
+        use LinkerFlavor::*;
+
+        let args = match (self, flavor) {
+            (Rpath(path), Gnu) => vec![format!("-rpath={}", path)],
+            (Rpath(path), Ld64) => vec!["-rpath".into(), path.clone()],
+            (Soname(name), Gnu) => vec![format!("-soname={}", name)],
+            (Soname(name), Ld64) => vec!["-install_name".into(), name.clone()],
+            (AsNeeded(true), Gnu) => vec!["--as-needed".into()],
+            (AsNeeded(false), Gnu) => vec!["--no-as-needed".into()],
+            (AsNeeded(true), Ld64) => vec!["-dead_strip_dylibs".into()],
+            (GcSections(true), Gnu) => vec!["--gc-sections".into()],
+            (GcSections(false), Gnu) => vec!["--no-gc-sections".into()],
+            (GcSections(true), Ld64) => vec!["-dead_strip".into()],
+            (GcSections(true), Msvc) => vec!["/OPT:REF".into()],
+            (GcSections(false), Msvc) => vec!["/OPT:NOREF".into()],
+            (NoUndefined, Gnu) => vec!["--no-undefined".into()],
+            (NoUndefined, Ld64) => vec!["-undefined".into(), "error".into()],
+            (BuildId, Gnu) => vec!["--build-id".into()],
+            (StackSize(size), Gnu) => vec![format!("-zstack-size={}", size)],
+            (StackSize(size), Ld64) => vec!["-stack_size".into(), format!("{:#x}", size)],
+            (StackSize(size), Msvc) => vec![format!("/STACK:{}", size)],
+            (Entry(symbol), Gnu) => vec![format!("--entry={}", symbol)],
+            (Entry(symbol), Ld64) => vec!["-e".into(), format!("_{}", symbol)],
+            (Entry(symbol), Msvc) => vec![format!("/ENTRY:{}", symbol)],
+            (AsNeeded(false), Ld64)
+            | (GcSections(false), Ld64)
+            | (NoUndefined, Msvc)
+            | (BuildId, Ld64) => Vec::new(),
+            (Rpath(_), Msvc) | (Soname(_), Msvc) | (AsNeeded(_), Msvc) | (BuildId, Msvc) => {
+                return None
+            }
+        };
+        Some(args)
+    }
+
+    /// Returns the name of the option, for warnings.
+    fn name(&self) -> &'static str {
+        match self {
+            LinkOption::Rpath(_) => "rpath",
+            LinkOption::Soname(_) => "soname",
+            LinkOption::AsNeeded(_) => "as-needed",
+            LinkOption::GcSections(_) => "gc-sections",
+            LinkOption::NoUndefined => "no-undefined",
+            LinkOption::BuildId => "build-id",
+            LinkOption::StackSize(_) => "stack size",
+            LinkOption::Entry(_) => "entry point",
+        }
+    }
+}
+
+/// A builder for a set of [`LinkOption`]s.
+#[derive(Clone, Debug)]
+pub struct LinkOptions {
+    options: Vec<LinkOption>,
+    flavor: Option<LinkerFlavor>,
+    driver: Option<bool>,
+    scope: LinkArgScope,
+}
+
+impl Default for LinkOptions {
+    fn default() -> Self {
+        LinkOptions {
+            options: Vec::new(),
+            flavor: None,
+            driver: None,
+            scope: LinkArgScope::All,
+        }
+    }
+}
+
+impl LinkOptions {
+    /// Creates an empty set of options for the target's linker.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `option`.
+    pub fn option(&mut self, option: LinkOption) -> &mut Self {
+        self.options.push(option);
+        self
+    }
+
+    /// Renders the options for `flavor` instead of the target's linker.
+    pub fn flavor(&mut self, flavor: LinkerFlavor) -> &mut Self {
+        self.flavor = Some(flavor);
+        self
+    }
+
+    /// Whether the linker is invoked through a compiler driver such as `cc`,
+    /// so that arguments must be wrapped with `-Wl,`.
+    ///
+    /// Defaults to `true` for GNU and ld64 flavors, except on bare-metal and
+    /// WebAssembly targets, where `rust-lld` is invoked directly. `link.exe`
+    /// arguments are never wrapped.
+    pub fn driver(&mut self, driver: bool) -> &mut Self {
+        self.driver = Some(driver);
+        self
+    }
+
+    /// Passes the options to the targets in `scope` only.
+    ///
+    /// Defaults to [`LinkArgScope::All`].
+    pub fn scope(&mut self, scope: LinkArgScope) -> &mut Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Returns the link argument directives for the options, and a warning for
+    /// each option that the linker does not support.
+    pub fn directives(&self) -> Vec<Directive> {
+        self.directives_with(|key| env::var_os(key))
+    }
+
+    pub(crate) fn directives_with<F>(&self, var: F) -> Vec<Directive>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let target = Target::with(&var);
+        let flavor = self.flavor.unwrap_or(target.flavor);
+        let driver = flavor != LinkerFlavor::Msvc && self.driver.unwrap_or(target.driver);
+
+        let mut directives = Vec::new();
+        for option in &self.options {
+            let args = match option.args(flavor) {
+                Some(args) => args,
+                None => {
+                    directives.push(Directive::Warning(format!(
+                        "the {} link option is not supported by {}, and is ignored",
+                        option.name(),
+                        match flavor {
+                            LinkerFlavor::Gnu => "GNU linkers",
+                            LinkerFlavor::Ld64 => "ld64",
+                            LinkerFlavor::Msvc => "link.exe",
+                        }
+                    )));
+                    continue;
+                }
+            };
+            for arg in wrap(args, driver) {
+                directives.push(Directive::RustcLinkArg {
+                    scope: self.scope.clone(),
+                    arg,
+                });
+            }
+        }
+        directives
+    }
+
+    /// Emits the options to Cargo.
+    pub fn emit(&self) {
+        for directive in self.directives() {
+            directive.emit();
+        }
+    }
+
+    /// Emits the options to `stream`.
+    pub fn emit_to<W: fmt::Write + ?Sized>(&self, stream: &mut W) {
+        for directive in self.directives() {
+            directive.emit_to(stream);
+        }
+    }
+}
+
+/// Returns the arguments to pass with `-C link-arg`, wrapped for a compiler
+/// driver if `driver` is set.
+fn wrap(args: Vec<String>, driver: bool) -> Vec<String> {
+    if !driver || args.is_empty() {
+        return args;
+    }
+    if args.iter().any(|arg| arg.contains(',')) {
+        return args
+            .into_iter()
+            .flat_map(|arg| vec!["-Xlinker".to_owned(), arg])
+            .collect();
+    }
+    let mut wrapped = String::from("-Wl");
+    for arg in &args {
+        let _ = write!(wrapped, ",{}", arg);
+    }
+    vec![wrapped]
+}
+
+/// What the `CARGO_CFG_TARGET_*` variables say about the linker.
+pub(crate) struct Target {
+    pub(crate) flavor: LinkerFlavor,
+    pub(crate) driver: bool,
+}
+
+impl Target {
+    pub(crate) fn with<F>(var: F) -> Self
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let string = |key: &str, host: &str| {
+            var(key)
+                .and_then(|value| value.into_string().ok())
+                .unwrap_or_else(|| host.to_owned())
+        };
+        let target_env = string(
+            "CARGO_CFG_TARGET_ENV",
+            if cfg!(target_env = "msvc") {
+                "msvc"
+            } else {
+                ""
+            },
+        );
+        let vendor = string(
+            "CARGO_CFG_TARGET_VENDOR",
+            if cfg!(target_vendor = "apple") {
+                "apple"
+            } else {
+                ""
+            },
+        );
+        let os = string("CARGO_CFG_TARGET_OS", env::consts::OS);
+        let family = string("CARGO_CFG_TARGET_FAMILY", env::consts::FAMILY);
+
+        let flavor = if target_env == "msvc" || os == "uefi" {
+            LinkerFlavor::Msvc
+        } else if vendor == "apple" {
+            LinkerFlavor::Ld64
+        } else {
+            LinkerFlavor::Gnu
+        };
+        // Emscripten links through `emcc`, unlike the other WebAssembly
+        // targets.
+        let wasm = family.split(',').any(|family| family == "wasm");
+        let direct = os == "none" || os == "uefi" || (wasm && os != "emscripten");
+        Target {
+            flavor,
+            driver: !direct,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(
+        os: &'static str,
+        target_env: &'static str,
+        vendor: &'static str,
+        family: &'static str,
+    ) -> impl Fn(&str) -> Option<OsString> {
+        move |key| match key {
+            "CARGO_CFG_TARGET_OS" => Some(os.into()),
+            "CARGO_CFG_TARGET_ENV" => Some(target_env.into()),
+            "CARGO_CFG_TARGET_VENDOR" => Some(vendor.into()),
+            "CARGO_CFG_TARGET_FAMILY" => Some(family.into()),
+            _ => None,
+        }
+    }
+
+    fn all() -> LinkOptions {
+        let mut options = LinkOptions::new();
+        options
+            .option(LinkOption::Rpath("$ORIGIN/../lib".into()))
+            .option(LinkOption::Soname("libfoo.so.1".into()))
+            .option(LinkOption::AsNeeded(true))
+            .option(LinkOption::GcSections(true))
+            .option(LinkOption::NoUndefined)
+            .option(LinkOption::BuildId)
+            .option(LinkOption::StackSize(0x80_0000))
+            .option(LinkOption::Entry("start".into()));
+        options
+    }
+
+    fn render<F: Fn(&str) -> Option<OsString>>(options: &LinkOptions, var: F) -> String {
+        crate::capture_output(|output| {
+            for directive in options.directives_with(var) {
+                directive.emit_to(output);
+            }
+        })
+    }
+
+    #[test]
+    fn gnu() {
+        insta::assert_snapshot!(
+            render(&all(), target("linux", "gnu", "unknown", "unix")),
+            @r###"
+        cargo:rustc-link-arg=-Wl,-rpath=$ORIGIN/../lib
+        cargo:rustc-link-arg=-Wl,-soname=libfoo.so.1
+        cargo:rustc-link-arg=-Wl,--as-needed
+        cargo:rustc-link-arg=-Wl,--gc-sections
+        cargo:rustc-link-arg=-Wl,--no-undefined
+        cargo:rustc-link-arg=-Wl,--build-id
+        cargo:rustc-link-arg=-Wl,-zstack-size=8388608
+        cargo:rustc-link-arg=-Wl,--entry=start
+        "###
+        );
+    }
+
+    #[test]
+    fn bare_metal() {
+        insta::assert_snapshot!(
+            render(
+                LinkOptions::new()
+                    .option(LinkOption::GcSections(true))
+                    .option(LinkOption::Entry("Reset".into()))
+                    .scope(LinkArgScope::Bins),
+                target("none", "", "unknown", "")
+            ),
+            @r###"
+        cargo:rustc-link-arg-bins=--gc-sections
+        cargo:rustc-link-arg-bins=--entry=Reset
+        "###
+        );
+    }
+
+    #[test]
+    fn emscripten() {
+        insta::assert_snapshot!(
+            render(
+                LinkOptions::new().option(LinkOption::GcSections(true)),
+                target("emscripten", "", "unknown", "unix,wasm")
+            ),
+            @"cargo:rustc-link-arg=-Wl,--gc-sections"
+        );
+        insta::assert_snapshot!(
+            render(
+                LinkOptions::new().option(LinkOption::GcSections(true)),
+                target("wasi", "p1", "unknown", "wasm")
+            ),
+            @"cargo:rustc-link-arg=--gc-sections"
+        );
+    }
+
+    #[test]
+    fn uefi() {
+        let uefi = || target("uefi", "", "unknown", "");
+        assert_eq!(Target::with(uefi()).flavor, LinkerFlavor::Msvc);
+        insta::assert_snapshot!(
+            render(
+                LinkOptions::new()
+                    .option(LinkOption::GcSections(true))
+                    .option(LinkOption::Entry("efi_main".into())),
+                uefi()
+            ),
+            @r###"
+        cargo:rustc-link-arg=/OPT:REF
+        cargo:rustc-link-arg=/ENTRY:efi_main
+        "###
+        );
+    }
+
+    #[test]
+    fn ld64() {
+        insta::assert_snapshot!(
+            render(&all(), target("macos", "", "apple", "unix")),
+            @r###"
+        cargo:rustc-link-arg=-Wl,-rpath,$ORIGIN/../lib
+        cargo:rustc-link-arg=-Wl,-install_name,libfoo.so.1
+        cargo:rustc-link-arg=-Wl,-dead_strip_dylibs
+        cargo:rustc-link-arg=-Wl,-dead_strip
+        cargo:rustc-link-arg=-Wl,-undefined,error
+        cargo:rustc-link-arg=-Wl,-stack_size,0x800000
+        cargo:rustc-link-arg=-Wl,-e,_start
+        "###
+        );
+    }
+
+    #[test]
+    fn msvc() {
+        insta::assert_snapshot!(
+            render(&all(), target("windows", "msvc", "pc", "windows")),
+            @r###"
+        cargo:warning=the rpath link option is not supported by link.exe, and is ignored
+        cargo:warning=the soname link option is not supported by link.exe, and is ignored
+        cargo:warning=the as-needed link option is not supported by link.exe, and is ignored
+        cargo:rustc-link-arg=/OPT:REF
+        cargo:warning=the build-id link option is not supported by link.exe, and is ignored
+        cargo:rustc-link-arg=/STACK:8388608
+        cargo:rustc-link-arg=/ENTRY:start
+        "###
+        );
+    }
+
+    #[test]
+    fn driver() {
+        let linux = || target("linux", "gnu", "unknown", "unix");
+        insta::assert_snapshot!(
+            render(
+                LinkOptions::new()
+                    .option(LinkOption::Rpath("/opt/a,b/lib".into()))
+                    .option(LinkOption::AsNeeded(false)),
+                linux()
+            ),
+            @r###"
+        cargo:rustc-link-arg=-Xlinker
+        cargo:rustc-link-arg=-rpath=/opt/a,b/lib
+        cargo:rustc-link-arg=-Wl,--no-as-needed
+        "###
+        );
+        insta::assert_snapshot!(
+            render(
+                LinkOptions::new()
+                    .option(LinkOption::Rpath("/opt/lib".into()))
+                    .flavor(LinkerFlavor::Ld64)
+                    .driver(false),
+                linux()
+            ),
+            @r###"
+        cargo:rustc-link-arg=-rpath
+        cargo:rustc-link-arg=/opt/lib
+        "###
+        );
+    }
+}
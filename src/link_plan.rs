@@ -0,0 +1,616 @@
+//! Ordering native libraries so that static linking resolves every symbol.
+//!
+//! A linker reads static libraries once, from left to right, and only keeps
+//! the objects that define symbols needed so far. A library therefore has to
+//! come before the libraries it depends on. [`LinkPlanner`] takes the
+//! libraries and the dependencies between them, and emits the
+//! [`rustc_link_lib!`](crate::rustc_link_lib) lines in such an order,
+//! keeping the order they were declared in where the dependencies allow.
+//!
+//! Libraries that depend on each other, directly or through others, have no
+//! such order. The static libraries in such a cycle are linked according to
+//! [`Cycles`]: repeated so that the linker reads them twice, or in full with
+//! the `+whole-archive` modifier.
+//!
+//! The order, and repeating libraries, only matters where the linker reads
+//! the libraries themselves: when linking a binary or another final artifact,
+//! and for libraries with the `-bundle` modifier. When building an `rlib`,
+//! `rustc` copies the objects of bundled static libraries into it once, and
+//! the linker finds them there in any order, so that cycles resolve without
+//! repeating anything. Older versions of `rustc` reject `+whole-archive` for
+//! bundled libraries, so with [`Cycles::WholeArchive`], every static library
+//! of a plan with a cycle is given `-bundle`, which also keeps them in the
+//! planned order.
+//!
+//! With [`verbose`](LinkPlanner::verbose), the plan is explained in
+//! warnings.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cargo_emit::{link_plan::LinkPlanner, LinkKind};
+//!
+//! LinkPlanner::new()
+//!     .lib("crypto", LinkKind::Static)
+//!     .lib("ssl", LinkKind::Static)
+//!     .lib("z", LinkKind::Dylib)
+//!     .dep("ssl", "crypto")
+//!     .dep("crypto", "z")
+//!     .plan()
+//!     .unwrap()
+//!     .emit();
+//! ```
+//!
+//! emits:
+//!
+//! ```text
+//! cargo:rustc-link-lib=static=ssl
+//! cargo:rustc-link-lib=static=crypto
+//! cargo:rustc-link-lib=dylib=z
+//! ```
+
+use crate::{Directive, LinkKind, LinkModifier};
+use std::{error, fmt};
+
+/// How static libraries that depend on each other are linked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Cycles {
+    /// Links the libraries twice in a row.
+    Repeat,
+    /// Links every object of the libraries with the `+whole-archive`
+    /// modifier, and every static library of the plan with `-bundle`.
+    WholeArchive,
+}
+
+/// A builder for the native libraries of a package and their dependencies.
+#[derive(Clone, Debug)]
+pub struct LinkPlanner {
+    libs: Vec<(String, LinkKind)>,
+    deps: Vec<(String, String)>,
+    cycles: Cycles,
+    verbose: bool,
+}
+
+impl Default for LinkPlanner {
+    fn default() -> Self {
+        LinkPlanner {
+            libs: Vec::new(),
+            deps: Vec::new(),
+            cycles: Cycles::Repeat,
+            verbose: false,
+        }
+    }
+}
+
+impl LinkPlanner {
+    /// Creates a planner without any libraries.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the library `name`, as it would be passed to `-l`.
+    pub fn lib(&mut self, name: &str, kind: LinkKind) -> &mut Self {
+        self.libs.push((name.to_owned(), kind));
+        self
+    }
+
+    /// Records that `lib` uses symbols defined in `dep`.
+    pub fn dep(&mut self, lib: &str, dep: &str) -> &mut Self {
+        self.deps.push((lib.to_owned(), dep.to_owned()));
+        self
+    }
+
+    /// Sets how static libraries that depend on each other are linked.
+    ///
+    /// Defaults to [`Cycles::Repeat`].
+    pub fn cycles(&mut self, cycles: Cycles) -> &mut Self {
+        self.cycles = cycles;
+        self
+    }
+
+    /// Whether to emit the [explanation](LinkPlan::explanation) of the plan
+    /// as warnings.
+    ///
+    /// Defaults to `false`.
+    pub fn verbose(&mut self, verbose: bool) -> &mut Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Orders the libraries.
+    pub fn plan(&self) -> Result<LinkPlan, Error> {
+        let index = |name: &str| self.libs.iter().position(|(lib, _)| lib == name);
+        for (i, (name, _)) in self.libs.iter().enumerate() {
+            if index(name) != Some(i) {
+                return Err(Error::Duplicate { name: name.clone() });
+            }
+        }
+        let mut edges = vec![Vec::new(); self.libs.len()];
+        for (lib, dep) in &self.deps {
+            let find =
+                |name: &String| index(name).ok_or_else(|| Error::Unknown { name: name.clone() });
+            let (lib, dep) = (find(lib)?, find(dep)?);
+            if lib != dep && !edges[lib].contains(&dep) {
+                edges[lib].push(dep);
+            }
+        }
+
+        let components = components(&edges);
+        let bundle = self.cycles == Cycles::Repeat
+            || components.iter().all(|component| component.len() == 1);
+        let mut notes = Vec::new();
+        let mut entries = Vec::new();
+        for component in order(&edges, &components) {
+            let names = component
+                .iter()
+                .map(|&lib| format!("`{}`", self.libs[lib].0))
+                .collect::<Vec<_>>();
+            let cyclic = component.len() > 1;
+            if cyclic {
+                notes.push(format!(
+                    "{} depend on each other, so their static libraries are {}",
+                    join(&names),
+                    match self.cycles {
+                        Cycles::WholeArchive => "linked with +whole-archive",
+                        Cycles::Repeat => "linked twice",
+                    }
+                ));
+            }
+            let rounds = if cyclic && self.cycles == Cycles::Repeat {
+                2
+            } else {
+                1
+            };
+            for round in 0..rounds {
+                for &lib in &component {
+                    let (name, kind) = &self.libs[lib];
+                    if round > 0 && *kind != LinkKind::Static {
+                        continue;
+                    }
+                    let statik = *kind == LinkKind::Static;
+                    entries.push(Entry {
+                        name: name.clone(),
+                        kind: *kind,
+                        whole_archive: cyclic && self.cycles == Cycles::WholeArchive && statik,
+                        bundle: bundle || !statik,
+                    });
+                }
+            }
+            for &lib in &component {
+                let deps = edges[lib]
+                    .iter()
+                    .filter(|dep| !component.contains(dep))
+                    .map(|&dep| format!("`{}`", self.libs[dep].0))
+                    .collect::<Vec<_>>();
+                if !deps.is_empty() {
+                    notes.push(format!(
+                        "`{}` is linked before {}, which it depends on",
+                        self.libs[lib].0,
+                        join(&deps)
+                    ));
+                }
+            }
+        }
+        let order = entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect::<Vec<_>>();
+        notes.insert(0, format!("linking in the order {}", order.join(", ")));
+        Ok(LinkPlan {
+            entries,
+            notes,
+            verbose: self.verbose,
+        })
+    }
+}
+
+/// Joins `names` into `a`, `a and b`, or `a, b and c`.
+fn join(names: &[String]) -> String {
+    match names.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+        _ => names.concat(),
+    }
+}
+
+/// Returns the strongly connected components of the graph with `edges`, each
+/// sorted by index, using Tarjan's algorithm.
+fn components(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    struct State<'a> {
+        edges: &'a [Vec<usize>],
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        next: usize,
+        components: Vec<Vec<usize>>,
+    }
+
+    fn visit(state: &mut State, node: usize) {
+        state.index[node] = Some(state.next);
+        state.low[node] = state.next;
+        state.next += 1;
+        state.stack.push(node);
+        state.on_stack[node] = true;
+
+        for &next in &state.edges[node] {
+            match state.index[next] {
+                None => {
+                    visit(state, next);
+                    state.low[node] = state.low[node].min(state.low[next]);
+                }
+                Some(index) if state.on_stack[next] => {
+                    state.low[node] = state.low[node].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(state.low[node]) == state.index[node] {
+            let mut component = Vec::new();
+            while let Some(member) = state.stack.pop() {
+                state.on_stack[member] = false;
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            component.sort_unstable();
+            state.components.push(component);
+        }
+    }
+
+    let mut state = State {
+        edges,
+        index: vec![None; edges.len()],
+        low: vec![0; edges.len()],
+        stack: Vec::new(),
+        on_stack: vec![false; edges.len()],
+        next: 0,
+        components: Vec::new(),
+    };
+    for node in 0..edges.len() {
+        if state.index[node].is_none() {
+            visit(&mut state, node);
+        }
+    }
+    state.components
+}
+
+/// Orders `components` so that each comes before the ones it depends on,
+/// picking the component declared first whenever there is a choice.
+fn order(edges: &[Vec<usize>], components: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut component_of = vec![0; edges.len()];
+    for (i, component) in components.iter().enumerate() {
+        for &node in component {
+            component_of[node] = i;
+        }
+    }
+    let mut deps = vec![Vec::new(); components.len()];
+    let mut dependents = vec![0; components.len()];
+    for (node, targets) in edges.iter().enumerate() {
+        for &target in targets {
+            let (from, to) = (component_of[node], component_of[target]);
+            if from != to && !deps[from].contains(&to) {
+                deps[from].push(to);
+                dependents[to] += 1;
+            }
+        }
+    }
+
+    let mut done = vec![false; components.len()];
+    let mut ordered = Vec::new();
+    // Components are sorted, so their first node is where they were declared.
+    while let Some(next) = (0..components.len())
+        .filter(|&i| !done[i] && dependents[i] == 0)
+        .min_by_key(|&i| components[i][0])
+    {
+        done[next] = true;
+        for &dep in &deps[next] {
+            dependents[dep] -= 1;
+        }
+        ordered.push(components[next].clone());
+    }
+    ordered
+}
+
+/// A library in a [`LinkPlan`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    name: String,
+    kind: LinkKind,
+    whole_archive: bool,
+    bundle: bool,
+}
+
+/// The libraries in link order, created by [`LinkPlanner::plan`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkPlan {
+    entries: Vec<Entry>,
+    notes: Vec<String>,
+    verbose: bool,
+}
+
+impl LinkPlan {
+    /// Returns the names of the libraries in the order they are linked, with
+    /// repeated libraries listed each time.
+    pub fn order(&self) -> Vec<&str> {
+        self.entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    /// Returns the reasons for the order and the handling of cycles, one
+    /// sentence each.
+    pub fn explanation(&self) -> &[String] {
+        &self.notes
+    }
+
+    /// Returns the explanation as warnings, if verbose, and the link
+    /// directives.
+    pub fn directives(&self) -> Vec<Directive> {
+        let mut directives = Vec::new();
+        if self.verbose {
+            for note in &self.notes {
+                directives.push(Directive::Warning(format!("link plan: {}", note)));
+            }
+        }
+        for entry in &self.entries {
+            let mut modifiers = Vec::new();
+            if entry.whole_archive {
+                modifiers.push(LinkModifier::WholeArchive(true));
+            }
+            if !entry.bundle {
+                modifiers.push(LinkModifier::Bundle(false));
+            }
+            directives.push(Directive::RustcLinkLib {
+                name: entry.name.clone(),
+                kind: Some(entry.kind),
+                modifiers,
+            });
+        }
+        directives
+    }
+
+    /// Emits the plan to Cargo.
+    pub fn emit(&self) {
+        for directive in self.directives() {
+            directive.emit();
+        }
+    }
+
+    /// Emits the plan to `stream`.
+    pub fn emit_to<W: fmt::Write + ?Sized>(&self, stream: &mut W) {
+        for directive in self.directives() {
+            directive.emit_to(stream);
+        }
+    }
+}
+
+/// The error returned by [`LinkPlanner::plan`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A library was added more than once.
+    Duplicate {
+        /// The name of the library.
+        name: String,
+    },
+    /// A dependency names a library that was not added.
+    Unknown {
+        /// The name of the library.
+        name: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Duplicate { name } => write!(f, "library `{}` was added more than once", name),
+            Error::Unknown { name } => {
+                write!(f, "library `{}` is a dependency but was not added", name)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, path::Path, process::Command};
+
+    fn render(planner: &LinkPlanner) -> String {
+        crate::capture_output(|output| planner.plan().unwrap().emit_to(output))
+    }
+
+    #[test]
+    fn dependency_order() {
+        insta::assert_snapshot!(
+            render(
+                LinkPlanner::new()
+                    .lib("z", LinkKind::Dylib)
+                    .lib("crypto", LinkKind::Static)
+                    .lib("ssl", LinkKind::Static)
+                    .lib("curl", LinkKind::Static)
+                    .dep("curl", "ssl")
+                    .dep("curl", "z")
+                    .dep("ssl", "crypto")
+                    .dep("crypto", "z")
+            ),
+            @r###"
+        cargo:rustc-link-lib=static=curl
+        cargo:rustc-link-lib=static=ssl
+        cargo:rustc-link-lib=static=crypto
+        cargo:rustc-link-lib=dylib=z
+        "###
+        );
+    }
+
+    #[test]
+    fn declaration_order() {
+        let plan = LinkPlanner::new()
+            .lib("b", LinkKind::Static)
+            .lib("a", LinkKind::Static)
+            .lib("c", LinkKind::Static)
+            .dep("c", "a")
+            .plan()
+            .unwrap();
+        assert_eq!(plan.order(), ["b", "c", "a"]);
+    }
+
+    fn cycle() -> LinkPlanner {
+        let mut planner = LinkPlanner::new();
+        planner
+            .lib("app", LinkKind::Static)
+            .lib("a", LinkKind::Static)
+            .lib("b", LinkKind::Static)
+            .lib("c", LinkKind::Static)
+            .lib("m", LinkKind::Dylib)
+            .dep("app", "a")
+            .dep("a", "b")
+            .dep("b", "c")
+            .dep("c", "a")
+            .dep("b", "m");
+        planner
+    }
+
+    #[test]
+    fn whole_archive() {
+        insta::assert_snapshot!(render(cycle().cycles(Cycles::WholeArchive)), @r###"
+        cargo:rustc-link-lib=static:-bundle=app
+        cargo:rustc-link-lib=static:+whole-archive,-bundle=a
+        cargo:rustc-link-lib=static:+whole-archive,-bundle=b
+        cargo:rustc-link-lib=static:+whole-archive,-bundle=c
+        cargo:rustc-link-lib=dylib=m
+        "###);
+    }
+
+    #[test]
+    fn repeat() {
+        insta::assert_snapshot!(render(&cycle()), @r###"
+        cargo:rustc-link-lib=static=app
+        cargo:rustc-link-lib=static=a
+        cargo:rustc-link-lib=static=b
+        cargo:rustc-link-lib=static=c
+        cargo:rustc-link-lib=static=a
+        cargo:rustc-link-lib=static=b
+        cargo:rustc-link-lib=static=c
+        cargo:rustc-link-lib=dylib=m
+        "###);
+    }
+
+    #[test]
+    fn verbose() {
+        insta::assert_snapshot!(render(cycle().cycles(Cycles::WholeArchive).verbose(true)), @r###"
+        cargo:warning=link plan: linking in the order app, a, b, c, m
+        cargo:warning=link plan: `app` is linked before `a`, which it depends on
+        cargo:warning=link plan: `a`, `b` and `c` depend on each other, so their static libraries are linked with +whole-archive
+        cargo:warning=link plan: `b` is linked before `m`, which it depends on
+        cargo:rustc-link-lib=static:-bundle=app
+        cargo:rustc-link-lib=static:+whole-archive,-bundle=a
+        cargo:rustc-link-lib=static:+whole-archive,-bundle=b
+        cargo:rustc-link-lib=static:+whole-archive,-bundle=c
+        cargo:rustc-link-lib=dylib=m
+        "###);
+    }
+
+    /// Builds an `rlib` that links `liba.a` and `libb.a`, which depend on
+    /// each other, as planned, then links and runs a binary that uses it.
+    /// Returns `None` if a tool is not available.
+    fn build(dir: &Path, cycles: Cycles) -> Option<()> {
+        let run = |command: &mut Command| {
+            let output = command.current_dir(dir).output().ok()?;
+            assert!(
+                output.status.success(),
+                "{:?} failed:\n{}",
+                command,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            Some(())
+        };
+        fs::write(
+            dir.join("a.c"),
+            "int b(void);\nint a(void) { return b(); }\n",
+        )
+        .unwrap();
+        fs::write(dir.join("a2.c"), "int a2(void) { return 1; }\n").unwrap();
+        fs::write(
+            dir.join("b.c"),
+            "int a2(void);\nint b(void) { return a2(); }\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("lib.rs"),
+            "extern \"C\" { fn a() -> i32; }\n\
+             pub fn call() -> i32 { unsafe { a() } }\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("main.rs"),
+            "fn main() { std::process::exit(cyclic::call() - 1) }\n",
+        )
+        .unwrap();
+        for file in ["a", "a2", "b"] {
+            run(Command::new("cc").args(["-c", &format!("{}.c", file)]))?;
+        }
+        run(Command::new("ar").args(["rcs", "liba.a", "a.o", "a2.o"]))?;
+        run(Command::new("ar").args(["rcs", "libb.a", "b.o"]))?;
+
+        let plan = LinkPlanner::new()
+            .lib("a", LinkKind::Static)
+            .lib("b", LinkKind::Static)
+            .dep("a", "b")
+            .dep("b", "a")
+            .cycles(cycles)
+            .plan()
+            .unwrap();
+        let rustc = || Command::new(env::var_os("RUSTC").unwrap_or_else(|| "rustc".into()));
+        let mut rlib = rustc();
+        rlib.args(["--crate-type", "rlib", "--crate-name", "cyclic", "-L", "."]);
+        for directive in plan.directives() {
+            let value = directive.to_string();
+            rlib.args(["-l", &value["cargo:rustc-link-lib=".len()..]]);
+        }
+        run(rlib.arg("lib.rs"))?;
+        run(rustc().args(["--extern", "cyclic=libcyclic.rlib", "-L", ".", "main.rs"]))?;
+        run(&mut Command::new(dir.join("main")))
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn rlib() {
+        for cycles in [Cycles::Repeat, Cycles::WholeArchive] {
+            let dir = crate::temp_dir(&format!("link-plan-{:?}", cycles));
+            build(&dir, cycles);
+        }
+    }
+
+    #[test]
+    fn parses_back() {
+        let plan = cycle().cycles(Cycles::WholeArchive).plan().unwrap();
+        for directive in plan.directives() {
+            assert_eq!(
+                Directive::parse(&directive.to_string()),
+                Some(directive.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let error = |planner: &LinkPlanner| planner.plan().unwrap_err().to_string();
+        insta::assert_snapshot!(
+            error(
+                LinkPlanner::new()
+                    .lib("a", LinkKind::Static)
+                    .lib("a", LinkKind::Dylib)
+            ),
+            @"library `a` was added more than once"
+        );
+        insta::assert_snapshot!(
+            error(LinkPlanner::new().lib("a", LinkKind::Static).dep("a", "b")),
+            @"library `b` is a dependency but was not added"
+        );
+    }
+}
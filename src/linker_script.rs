@@ -0,0 +1,457 @@
+//! Generating a linker script with the memory layout of a target into
+//! `OUT_DIR`.
+//!
+//! Embedded crates usually keep a hand-written `memory.x` next to `build.rs`,
+//! and then tell the linker where to find it. [`LinkerScript`] describes the
+//! `MEMORY` regions and their `REGION_ALIAS`es in Rust instead, checks that
+//! the regions neither overlap nor are misaligned, and writes the script:
+//!
+//! ```text
+//! /* @generated by cargo-emit. Do not edit. */
+//! MEMORY
+//! {
+//!   FLASH (rx) : ORIGIN = 0x08000000, LENGTH = 256K
+//!   RAM (rwx) : ORIGIN = 0x20000000, LENGTH = 64K
+//! }
+//!
+//! REGION_ALIAS("REGION_TEXT", FLASH);
+//! ```
+//!
+//! The file is only rewritten if its contents change, so that its timestamp
+//! does not force the crate to be relinked. The output directory is then
+//! added with [`rustc_link_search!`](crate::rustc_link_search), and the
+//! script is passed with a `-T` [`rustc_link_arg!`](crate::rustc_link_arg).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cargo_emit::linker_script::LinkerScript;
+//!
+//! LinkerScript::new("memory.x")
+//!     .region("FLASH", "rx", 0x0800_0000, 256 * 1024)
+//!     .region("RAM", "rwx", 0x2000_0000, 64 * 1024)
+//!     .alias("REGION_TEXT", "FLASH")
+//!     .write()
+//!     .unwrap();
+//! ```
+//!
+//! When the script is `INCLUDE`d by another one, such as the `link.x` of
+//! `cortex-m-rt`, only the search path is needed:
+//!
+//! ```no_run
+//! use cargo_emit::linker_script::LinkerScript;
+//!
+//! LinkerScript::new("memory.x")
+//!     .region("FLASH", "", 0x0000_0000, 1024 * 1024)
+//!     .region("RAM", "", 0x2000_0000, 256 * 1024)
+//!     .link_arg(false)
+//!     .write()
+//!     .unwrap();
+//! ```
+
+use crate::{constants::write_if_changed, Directive, LinkArgScope};
+use std::{
+    env,
+    fmt::{self, Write as _},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// A builder for a linker script.
+#[derive(Clone, Debug)]
+pub struct LinkerScript {
+    name: String,
+    out_dir: Option<PathBuf>,
+    alignment: u64,
+    link_arg: Option<LinkArgScope>,
+    regions: Vec<Region>,
+    aliases: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug)]
+struct Region {
+    name: String,
+    attributes: String,
+    origin: u64,
+    length: u64,
+}
+
+impl LinkerScript {
+    /// Creates an empty script that will be written to `$OUT_DIR/$name`.
+    pub fn new(name: &str) -> Self {
+        LinkerScript {
+            name: name.to_owned(),
+            out_dir: None,
+            alignment: 4,
+            link_arg: Some(LinkArgScope::All),
+            regions: Vec::new(),
+            aliases: Vec::new(),
+        }
+    }
+
+    /// Writes into `dir` instead of `OUT_DIR`.
+    pub fn out_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.out_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Sets the alignment, in bytes, that the origin and length of every
+    /// region must have.
+    ///
+    /// Defaults to 4. Set to 1 to allow any address.
+    pub fn alignment(&mut self, alignment: u64) -> &mut Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Whether to emit `-T$name`, so that the linker uses the script.
+    ///
+    /// Defaults to `true`. Set to `false` when another script `INCLUDE`s this
+    /// one.
+    pub fn link_arg(&mut self, link_arg: bool) -> &mut Self {
+        self.link_arg = if link_arg {
+            Some(LinkArgScope::All)
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Emits `-T$name` for the targets in `scope` only, such as
+    /// [`LinkArgScope::Bins`] to leave tests linked for the host alone.
+    pub fn link_arg_scope(&mut self, scope: LinkArgScope) -> &mut Self {
+        self.link_arg = Some(scope);
+        self
+    }
+
+    /// Adds the memory region `name` of `length` bytes at `origin`.
+    ///
+    /// `attributes` is any combination of `r`, `w`, `x`, `a`, `i`, `l` and
+    /// `!`, or empty.
+    pub fn region(&mut self, name: &str, attributes: &str, origin: u64, length: u64) -> &mut Self {
+        self.regions.push(Region {
+            name: name.to_owned(),
+            attributes: attributes.to_owned(),
+            origin,
+            length,
+        });
+        self
+    }
+
+    /// Makes `alias` refer to the memory region `region`, for use in the
+    /// `SECTIONS` of another script.
+    pub fn alias(&mut self, alias: &str, region: &str) -> &mut Self {
+        self.aliases.push((alias.to_owned(), region.to_owned()));
+        self
+    }
+
+    /// Returns the generated script.
+    pub fn contents(&self) -> String {
+        let mut contents = String::from("/* @generated by cargo-emit. Do not edit. */\n");
+        if !self.regions.is_empty() {
+            contents.push_str("MEMORY\n{\n");
+            for region in &self.regions {
+                let _ = write!(contents, "  {}", region.name);
+                if !region.attributes.is_empty() {
+                    let _ = write!(contents, " ({})", region.attributes);
+                }
+                let _ = writeln!(
+                    contents,
+                    " : ORIGIN = 0x{:08X}, LENGTH = {}",
+                    region.origin,
+                    Size(region.length)
+                );
+            }
+            contents.push_str("}\n");
+        }
+        if !self.aliases.is_empty() {
+            contents.push('\n');
+            for (alias, region) in &self.aliases {
+                let _ = writeln!(contents, "REGION_ALIAS(\"{}\", {});", alias, region);
+            }
+        }
+        contents
+    }
+
+    /// Checks the layout, writes the script if its contents changed, then
+    /// emits the search path and link argument.
+    ///
+    /// Returns the path of the script.
+    pub fn write(&self) -> io::Result<PathBuf> {
+        let (path, directives) = self.write_file()?;
+        for directive in directives {
+            directive.emit();
+        }
+        Ok(path)
+    }
+
+    /// Checks the layout, writes the script if its contents changed, then
+    /// emits the search path and link argument to `stream`.
+    ///
+    /// Returns the path of the script.
+    pub fn write_to<W: fmt::Write + ?Sized>(&self, stream: &mut W) -> io::Result<PathBuf> {
+        let (path, directives) = self.write_file()?;
+        for directive in directives {
+            directive.emit_to(stream);
+        }
+        Ok(path)
+    }
+
+    fn write_file(&self) -> io::Result<(PathBuf, Vec<Directive>)> {
+        self.check()
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidInput, message))?;
+
+        let dir = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => env::var_os("OUT_DIR")
+                .map(PathBuf::from)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is not set"))?,
+        };
+        let path = dir.join(&self.name);
+        write_if_changed(&path, self.contents().as_bytes())?;
+
+        let mut directives = vec![Directive::RustcLinkSearch {
+            path: dir.display().to_string(),
+            kind: None,
+        }];
+        if let Some(scope) = &self.link_arg {
+            directives.push(Directive::RustcLinkArg {
+                scope: scope.clone(),
+                arg: format!("-T{}", self.name),
+            });
+        }
+        Ok((path, directives))
+    }
+
+    /// Returns a description of the first problem with the layout.
+    fn check(&self) -> Result<(), String> {
+        if !self.alignment.is_power_of_two() {
+            return Err(format!(
+                "alignment {} is not a power of two",
+                self.alignment
+            ));
+        }
+
+        for (i, region) in self.regions.iter().enumerate() {
+            if !is_symbol(&region.name) {
+                return Err(format!("`{}` is not a valid region name", region.name));
+            }
+            if self.regions[..i].iter().any(|r| r.name == region.name) {
+                return Err(format!("region `{}` is defined twice", region.name));
+            }
+            if let Some(c) = region
+                .attributes
+                .chars()
+                .find(|c| !"rwxail!RWXAIL".contains(*c))
+            {
+                return Err(format!(
+                    "region `{}` has unknown attribute `{}`",
+                    region.name, c
+                ));
+            }
+            if region.length == 0 {
+                return Err(format!("region `{}` is empty", region.name));
+            }
+            if region.origin.checked_add(region.length).is_none() {
+                return Err(format!(
+                    "region `{}` extends past the end of the address space",
+                    region.name
+                ));
+            }
+            for (what, value) in [("origin", region.origin), ("length", region.length)] {
+                if value % self.alignment != 0 {
+                    return Err(format!(
+                        "region `{}` has {} {:#x}, which is not aligned to {} bytes",
+                        region.name, what, value, self.alignment
+                    ));
+                }
+            }
+        }
+
+        let mut regions: Vec<&Region> = self.regions.iter().collect();
+        regions.sort_by_key(|region| region.origin);
+        for pair in regions.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if a.origin + a.length > b.origin {
+                return Err(format!(
+                    "regions `{}` ({}) and `{}` ({}) overlap",
+                    a.name,
+                    Range(a),
+                    b.name,
+                    Range(b)
+                ));
+            }
+        }
+
+        for (alias, region) in &self.aliases {
+            if !is_symbol(alias) {
+                return Err(format!("`{}` is not a valid alias name", alias));
+            }
+            if !self.regions.iter().any(|r| &r.name == region) {
+                return Err(format!(
+                    "alias `{}` refers to unknown region `{}`",
+                    alias, region
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Renders a length with the largest `K` or `M` suffix that divides it.
+struct Size(u64);
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const K: u64 = 1024;
+        match self.0 {
+            length if length % (K * K) == 0 => write!(f, "{}M", length / (K * K)),
+            length if length % K == 0 => write!(f, "{}K", length / K),
+            length => write!(f, "{:#x}", length),
+        }
+    }
+}
+
+/// Renders the addresses of a region as a half-open range.
+struct Range<'a>(&'a Region);
+
+impl fmt::Display for Range<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:#010x}..{:#010x}",
+            self.0.origin,
+            self.0.origin + self.0.length
+        )
+    }
+}
+
+/// Returns whether `name` can be used as a region name without quoting.
+fn is_symbol(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn layout() -> LinkerScript {
+        let mut script = LinkerScript::new("memory.x");
+        script
+            .region("FLASH", "rx", 0x0800_0000, 256 * 1024)
+            .region("RAM", "rwx", 0x2000_0000, 64 * 1024)
+            .region("BACKUP", "", 0x4002_4000, 0x1004)
+            .alias("REGION_TEXT", "FLASH")
+            .alias("REGION_DATA", "RAM");
+        script
+    }
+
+    #[test]
+    fn contents() {
+        insta::assert_snapshot!(layout().contents(), @r###"
+        /* @generated by cargo-emit. Do not edit. */
+        MEMORY
+        {
+          FLASH (rx) : ORIGIN = 0x08000000, LENGTH = 256K
+          RAM (rwx) : ORIGIN = 0x20000000, LENGTH = 64K
+          BACKUP : ORIGIN = 0x40024000, LENGTH = 0x1004
+        }
+
+        REGION_ALIAS("REGION_TEXT", FLASH);
+        REGION_ALIAS("REGION_DATA", RAM);
+        "###);
+    }
+
+    #[test]
+    fn write() {
+        let dir = crate::temp_dir("linker-script");
+        let mut script = layout();
+        script.out_dir(&dir);
+
+        let output = crate::capture_output(|output| {
+            let path = script.write_to(output).unwrap();
+            assert_eq!(path, dir.join("memory.x"));
+        });
+        assert_eq!(
+            output,
+            format!(
+                "cargo:rustc-link-search={}\ncargo:rustc-link-arg=-Tmemory.x\n",
+                dir.display()
+            )
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("memory.x")).unwrap(),
+            script.contents()
+        );
+
+        let output = crate::capture_output(|output| {
+            script
+                .link_arg_scope(LinkArgScope::Bins)
+                .write_to(output)
+                .unwrap();
+            script.link_arg(false).write_to(output).unwrap();
+        });
+        assert_eq!(
+            output,
+            format!(
+                "cargo:rustc-link-search={0}\ncargo:rustc-link-arg-bins=-Tmemory.x\n\
+                 cargo:rustc-link-search={0}\n",
+                dir.display()
+            )
+        );
+    }
+
+    #[test]
+    fn check() {
+        let error = |script: &mut LinkerScript| script.check().unwrap_err();
+
+        insta::assert_snapshot!(
+            error(layout().region("CCM", "rw", 0x2000_8000, 0x1000)),
+            @"regions `RAM` (0x20000000..0x20010000) and `CCM` (0x20008000..0x20009000) overlap"
+        );
+        insta::assert_snapshot!(
+            error(layout().region("CCM", "rw", 0x1000_0002, 0x1000)),
+            @"region `CCM` has origin 0x10000002, which is not aligned to 4 bytes"
+        );
+        insta::assert_snapshot!(
+            error(layout().alignment(0x1000)),
+            @"region `BACKUP` has length 0x1004, which is not aligned to 4096 bytes"
+        );
+        insta::assert_snapshot!(
+            error(layout().region("RAM", "rw", 0x3000_0000, 0x1000)),
+            @"region `RAM` is defined twice"
+        );
+        insta::assert_snapshot!(
+            error(layout().region("CCM", "rq", 0x1000_0000, 0x1000)),
+            @"region `CCM` has unknown attribute `q`"
+        );
+        insta::assert_snapshot!(
+            error(layout().region("TOP", "", u64::MAX - 3, 8)),
+            @"region `TOP` extends past the end of the address space"
+        );
+        insta::assert_snapshot!(
+            error(layout().alias("REGION_BSS", "SRAM")),
+            @"alias `REGION_BSS` refers to unknown region `SRAM`"
+        );
+        insta::assert_snapshot!(
+            error(layout().region("my ram", "", 0x3000_0000, 0x1000)),
+            @"`my ram` is not a valid region name"
+        );
+        assert!(layout().alignment(1).check().is_ok());
+
+        let dir = crate::temp_dir("linker-script-invalid");
+        let error = layout()
+            .alignment(3)
+            .out_dir(&dir)
+            .write_to(&mut String::new())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(!dir.join("memory.x").exists());
+    }
+}
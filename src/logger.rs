@@ -0,0 +1,149 @@
+//! Showing [`log`] records during `cargo build`.
+//!
+//! Cargo hides everything a build script prints except for its directives, so
+//! anything logged by the libraries it uses is invisible unless the build
+//! fails. [`Logger`] emits each record at or above its level as a
+//! [`warning!`](crate::warning), prefixed with the record's level and target:
+//!
+//! ```text
+//! warning: my-crate@0.1.0: [WARN bindgen::ir] unknown type `__m128`
+//! ```
+//!
+//! Multi-line messages are split into one warning per line, since Cargo would
+//! otherwise treat the following lines as ordinary output.
+//!
+//! # Examples
+//!
+//! ```
+//! use cargo_emit::logger::Logger;
+//! use log::LevelFilter;
+//!
+//! Logger::new().level(LevelFilter::Info).init().unwrap();
+//!
+//! log::info!("generating bindings");
+//! ```
+
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+/// A [`Log`] implementation that emits records as Cargo warnings.
+#[derive(Clone, Debug)]
+pub struct Logger {
+    level: LevelFilter,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Logger {
+            level: LevelFilter::Warn,
+        }
+    }
+}
+
+impl Logger {
+    /// Creates a logger for records at [`Warn`](log::Level::Warn) level and
+    /// above.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the most verbose level that is emitted.
+    pub fn level(&mut self, level: LevelFilter) -> &mut Self {
+        self.level = level;
+        self
+    }
+
+    /// Installs this logger as the global logger, and sets the maximum log
+    /// level to match.
+    ///
+    /// This fails if a global logger was already installed.
+    pub fn init(&self) -> Result<(), SetLoggerError> {
+        log::set_boxed_logger(Box::new(self.clone()))?;
+        log::set_max_level(self.level);
+        Ok(())
+    }
+
+    /// Returns the text of each warning emitted for `record`.
+    fn warnings(&self, record: &Record) -> Vec<String> {
+        let message = record.args().to_string();
+        let prefix = format!("[{} {}]", record.level(), record.target());
+        if message.is_empty() {
+            return vec![prefix];
+        }
+        message
+            .lines()
+            .map(|line| match line {
+                "" => prefix.clone(),
+                line => format!("{} {}", prefix, line),
+            })
+            .collect()
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        for warning in self.warnings(record) {
+            crate::warning!("{}", warning);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    fn log(logger: &Logger, level: Level, message: &str) -> String {
+        crate::output::capture(|| {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target("bindgen::ir")
+                    .args(format_args!("{}", message))
+                    .build(),
+            );
+        })
+    }
+
+    #[test]
+    fn single_line() {
+        insta::assert_snapshot!(
+            log(&Logger::new(), Level::Error, "unknown type `__m128`"),
+            @"cargo:warning=[ERROR bindgen::ir] unknown type `__m128`\n"
+        );
+    }
+
+    #[test]
+    fn multiple_lines() {
+        insta::assert_snapshot!(
+            log(&Logger::new(), Level::Warn, "first\nsecond\n\nfourth"),
+            @r###"
+        cargo:warning=[WARN bindgen::ir] first
+        cargo:warning=[WARN bindgen::ir] second
+        cargo:warning=[WARN bindgen::ir]
+        cargo:warning=[WARN bindgen::ir] fourth
+        "###
+        );
+    }
+
+    #[test]
+    fn level() {
+        let mut logger = Logger::new();
+        assert_eq!(log(&logger, Level::Info, "hidden"), "");
+
+        logger.level(LevelFilter::Debug);
+        assert_eq!(log(&logger, Level::Trace, "hidden"), "");
+        assert_eq!(
+            log(&logger, Level::Debug, ""),
+            "cargo:warning=[DEBUG bindgen::ir]\n"
+        );
+    }
+}
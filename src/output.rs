@@ -0,0 +1,247 @@
+use crate::Location;
+use std::{
+    cell::{Cell, RefCell},
+    fmt, io,
+    sync::{Mutex, MutexGuard},
+};
+
+thread_local! {
+    /// Output captured on this thread instead of being written to stdout.
+    static CAPTURED: RefCell<Option<Captured>> = const { RefCell::new(None) };
+
+    /// The location of the macro call whose line is being written on this
+    /// thread.
+    static LOCATION: Cell<Option<Location>> = const { Cell::new(None) };
+}
+
+/// Output captured by [`capture`] or [`capture_lines`].
+#[derive(Default)]
+struct Captured {
+    text: Vec<u8>,
+    /// Each line of `text` that was written with `write_fmt`, and the location
+    /// of the macro call that wrote it, if any.
+    lines: Vec<(String, Option<Location>)>,
+}
+
+/// A function called with each line the macros write, on any thread, and the
+/// location of the macro call if the line was written by one.
+type Observer = Box<dyn Fn(&str, Option<Location>) + Send>;
+
+static OBSERVERS: Mutex<Vec<Observer>> = Mutex::new(Vec::new());
+
+/// Every path emitted with `rerun-if-changed`, in order.
+static RERUN_PATHS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The stream that macros without a `to:` argument write to.
+///
+/// This writes to stdout, unless output is being captured on the current
+/// thread by [`capture`].
+#[doc(hidden)]
+pub struct Stdout(());
+
+#[doc(hidden)]
+pub fn stdout() -> Stdout {
+    Stdout(())
+}
+
+impl io::Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let captured = CAPTURED.with(|captured| match &mut *captured.borrow_mut() {
+            Some(captured) => {
+                captured.text.extend_from_slice(buf);
+                true
+            }
+            None => false,
+        });
+        if captured {
+            Ok(buf.len())
+        } else {
+            io::stdout().write(buf)
+        }
+    }
+
+    /// Writes the formatted text in one go, so that lines from different
+    /// threads are not interleaved, then passes it to the observers.
+    fn write_fmt(&mut self, args: fmt::Arguments) -> io::Result<()> {
+        let text = args.to_string();
+        self.write_all(text.as_bytes())?;
+        let location = LOCATION.with(Cell::get);
+
+        CAPTURED.with(|captured| {
+            if let Some(captured) = &mut *captured.borrow_mut() {
+                let lines = text.lines().map(|line| (line.to_owned(), location));
+                captured.lines.extend(lines);
+            }
+        });
+
+        for line in text.lines() {
+            if let Some(path) = line.strip_prefix("cargo:rerun-if-changed=") {
+                lock(&RERUN_PATHS).push(path.to_owned());
+            }
+        }
+
+        let observers = lock(&OBSERVERS);
+        if !observers.is_empty() {
+            for line in text.lines() {
+                for observer in observers.iter() {
+                    observer(line, location);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+/// A line that a macro is emitting, from when it is created until it is
+/// dropped.
+#[doc(hidden)]
+pub struct Emitting {
+    location: Location,
+    previous: Option<Location>,
+    annotate: bool,
+}
+
+impl Emitting {
+    /// Mirrors `line` if debugging is enabled, and attributes everything
+    /// written on this thread to `location` until the result is dropped.
+    pub fn new(location: &Location, line: &fmt::Arguments) -> Self {
+        let annotate = crate::debug::mirror(location, line);
+        Emitting {
+            location: *location,
+            previous: LOCATION.with(|current| current.replace(Some(*location))),
+            annotate,
+        }
+    }
+
+    /// Follows the line with a warning with its location, if
+    /// `CARGO_EMIT_DEBUG` is `warn`.
+    ///
+    /// The warning is written to stdout even if the line was written to
+    /// another stream, since only Cargo reads it.
+    pub fn annotate(&self) {
+        if self.annotate {
+            use io::Write as _;
+            writeln!(stdout(), "cargo:warning=# emitted at {}", self.location).unwrap();
+        }
+    }
+}
+
+impl Drop for Emitting {
+    fn drop(&mut self) {
+        LOCATION.with(|current| current.set(self.previous));
+    }
+}
+
+/// Calls `observer` with every line that is emitted from now on, whether it
+/// is written to stdout or captured.
+///
+/// `observer` must not emit anything itself.
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+pub(crate) fn observe<F: Fn(&str, Option<Location>) + Send + 'static>(observer: F) {
+    lock(&OBSERVERS).push(Box::new(observer));
+}
+
+/// Returns every path emitted with `rerun-if-changed` so far, whether it was
+/// written to stdout or captured.
+pub(crate) fn rerun_paths() -> Vec<String> {
+    lock(&RERUN_PATHS).clone()
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+/// Runs `f`, returning everything the macros wrote to stdout on this thread.
+///
+/// Captures can be nested, in which case the innermost one receives the
+/// output.
+#[cfg(test)]
+pub(crate) fn capture<F: FnOnce()>(f: F) -> String {
+    String::from_utf8_lossy(&capture_with(f).text).into_owned()
+}
+
+/// Runs `f`, returning each line the macros wrote to stdout on this thread,
+/// with the location of the macro call that wrote it, if any.
+pub(crate) fn capture_lines<F: FnOnce()>(f: F) -> Vec<(String, Option<Location>)> {
+    capture_with(f).lines
+}
+
+fn capture_with<F: FnOnce()>(f: F) -> Captured {
+    /// Restores the previous capture, even if `f` panics.
+    struct Guard(Option<Option<Captured>>);
+
+    impl Guard {
+        fn restore(&mut self) -> Captured {
+            let previous = self.0.take().unwrap_or_default();
+            CAPTURED
+                .with(|captured| std::mem::replace(&mut *captured.borrow_mut(), previous))
+                .unwrap_or_default()
+        }
+    }
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            if self.0.is_some() {
+                self.restore();
+            }
+        }
+    }
+
+    let previous = CAPTURED.with(|captured| captured.borrow_mut().replace(Captured::default()));
+    let mut guard = Guard(Some(previous));
+    f();
+    guard.restore()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Location;
+    use std::sync::{Arc, Mutex};
+    #[test]
+    fn captures_macros() {
+        let output = super::capture(|| {
+            crate::rustc_cfg!("FEATURE");
+            let inner = super::capture(|| crate::warning!("INNER"));
+            assert_eq!(inner, "cargo:warning=INNER\n");
+            crate::rustc_link_lib!("NAME" => "static");
+        });
+        assert_eq!(
+            output,
+            "cargo:rustc-cfg=FEATURE\n\
+             cargo:rustc-link-lib=static=NAME\n"
+        );
+    }
+
+    #[test]
+    fn observes_locations() {
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        super::observe({
+            let observed = observed.clone();
+            move |line, location| {
+                if line.contains("observes_locations") {
+                    super::lock(&observed).push((line.to_owned(), location));
+                }
+            }
+        });
+
+        let line = line!();
+        super::capture(|| {
+            crate::rustc_cfg!("observes_locations");
+            crate::Directive::RustcCfg("observes_locations".into()).emit();
+        });
+        assert_eq!(
+            *super::lock(&observed),
+            [
+                (
+                    "cargo:rustc-cfg=observes_locations".to_owned(),
+                    Some(Location::new(file!(), line + 2, module_path!()))
+                ),
+                ("cargo:rustc-cfg=observes_locations".to_owned(), None),
+            ]
+        );
+    }
+}
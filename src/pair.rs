@@ -13,39 +13,57 @@
 /// This can be used to emit arbitrary user-defined metadata.
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// cargo_emit::pair!("root", "/path/to/root");
+/// # }
 /// ```
 ///
 /// or, in case you want it to emit to a custom stream:
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let mut stdout = std::io::stdout();
 /// cargo_emit::pair!(
 ///     to: stdout,
 ///     "root", "/path/to/root"
 /// );
+/// # }
 /// ```
 ///
 /// The `$key` and `$value` parameters get concatenated into a single formatting
 /// string. Formatting runtime values can be done by passing subsequent values.
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let name = "foo";
 /// cargo_emit::pair!("{lib}dir", "/path/to/{lib}", lib = name);
+/// # }
 /// ```
 ///
 /// [build script outputs]: https://doc.rust-lang.org/cargo/reference/build-scripts.html#outputs-of-the-build-script
 #[macro_export]
 macro_rules! pair {
     (to: $stream:expr, $key:expr, $value:expr $(, $($args:tt)*)?) => {{
+        $crate::__validate!(key, $key);
+        $crate::__validate!(value, $value);
+
         #[allow(unused_imports)]
-        use std::{fmt::Write as _, io::Write as _};
+        use $crate::__private::{FmtWrite as _, IoWrite as _};
+
+        // `match` keeps the arguments alive while the line is used twice.
+        match format_args!(concat!("cargo:", $key, "=", $value) $(, $($args)*)?) {
+            line => {
+                let location = $crate::Location::new(file!(), line!(), module_path!());
+                let emitting = $crate::__private::Emitting::new(&location, &line);
 
-        #[allow(clippy::explicit_write)]
-        writeln!($stream, concat!("cargo:", $key, "=", $value) $(, $($args)*)?).unwrap()
+                #[allow(clippy::explicit_write)]
+                writeln!($stream, "{}", line).unwrap();
+                emitting.annotate();
+            }
+        }
     }};
     ($key:expr, $value:expr $(, $($args:tt)*)?) => {
-        $crate::pair!(to: std::io::stdout(), $key, $value $(, $($args)*)?);
+        $crate::pair!(to: $crate::__private::stdout(), $key, $value $(, $($args)*)?);
     };
 }
 
@@ -53,7 +71,7 @@ macro_rules! pair {
 mod tests {
     #[test]
     fn single_literal() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::pair!(
                     to: output,
@@ -66,7 +84,7 @@ mod tests {
 
     #[test]
     fn single_with_key_formatted_by_index() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::pair!(
                     to: output,
@@ -79,7 +97,7 @@ mod tests {
 
     #[test]
     fn single_with_key_formatted_by_name() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::pair!(
                     to: output,
@@ -92,7 +110,7 @@ mod tests {
 
     #[test]
     fn single_with_value_formatted_by_index() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::pair!(
                     to: output,
@@ -105,7 +123,7 @@ mod tests {
 
     #[test]
     fn single_with_value_formatted_by_name() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::pair!(
                     to: output,
@@ -118,7 +136,7 @@ mod tests {
 
     #[test]
     fn single_with_key_and_value_formatted_by_index() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::pair!(
                     to: output,
@@ -131,7 +149,7 @@ mod tests {
 
     #[test]
     fn single_with_key_and_value_formatted_by_name() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::pair!(
                     to: output,
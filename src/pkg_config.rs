@@ -0,0 +1,953 @@
+//! Reading [pkg-config] `.pc` files without the `pkg-config` binary.
+//!
+//! [`Config::probe`] searches `PKG_CONFIG_PATH` and `PKG_CONFIG_LIBDIR` for
+//! the requested package, resolves its variables and `Requires`, and returns
+//! a [`Library`] that can emit the matching
+//! [`rustc_link_search!`](crate::rustc_link_search) and
+//! [`rustc_link_lib!`](crate::rustc_link_lib) lines. Cargo is also told to
+//! rerun the build script if any of the `.pc` files read or any of the
+//! environment variables consulted change.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cargo_emit::pkg_config::Config;
+//!
+//! let library = Config::new()
+//!     .atleast_version("1.1")
+//!     .statik(true)
+//!     .probe("openssl")
+//!     .unwrap();
+//!
+//! library.emit();
+//! ```
+//!
+//! [pkg-config]: https://www.freedesktop.org/wiki/Software/pkg-config/
+
+use crate::{
+    flags::{flag_value, split},
+    Directive, LinkArgScope, LinkKind, SearchKind,
+};
+use std::{
+    cmp::Ordering, collections::HashMap, env, error, ffi::OsString, fmt, fs, io, path::PathBuf,
+};
+
+/// The environment variables consulted when searching for `.pc` files.
+const ENV_VARS: [&str; 2] = ["PKG_CONFIG_PATH", "PKG_CONFIG_LIBDIR"];
+
+/// Options for finding a package.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    min_version: Option<String>,
+    statik: bool,
+}
+
+impl Config {
+    /// Creates a configuration that accepts any version and links
+    /// dynamically.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requires the package to be at least version `version`.
+    pub fn atleast_version(&mut self, version: &str) -> &mut Self {
+        self.min_version = Some(version.to_owned());
+        self
+    }
+
+    /// Whether to link statically, like `pkg-config --static`.
+    ///
+    /// This pulls in `Libs.private` and the libraries of `Requires.private`,
+    /// and links a library as `static` whenever a `lib$name.a` exists in one
+    /// of the package's `-L` directories.
+    pub fn statik(&mut self, statik: bool) -> &mut Self {
+        self.statik = statik;
+        self
+    }
+
+    /// Finds the package `name` and resolves everything needed to link it.
+    pub fn probe(&self, name: &str) -> Result<Library, Error> {
+        self.probe_with(name, |key| env::var_os(key))
+    }
+
+    fn probe_with<F>(&self, name: &str, var: F) -> Result<Library, Error>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let mut resolver = Resolver {
+            dirs: search_dirs(&var),
+            statik: self.statik,
+            packages: Vec::new(),
+            indices: HashMap::new(),
+            linked: HashMap::new(),
+            order: Vec::new(),
+        };
+
+        let constraint = self
+            .min_version
+            .as_ref()
+            .map(|version| (Op::GreaterEqual, version.clone()));
+        let root = resolver.visit(name, constraint.as_ref(), true)?;
+
+        let mut library = Library {
+            name: name.to_owned(),
+            version: resolver.packages[root].version.clone(),
+            libs: Vec::new(),
+            link_paths: Vec::new(),
+            frameworks: Vec::new(),
+            framework_paths: Vec::new(),
+            link_args: Vec::new(),
+            include_paths: Vec::new(),
+            defines: Vec::new(),
+            pc_files: resolver.packages.iter().map(|p| p.path.clone()).collect(),
+            env_vars: ENV_VARS.iter().map(|&var| var.to_owned()).collect(),
+            statik: self.statik,
+        };
+
+        for &(index, link) in resolver.order.iter().rev() {
+            let package = &resolver.packages[index];
+            library.add_cflags(&package.cflags);
+            if link {
+                library.add_libs(&package.libs);
+                if self.statik {
+                    library.add_libs(&package.libs_private);
+                }
+            }
+        }
+
+        dedup_last(&mut library.libs);
+        dedup_last(&mut library.frameworks);
+        dedup_first(&mut library.link_paths);
+        dedup_first(&mut library.framework_paths);
+        dedup_first(&mut library.include_paths);
+        dedup_first(&mut library.defines);
+        dedup_first(&mut library.link_args);
+
+        Ok(library)
+    }
+}
+
+/// A package found by [`Config::probe`], including everything it requires.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Library {
+    /// The name the package was probed by.
+    pub name: String,
+    /// The package's `Version`.
+    pub version: String,
+    /// Libraries to link, from `-l` flags.
+    pub libs: Vec<String>,
+    /// Directories to search for libraries, from `-L` flags.
+    pub link_paths: Vec<PathBuf>,
+    /// Frameworks to link, from `-framework` flags.
+    pub frameworks: Vec<String>,
+    /// Directories to search for frameworks, from `-F` flags.
+    pub framework_paths: Vec<PathBuf>,
+    /// Any other `Libs` flags, passed to the linker as-is.
+    pub link_args: Vec<String>,
+    /// Header directories, from `-I` flags.
+    pub include_paths: Vec<PathBuf>,
+    /// Preprocessor definitions, from `-D` flags.
+    pub defines: Vec<(String, Option<String>)>,
+    /// Every `.pc` file that was read.
+    pub pc_files: Vec<PathBuf>,
+    /// Every environment variable that was consulted.
+    pub env_vars: Vec<String>,
+    statik: bool,
+}
+
+impl Library {
+    /// Returns the directives needed to link this library, in order.
+    pub fn directives(&self) -> Vec<Directive> {
+        let mut directives = Vec::new();
+
+        for var in &self.env_vars {
+            directives.push(Directive::RerunIfEnvChanged(var.clone()));
+        }
+        for file in &self.pc_files {
+            directives.push(Directive::RerunIfChanged(file.display().to_string()));
+        }
+        for path in &self.link_paths {
+            directives.push(Directive::RustcLinkSearch {
+                path: path.display().to_string(),
+                kind: Some(SearchKind::Native),
+            });
+        }
+        for path in &self.framework_paths {
+            directives.push(Directive::RustcLinkSearch {
+                path: path.display().to_string(),
+                kind: Some(SearchKind::Framework),
+            });
+        }
+        for lib in &self.libs {
+            directives.push(Directive::RustcLinkLib {
+                name: lib.clone(),
+                kind: self.link_kind(lib),
+                modifiers: Vec::new(),
+            });
+        }
+        for framework in &self.frameworks {
+            directives.push(Directive::RustcLinkLib {
+                name: framework.clone(),
+                kind: Some(LinkKind::Framework),
+                modifiers: Vec::new(),
+            });
+        }
+        for arg in &self.link_args {
+            directives.push(Directive::RustcLinkArg {
+                scope: LinkArgScope::All,
+                arg: arg.clone(),
+            });
+        }
+
+        directives
+    }
+
+    /// Emits the directives needed to link this library to Cargo.
+    pub fn emit(&self) {
+        for directive in self.directives() {
+            directive.emit();
+        }
+    }
+
+    /// Emits the directives needed to link this library to `stream`.
+    pub fn emit_to<W: fmt::Write + ?Sized>(&self, stream: &mut W) {
+        for directive in self.directives() {
+            directive.emit_to(stream);
+        }
+    }
+
+    fn link_kind(&self, lib: &str) -> Option<LinkKind> {
+        let archive = format!("lib{}.a", lib);
+        if self.statik
+            && self
+                .link_paths
+                .iter()
+                .any(|dir| dir.join(&archive).is_file())
+        {
+            Some(LinkKind::Static)
+        } else {
+            None
+        }
+    }
+
+    fn add_libs(&mut self, flags: &[String]) {
+        let mut flags = flags.iter();
+        while let Some(flag) = flags.next() {
+            if flag == "-framework" {
+                if let Some(framework) = flags.next() {
+                    self.frameworks.push(framework.clone());
+                }
+            } else if let Some(path) = flag_value(flag, "-L", &mut flags) {
+                self.link_paths.push(path.into());
+            } else if let Some(path) = flag_value(flag, "-F", &mut flags) {
+                self.framework_paths.push(path.into());
+            } else if flag.starts_with("-l") && !flag.starts_with("-l:") {
+                if let Some(name) = flag_value(flag, "-l", &mut flags) {
+                    self.libs.push(name);
+                }
+            } else {
+                self.link_args.push(flag.clone());
+            }
+        }
+    }
+
+    fn add_cflags(&mut self, flags: &[String]) {
+        let mut flags = flags.iter();
+        while let Some(flag) = flags.next() {
+            if let Some(path) = flag_value(flag, "-I", &mut flags) {
+                self.include_paths.push(path.into());
+            } else if let Some(define) = flag_value(flag, "-D", &mut flags) {
+                let mut parts = define.splitn(2, '=');
+                let name = parts.next().unwrap_or_default().to_owned();
+                self.defines.push((name, parts.next().map(str::to_owned)));
+            }
+        }
+    }
+}
+
+/// The error returned by [`Config::probe`].
+#[derive(Debug)]
+pub enum Error {
+    /// No `.pc` file was found for the package.
+    NotFound {
+        /// The package that was searched for.
+        name: String,
+        /// The directories that were searched.
+        searched: Vec<PathBuf>,
+    },
+    /// A `.pc` file could not be read.
+    Io {
+        /// The file being read.
+        path: PathBuf,
+        /// The underlying error.
+        error: io::Error,
+    },
+    /// A `.pc` file is malformed.
+    Parse {
+        /// The file being parsed.
+        path: PathBuf,
+        /// The 1-based line number of the problem.
+        line: usize,
+        /// What went wrong.
+        message: String,
+    },
+    /// A package was found, but its version does not satisfy a constraint.
+    Version {
+        /// The package whose version was checked.
+        name: String,
+        /// The version that was found.
+        found: String,
+        /// The constraint that was not met, such as `>= 1.1`.
+        required: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFound { name, searched } => {
+                write!(f, "package `{}` was not found in", name)?;
+                if searched.is_empty() {
+                    return f.write_str(" any directory (PKG_CONFIG_LIBDIR is empty)");
+                }
+                for (i, dir) in searched.iter().enumerate() {
+                    let sep = if i == 0 { " " } else { ", " };
+                    write!(f, "{}{}", sep, dir.display())?;
+                }
+                Ok(())
+            }
+            Error::Io { path, error } => {
+                write!(f, "could not read `{}`: {}", path.display(), error)
+            }
+            Error::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            Error::Version {
+                name,
+                found,
+                required,
+            } => write!(
+                f,
+                "package `{}` has version {}, but {} is required",
+                name, found, required
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the directories to search, in order of precedence.
+fn search_dirs<F>(var: &F) -> Vec<PathBuf>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    let mut dirs = Vec::new();
+
+    if let Some(paths) = var("PKG_CONFIG_PATH") {
+        dirs.extend(env::split_paths(&paths));
+    }
+
+    match var("PKG_CONFIG_LIBDIR") {
+        Some(paths) => dirs.extend(env::split_paths(&paths)),
+        None => {
+            dirs.push("/usr/local/lib/pkgconfig".into());
+            dirs.push("/usr/local/share/pkgconfig".into());
+            if let Some(multiarch) = var("TARGET").as_ref().and_then(multiarch) {
+                dirs.push(format!("/usr/lib/{}/pkgconfig", multiarch).into());
+            }
+            dirs.push("/usr/lib64/pkgconfig".into());
+            dirs.push("/usr/lib/pkgconfig".into());
+            dirs.push("/usr/share/pkgconfig".into());
+        }
+    }
+
+    dirs.retain(|dir| !dir.as_os_str().is_empty());
+    dirs
+}
+
+/// Returns the Debian multiarch tuple for a Linux target triple.
+fn multiarch(target: &OsString) -> Option<String> {
+    let target = target.to_str()?;
+    let parts: Vec<&str> = target.split('-').collect();
+    match parts.as_slice() {
+        [arch, _, "linux", abi] => {
+            let arch = match *arch {
+                "i586" | "i686" => "i386",
+                arch => arch,
+            };
+            Some(format!("{}-linux-{}", arch, abi))
+        }
+        _ => None,
+    }
+}
+
+/// A parsed `.pc` file.
+struct Package {
+    path: PathBuf,
+    version: String,
+    requires: Vec<Requirement>,
+    requires_private: Vec<Requirement>,
+    libs: Vec<String>,
+    libs_private: Vec<String>,
+    cflags: Vec<String>,
+}
+
+/// A single entry of a `Requires` field.
+struct Requirement {
+    name: String,
+    constraint: Option<(Op, String)>,
+}
+
+/// Walks the `Requires` graph, loading each package at most once.
+struct Resolver {
+    dirs: Vec<PathBuf>,
+    statik: bool,
+    packages: Vec<Package>,
+    indices: HashMap<String, usize>,
+    /// Whether each visited package has had its libraries pulled in.
+    linked: HashMap<usize, bool>,
+    /// Visited packages in post-order, with whether to link each.
+    order: Vec<(usize, bool)>,
+}
+
+impl Resolver {
+    fn visit(
+        &mut self,
+        name: &str,
+        constraint: Option<&(Op, String)>,
+        link: bool,
+    ) -> Result<usize, Error> {
+        let index = self.load(name)?;
+
+        if let Some((op, version)) = constraint {
+            let found = &self.packages[index].version;
+            if !op.matches(compare_versions(found, version)) {
+                return Err(Error::Version {
+                    name: name.to_owned(),
+                    found: found.clone(),
+                    required: format!("{} {}", op, version),
+                });
+            }
+        }
+
+        match self.linked.get(&index) {
+            Some(&linked) if linked || !link => return Ok(index),
+            _ => {
+                self.linked.insert(index, link);
+            }
+        }
+
+        let requires = std::mem::take(&mut self.packages[index].requires);
+        let requires_private = std::mem::take(&mut self.packages[index].requires_private);
+
+        let mut result = Ok(());
+        for requirement in &requires {
+            result = result.and_then(|_| self.require(requirement, link));
+        }
+        for requirement in &requires_private {
+            result = result.and_then(|_| self.require(requirement, link && self.statik));
+        }
+
+        self.packages[index].requires = requires;
+        self.packages[index].requires_private = requires_private;
+        result?;
+
+        self.order.push((index, link));
+        Ok(index)
+    }
+
+    fn require(&mut self, requirement: &Requirement, link: bool) -> Result<(), Error> {
+        self.visit(&requirement.name, requirement.constraint.as_ref(), link)
+            .map(|_| ())
+    }
+
+    fn load(&mut self, name: &str) -> Result<usize, Error> {
+        if let Some(&index) = self.indices.get(name) {
+            return Ok(index);
+        }
+
+        let file_name = format!("{}.pc", name);
+        let path = self
+            .dirs
+            .iter()
+            .map(|dir| dir.join(&file_name))
+            .find(|path| path.is_file())
+            .ok_or_else(|| Error::NotFound {
+                name: name.to_owned(),
+                searched: self.dirs.clone(),
+            })?;
+
+        let contents = fs::read_to_string(&path).map_err(|error| Error::Io {
+            path: path.clone(),
+            error,
+        })?;
+        let package = parse(path, &contents)?;
+
+        let index = self.packages.len();
+        self.packages.push(package);
+        self.indices.insert(name.to_owned(), index);
+        Ok(index)
+    }
+}
+
+/// Parses the contents of the `.pc` file at `path`.
+fn parse(path: PathBuf, contents: &str) -> Result<Package, Error> {
+    let mut vars = HashMap::new();
+    if let Some(dir) = path.parent() {
+        vars.insert("pcfiledir".to_owned(), dir.display().to_string());
+    }
+
+    // Each field keeps its line number, for errors found once all are read.
+    let mut fields: HashMap<String, (usize, String)> = HashMap::new();
+    let error = |line: usize, message: String| Error::Parse {
+        path: path.clone(),
+        line,
+        message,
+    };
+
+    for (line_number, line) in logical_lines(contents) {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let ident_len = line
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(line.len());
+        let (ident, rest) = line.split_at(ident_len);
+        let rest = rest.trim_start();
+
+        let (is_field, value) = if let Some(value) = rest.strip_prefix(':') {
+            (true, value)
+        } else if let Some(value) = rest.strip_prefix('=') {
+            (false, value)
+        } else {
+            continue;
+        };
+
+        let value = expand(value.trim(), &vars).map_err(|message| error(line_number, message))?;
+        if is_field {
+            fields.insert(ident.to_owned(), (line_number, value));
+        } else {
+            vars.insert(ident.to_owned(), value);
+        }
+    }
+
+    let field = |name: &str| {
+        fields
+            .get(name)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_default()
+    };
+    let requires = |name: &str| match fields.get(name) {
+        Some((line_number, value)) => parse_requires(value)
+            .map_err(|message| error(*line_number, format!("{}: {}", name, message))),
+        None => Ok(Vec::new()),
+    };
+
+    Ok(Package {
+        version: field("Version").to_owned(),
+        requires: requires("Requires")?,
+        requires_private: requires("Requires.private")?,
+        libs: split(field("Libs")),
+        libs_private: split(field("Libs.private")),
+        cflags: split(field("Cflags")),
+        path,
+    })
+}
+
+/// Joins `\`-continued lines and strips `#` comments, yielding each logical
+/// line with the 1-based number of its first physical line.
+fn logical_lines(contents: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (index, raw) in contents.lines().enumerate() {
+        let (start, mut line) = current.take().unwrap_or((index + 1, String::new()));
+
+        let mut chars = raw.chars().peekable();
+        let mut continued = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&'#') => {
+                    line.push('#');
+                    chars.next();
+                }
+                '\\' if chars.peek().is_none() => continued = true,
+                '#' => break,
+                c => line.push(c),
+            }
+        }
+
+        if continued {
+            current = Some((start, line));
+        } else {
+            lines.push((start, line));
+        }
+    }
+
+    lines.extend(current);
+    lines
+}
+
+/// Substitutes `${var}` references and `$$` escapes in `value`.
+fn expand(value: &str, vars: &HashMap<String, String>) -> Result<String, String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{') {
+            let end = after
+                .find('}')
+                .ok_or_else(|| "unterminated variable reference".to_owned())?;
+            let name = &after[..end];
+            let value = vars
+                .get(name)
+                .ok_or_else(|| format!("undefined variable `{}`", name))?;
+            result.push_str(value);
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+        }
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Parses a `Requires` field, such as `foo >= 1.0, bar`.
+fn parse_requires(s: &str) -> Result<Vec<Requirement>, String> {
+    let is_sep = |c: char| c.is_whitespace() || c == ',';
+    let is_op = |c: char| c == '<' || c == '>' || c == '=' || c == '!';
+
+    let mut requirements = Vec::new();
+    let mut rest = s.trim_start_matches(is_sep);
+
+    while !rest.is_empty() {
+        let end = rest.find(|c| is_sep(c) || is_op(c)).unwrap_or(rest.len());
+        let name = rest[..end].to_owned();
+        rest = rest[end..].trim_start();
+
+        let mut constraint = None;
+        if rest.starts_with(is_op) {
+            let end = rest.find(|c| !is_op(c)).unwrap_or(rest.len());
+            let op = Op::parse(&rest[..end])
+                .ok_or_else(|| format!("unknown operator `{}`", &rest[..end]))?;
+            rest = rest[end..].trim_start();
+
+            let end = rest.find(is_sep).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("missing version after `{} {}`", name, op));
+            }
+            constraint = Some((op, rest[..end].to_owned()));
+            rest = &rest[end..];
+        }
+
+        requirements.push(Requirement { name, constraint });
+        rest = rest.trim_start_matches(is_sep);
+    }
+
+    Ok(requirements)
+}
+
+/// A version comparison operator.
+#[derive(Clone, Copy)]
+enum Op {
+    Less,
+    LessEqual,
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    Greater,
+}
+
+impl Op {
+    fn parse(s: &str) -> Option<Op> {
+        match s {
+            "<" => Some(Op::Less),
+            "<=" => Some(Op::LessEqual),
+            "=" => Some(Op::Equal),
+            "!=" => Some(Op::NotEqual),
+            ">=" => Some(Op::GreaterEqual),
+            ">" => Some(Op::Greater),
+            _ => None,
+        }
+    }
+
+    fn matches(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Less => ordering == Ordering::Less,
+            Op::LessEqual => ordering != Ordering::Greater,
+            Op::Equal => ordering == Ordering::Equal,
+            Op::NotEqual => ordering != Ordering::Equal,
+            Op::GreaterEqual => ordering != Ordering::Less,
+            Op::Greater => ordering == Ordering::Greater,
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Op::Less => "<",
+            Op::LessEqual => "<=",
+            Op::Equal => "=",
+            Op::NotEqual => "!=",
+            Op::GreaterEqual => ">=",
+            Op::Greater => ">",
+        })
+    }
+}
+
+/// Compares versions the way pkg-config does: segment by segment, with
+/// numeric segments compared numerically and newer than alphabetic ones.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn segments(s: &str) -> Vec<&str> {
+        let mut segments = Vec::new();
+        let mut rest = s;
+        loop {
+            rest = rest.trim_start_matches(|c: char| !c.is_ascii_alphanumeric());
+            let first = match rest.chars().next() {
+                Some(c) => c,
+                None => return segments,
+            };
+            let end = if first.is_ascii_digit() {
+                rest.find(|c: char| !c.is_ascii_digit())
+            } else {
+                rest.find(|c: char| !c.is_ascii_alphabetic())
+            }
+            .unwrap_or(rest.len());
+            segments.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+
+    let (a, b) = (segments(a), segments(b));
+    for (a, b) in a.iter().zip(&b) {
+        let a_numeric = a.starts_with(|c: char| c.is_ascii_digit());
+        let b_numeric = b.starts_with(|c: char| c.is_ascii_digit());
+        let ordering = match (a_numeric, b_numeric) {
+            (true, true) => {
+                let a = a.trim_start_matches('0');
+                let b = b.trim_start_matches('0');
+                a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+            }
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Removes duplicates, keeping the last occurrence of each item.
+///
+/// This is what linkers need for libraries: a library must come after
+/// everything that depends on it.
+fn dedup_last<T: PartialEq>(items: &mut Vec<T>) {
+    let mut i = 0;
+    while i < items.len() {
+        if items[i + 1..].contains(&items[i]) {
+            items.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Removes duplicates, keeping the first occurrence of each item.
+fn dedup_first<T: PartialEq>(items: &mut Vec<T>) {
+    let mut i = 0;
+    while i < items.len() {
+        if items[..i].contains(&items[i]) {
+            items.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn probe(config: &Config, dir: &Path, name: &str) -> Result<Library, Error> {
+        let dir = dir.as_os_str().to_owned();
+        config.probe_with(name, |key| match key {
+            "PKG_CONFIG_PATH" => Some(dir.clone()),
+            "PKG_CONFIG_LIBDIR" => Some(OsString::new()),
+            _ => None,
+        })
+    }
+
+    fn output(library: &Library, dir: &Path) -> String {
+        crate::capture_output(|output| library.emit_to(output))
+            .replace(&dir.display().to_string(), "$DIR")
+    }
+
+    #[test]
+    fn single() {
+        let dir = crate::temp_dir("pkg-config-single");
+        fs::write(
+            dir.join("foo.pc"),
+            "prefix=/opt/foo\n\
+             libdir=${prefix}/lib # comment\n\
+             \n\
+             Name: foo\n\
+             Version: 1.2.3\n\
+             Libs: -L${libdir} -lfoo -Wl,--as-needed\n\
+             Cflags: -I${prefix}/include -DFOO=1\n",
+        )
+        .unwrap();
+
+        let library = probe(&Config::new(), &dir, "foo").unwrap();
+        assert_eq!(library.version, "1.2.3");
+        assert_eq!(library.include_paths, [PathBuf::from("/opt/foo/include")]);
+        assert_eq!(library.defines, [("FOO".to_owned(), Some("1".to_owned()))]);
+
+        insta::assert_snapshot!(
+            output(&library, &dir),
+            @"cargo:rerun-if-env-changed=PKG_CONFIG_PATH\n\
+              cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR\n\
+              cargo:rerun-if-changed=$DIR/foo.pc\n\
+              cargo:rustc-link-search=native=/opt/foo/lib\n\
+              cargo:rustc-link-lib=foo\n\
+              cargo:rustc-link-arg=-Wl,--as-needed\n"
+        );
+    }
+
+    #[test]
+    fn requires() {
+        let dir = crate::temp_dir("pkg-config-requires");
+        fs::write(
+            dir.join("app.pc"),
+            "Version: 1\nRequires: net >= 2.0, base\nLibs: -lapp\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("net.pc"),
+            "Version: 2.10\nRequires: base\nRequires.private: z\nLibs: -L/net -lnet\n",
+        )
+        .unwrap();
+        fs::write(dir.join("base.pc"), "Version: 1\nLibs: -lbase\n").unwrap();
+        fs::write(
+            dir.join("z.pc"),
+            "Version: 1\nLibs: -lz\nLibs.private: -lm\n",
+        )
+        .unwrap();
+
+        let library = probe(&Config::new(), &dir, "app").unwrap();
+        assert_eq!(library.libs, ["app", "net", "base"]);
+        assert_eq!(library.pc_files.len(), 4);
+
+        let library = probe(Config::new().statik(true), &dir, "app").unwrap();
+        assert_eq!(library.libs, ["app", "net", "z", "m", "base"]);
+    }
+
+    #[test]
+    fn static_archives() {
+        let dir = crate::temp_dir("pkg-config-static");
+        fs::write(
+            dir.join("foo.pc"),
+            "Version: 1\nLibs: -L${pcfiledir} -lfoo\nLibs.private: -lpthread\n",
+        )
+        .unwrap();
+        fs::write(dir.join("libfoo.a"), "").unwrap();
+
+        let library = probe(Config::new().statik(true), &dir, "foo").unwrap();
+        insta::assert_snapshot!(
+            output(&library, &dir),
+            @"cargo:rerun-if-env-changed=PKG_CONFIG_PATH\n\
+              cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR\n\
+              cargo:rerun-if-changed=$DIR/foo.pc\n\
+              cargo:rustc-link-search=native=$DIR\n\
+              cargo:rustc-link-lib=static=foo\n\
+              cargo:rustc-link-lib=pthread\n"
+        );
+    }
+
+    #[test]
+    fn frameworks() {
+        let dir = crate::temp_dir("pkg-config-frameworks");
+        fs::write(
+            dir.join("sdl.pc"),
+            "Version: 2\nLibs: -F /Library/Frameworks -framework Cocoa \"-lSDL 2\"\n",
+        )
+        .unwrap();
+
+        let library = probe(&Config::new(), &dir, "sdl").unwrap();
+        assert_eq!(library.frameworks, ["Cocoa"]);
+        assert_eq!(
+            library.framework_paths,
+            [PathBuf::from("/Library/Frameworks")]
+        );
+        assert_eq!(library.libs, ["SDL 2"]);
+    }
+
+    #[test]
+    fn errors() {
+        let dir = crate::temp_dir("pkg-config-errors");
+        fs::write(dir.join("old.pc"), "Version: 1.9\n").unwrap();
+        fs::write(dir.join("bad.pc"), "Version: 1\nLibs: ${missing}\n").unwrap();
+        fs::write(
+            dir.join("requires.pc"),
+            "Version: 1\nRequires: foo\n# comment\nRequires.private: bar >=\n",
+        )
+        .unwrap();
+
+        let error = probe(Config::new().atleast_version("1.10"), &dir, "old").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "package `old` has version 1.9, but >= 1.10 is required"
+        );
+
+        let error = probe(&Config::new(), &dir, "bad").unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("bad.pc:2: undefined variable `missing`"));
+
+        let error = probe(&Config::new(), &dir, "requires").unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("requires.pc:4: Requires.private: missing version after `bar >=`"));
+
+        let error = probe(&Config::new(), &dir, "none").unwrap_err();
+        assert!(matches!(error, Error::NotFound { .. }));
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(compare_versions("1.2.3", "1.2.3"), Ordering::Equal);
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.1"), Ordering::Less);
+        assert_eq!(compare_versions("1.0a", "1.0"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0.1", "1.0a"), Ordering::Greater);
+    }
+
+    #[test]
+    fn multiarch_dirs() {
+        let target = OsString::from("i686-unknown-linux-gnu");
+        assert_eq!(multiarch(&target).as_deref(), Some("i386-linux-gnu"));
+        assert_eq!(multiarch(&"x86_64-apple-darwin".into()), None);
+    }
+}
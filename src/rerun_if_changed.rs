@@ -27,15 +27,18 @@
 /// already know.
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// cargo_emit::rerun_if_changed!(
 ///     "/path/to/resource1",
 ///     "/path/to/resource2",
 /// );
+/// # }
 /// ```
 ///
 /// or, in case you want it to emit to a custom stream:
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let mut stdout = std::io::stdout();
 /// // ..
 /// cargo_emit::rerun_if_changed!(
@@ -43,14 +46,18 @@
 ///     "/path/to/resource1",
 ///     "/path/to/resource2",
 /// );
+/// # }
 /// ```
 #[macro_export]
 macro_rules! rerun_if_changed {
-    (to: $stream:expr, $($path:expr),+ $(,)?) => {
-        $($crate::pair!(to: $stream, "rerun-if-changed", "{}", $path);)+
-    };
+    (to: $stream:expr, $($path:expr),+ $(,)?) => {{
+        $(
+            $crate::__validate!(value, $path);
+            $crate::pair!(to: $stream, "rerun-if-changed", "{}", $path);
+        )+
+    }};
     ($($path:expr),+ $(,)?) => {
-        $crate::rerun_if_changed!(to: std::io::stdout(), $($path),+);
+        $crate::rerun_if_changed!(to: $crate::__private::stdout(), $($path),+);
     };
 }
 
@@ -60,7 +67,7 @@ mod tests {
 
     #[test]
     fn single_literal() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rerun_if_changed!(
                     to: output,
@@ -73,7 +80,7 @@ mod tests {
 
     #[test]
     fn single_expression() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rerun_if_changed!(
                     to: output,
@@ -86,7 +93,7 @@ mod tests {
 
     #[test]
     fn multiple_literals() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rerun_if_changed!(
                     to: output,
@@ -105,7 +112,7 @@ mod tests {
 
     #[test]
     fn multiple_expressions() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rerun_if_changed!(
                     to: output,
@@ -124,7 +131,7 @@ mod tests {
 
     #[test]
     fn multiple_mixed() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rerun_if_changed!(
                     to: output,
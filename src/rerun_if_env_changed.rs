@@ -22,28 +22,35 @@
 /// already know.
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// cargo_emit::rerun_if_env_changed!("MY_DEPENDENCY", "PATH");
+/// # }
 /// ```
 ///
 /// or, in case you want it to emit to a custom stream:
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let mut stdout = std::io::stdout();
 /// cargo_emit::rerun_if_env_changed!(
 ///     to: stdout,
 ///     "MY_DEPENDENCY", "PATH"
 /// );
+/// # }
 /// ```
 ///
 /// [`rerun_if_changed!`]: macro.rerun_if_changed.html
 /// [`rerun_if_env_changed!`]: macro.rerun_if_env_changed.html
 #[macro_export]
 macro_rules! rerun_if_env_changed {
-    (to: $stream:expr, $($key:expr),+ $(,)?) => {
-        $($crate::pair!(to: $stream, "rerun-if-env-changed", "{}", $key);)+
-    };
+    (to: $stream:expr, $($key:expr),+ $(,)?) => {{
+        $(
+            $crate::__validate!(env_key, $key);
+            $crate::pair!(to: $stream, "rerun-if-env-changed", "{}", $key);
+        )+
+    }};
     ($($key:expr),+ $(,)?) => {
-        $crate::rerun_if_env_changed!(to: std::io::stdout(), "{}", $($key),+);
+        $crate::rerun_if_env_changed!(to: $crate::__private::stdout(), $($key),+);
     };
 }
 
@@ -51,7 +58,7 @@ macro_rules! rerun_if_env_changed {
 mod tests {
     #[test]
     fn single() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rerun_if_env_changed!(
                     to: output,
@@ -64,7 +71,7 @@ mod tests {
 
     #[test]
     fn multiple() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rerun_if_env_changed!(
                     to: output,
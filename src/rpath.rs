@@ -0,0 +1,385 @@
+//! Embedding runtime library search paths that are relative to the binary.
+//!
+//! Binaries shipped next to their shared libraries, such as a `bin/foo` with a
+//! `lib/libbar.so`, find them at runtime through an rpath relative to the
+//! directory of the binary or library being loaded. [`Rpath`] takes relative
+//! directories and anchors them for the target:
+//!
+//! | Target             | Anchor         | Argument                            |
+//! |--------------------|----------------|-------------------------------------|
+//! | ELF, such as Linux | `$ORIGIN`      | `-Wl,-rpath=$ORIGIN/../lib`         |
+//! | Apple              | `@loader_path` | `-Wl,-rpath,@loader_path/../lib`    |
+//!
+//! The arguments are rendered with [`link_options`](crate::link_options), so
+//! they are wrapped for the target's linker in the same way. `rustc` passes
+//! link arguments to the linker without a shell, so `$ORIGIN` needs no
+//! quoting. Directories that already start with `$ORIGIN` or `@loader_path`
+//! are translated to the anchor of the target. Other directories starting
+//! with `@`, such as `@executable_path/../Frameworks`, are passed through
+//! as-is on Apple targets, and refused elsewhere. Windows has no rpath, whichever
+//! linker is used, and gets a warning for each directory instead.
+//!
+//! Absolute directories are passed through as-is, but as they point into the
+//! build machine, they are refused in release builds unless
+//! [allowed](Rpath::allow_absolute). Refused directories are returned as an
+//! [`Error`] before anything is emitted, so that the build script can fail.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cargo_emit::{rpath::Rpath, LinkArgScope};
+//!
+//! Rpath::new(LinkArgScope::Bins).dir("../lib").emit().unwrap();
+//! ```
+//!
+//! emits on Linux:
+//!
+//! ```text
+//! cargo:rustc-link-arg-bins=-Wl,-rpath=$ORIGIN/../lib
+//! ```
+
+use crate::{
+    link_options::{LinkOption, LinkOptions, LinkerFlavor, Target},
+    Directive, LinkArgScope,
+};
+use std::{
+    env, error,
+    ffi::OsString,
+    fmt,
+    path::{Component, Path, PathBuf},
+};
+
+/// The anchor of relative rpaths on ELF targets.
+const ORIGIN: &str = "$ORIGIN";
+
+/// The anchor of relative rpaths on Apple targets.
+const LOADER_PATH: &str = "@loader_path";
+
+/// A builder for the rpath of the targets in a [`LinkArgScope`].
+#[derive(Clone, Debug)]
+pub struct Rpath {
+    dirs: Vec<PathBuf>,
+    scope: LinkArgScope,
+    allow_absolute: bool,
+}
+
+impl Rpath {
+    /// Creates an empty rpath for the targets in `scope`, usually
+    /// [`LinkArgScope::Bins`] or [`LinkArgScope::Cdylib`].
+    pub fn new(scope: LinkArgScope) -> Self {
+        Rpath {
+            dirs: Vec::new(),
+            scope,
+            allow_absolute: false,
+        }
+    }
+
+    /// Adds `dir`, relative to the directory of the binary or library unless
+    /// it is absolute.
+    pub fn dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.dirs.push(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Whether absolute directories are allowed in release builds.
+    ///
+    /// Defaults to `false`, as such paths usually only exist on the build
+    /// machine. Allow them for directories the package is installed into,
+    /// such as `/opt/foo/lib`.
+    pub fn allow_absolute(&mut self, allow: bool) -> &mut Self {
+        self.allow_absolute = allow;
+        self
+    }
+
+    /// Returns the link argument directives for the directories, or the first
+    /// directory that cannot be embedded.
+    pub fn directives(&self) -> Result<Vec<Directive>, Error> {
+        self.directives_with(|key| env::var_os(key))
+    }
+
+    fn directives_with<F>(&self, var: F) -> Result<Vec<Directive>, Error>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let os = var("CARGO_CFG_TARGET_OS");
+        let windows = match &os {
+            Some(os) => os == "windows",
+            None => cfg!(windows),
+        };
+        if windows {
+            return Ok(self
+                .dirs
+                .iter()
+                .map(|dir| {
+                    Directive::Warning(format!(
+                        "Windows has no rpath, so `{}` is ignored",
+                        dir.display()
+                    ))
+                })
+                .collect());
+        }
+
+        let anchor = match Target::with(&var).flavor {
+            LinkerFlavor::Ld64 => LOADER_PATH,
+            LinkerFlavor::Gnu | LinkerFlavor::Msvc => ORIGIN,
+        };
+        let release = var("PROFILE") == Some("release".into());
+
+        let mut options = LinkOptions::new();
+        options.scope(self.scope.clone());
+        for dir in &self.dirs {
+            let path = dir
+                .to_str()
+                .ok_or_else(|| Error::NotUtf8 { dir: dir.clone() })?;
+            if dir.is_absolute() {
+                if release && !self.allow_absolute {
+                    return Err(Error::Absolute { dir: dir.clone() });
+                }
+                options.option(LinkOption::Rpath(path.to_owned()));
+            } else if path.starts_with('@') && !is_anchored(path, LOADER_PATH) {
+                if anchor != LOADER_PATH {
+                    return Err(Error::AppleOnly { dir: dir.clone() });
+                }
+                options.option(LinkOption::Rpath(path.to_owned()));
+            } else {
+                options.option(LinkOption::Rpath(anchored(path, anchor)));
+            }
+        }
+        Ok(options.directives_with(var))
+    }
+
+    /// Emits the rpath to Cargo, unless a directory cannot be embedded.
+    pub fn emit(&self) -> Result<(), Error> {
+        for directive in self.directives()? {
+            directive.emit();
+        }
+        Ok(())
+    }
+
+    /// Emits the rpath to `stream`, unless a directory cannot be embedded.
+    pub fn emit_to<W: fmt::Write + ?Sized>(&self, stream: &mut W) -> Result<(), Error> {
+        for directive in self.directives()? {
+            directive.emit_to(stream);
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when a directory cannot be embedded in the rpath.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The directory is not valid UTF-8.
+    NotUtf8 {
+        /// The directory.
+        dir: PathBuf,
+    },
+    /// The directory is absolute, in a release build, without
+    /// [`Rpath::allow_absolute`].
+    Absolute {
+        /// The directory.
+        dir: PathBuf,
+    },
+    /// The directory starts with an anchor only Apple targets have, such as
+    /// `@executable_path`.
+    AppleOnly {
+        /// The directory.
+        dir: PathBuf,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotUtf8 { dir } => write!(f, "the rpath `{}` is not valid UTF-8", dir.display()),
+            Error::Absolute { dir } => write!(
+                f,
+                "refusing to embed the absolute rpath `{}` in a release build, as it points \
+                 into the build machine; use a path relative to the binary, or \
+                 `Rpath::allow_absolute` if it is where the package is installed",
+                dir.display()
+            ),
+            Error::AppleOnly { dir } => write!(
+                f,
+                "the rpath `{}` is only supported on Apple targets",
+                dir.display()
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Returns whether `path` starts with the `anchor` component.
+fn is_anchored(path: &str, anchor: &str) -> bool {
+    match path.strip_prefix(anchor) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Joins the relative `path` onto `anchor`, replacing an anchor it already
+/// starts with.
+fn anchored(path: &str, anchor: &str) -> String {
+    let rest = [ORIGIN, "${ORIGIN}", LOADER_PATH]
+        .iter()
+        .find(|prefix| is_anchored(path, prefix))
+        .map_or(path, |prefix| &path[prefix.len()..]);
+
+    let mut anchored = anchor.to_owned();
+    for component in Path::new(rest).components() {
+        match component {
+            Component::Normal(name) => {
+                anchored.push('/');
+                anchored.push_str(&name.to_string_lossy());
+            }
+            Component::ParentDir => anchored.push_str("/.."),
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    anchored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(
+        vendor: &'static str,
+        target_env: &'static str,
+        profile: &'static str,
+    ) -> impl Fn(&str) -> Option<OsString> {
+        move |key| match key {
+            "CARGO_CFG_TARGET_OS" => Some(
+                match vendor {
+                    "apple" => "macos",
+                    "pc" => "windows",
+                    _ => "linux",
+                }
+                .into(),
+            ),
+            "CARGO_CFG_TARGET_ENV" => Some(target_env.into()),
+            "CARGO_CFG_TARGET_VENDOR" => Some(vendor.into()),
+            "CARGO_CFG_TARGET_FAMILY" => {
+                Some(if vendor == "pc" { "windows" } else { "unix" }.into())
+            }
+            "PROFILE" => Some(profile.into()),
+            _ => None,
+        }
+    }
+
+    fn render<F: Fn(&str) -> Option<OsString>>(rpath: &Rpath, var: F) -> String {
+        crate::capture_output(|output| {
+            for directive in rpath.directives_with(var).unwrap() {
+                directive.emit_to(output);
+            }
+        })
+    }
+
+    fn dirs(scope: LinkArgScope) -> Rpath {
+        let mut rpath = Rpath::new(scope);
+        rpath
+            .dir("../lib")
+            .dir(".")
+            .dir("./plugins/")
+            .dir("@loader_path/../Frameworks")
+            .dir("/opt/foo/lib");
+        rpath
+    }
+
+    #[test]
+    fn elf() {
+        insta::assert_snapshot!(
+            render(&dirs(LinkArgScope::Bins), target("unknown", "gnu", "debug")),
+            @r###"
+        cargo:rustc-link-arg-bins=-Wl,-rpath=$ORIGIN/../lib
+        cargo:rustc-link-arg-bins=-Wl,-rpath=$ORIGIN
+        cargo:rustc-link-arg-bins=-Wl,-rpath=$ORIGIN/plugins
+        cargo:rustc-link-arg-bins=-Wl,-rpath=$ORIGIN/../Frameworks
+        cargo:rustc-link-arg-bins=-Wl,-rpath=/opt/foo/lib
+        "###
+        );
+    }
+
+    #[test]
+    fn apple() {
+        insta::assert_snapshot!(
+            render(
+                Rpath::new(LinkArgScope::Cdylib { legacy: false })
+                    .dir("$ORIGIN/../lib")
+                    .dir("${ORIGIN}"),
+                target("apple", "", "debug")
+            ),
+            @r###"
+        cargo:rustc-link-arg-cdylib=-Wl,-rpath,@loader_path/../lib
+        cargo:rustc-link-arg-cdylib=-Wl,-rpath,@loader_path
+        "###
+        );
+    }
+
+    #[test]
+    fn release() {
+        let error = dirs(LinkArgScope::Bins)
+            .directives_with(target("unknown", "gnu", "release"))
+            .unwrap_err();
+        assert_eq!(
+            error,
+            Error::Absolute {
+                dir: "/opt/foo/lib".into()
+            }
+        );
+        insta::assert_snapshot!(
+            error,
+            @"refusing to embed the absolute rpath `/opt/foo/lib` in a release build, as it points into the build machine; use a path relative to the binary, or `Rpath::allow_absolute` if it is where the package is installed"
+        );
+        insta::assert_snapshot!(
+            render(
+                Rpath::new(LinkArgScope::Bins)
+                    .dir("/opt/foo/lib")
+                    .allow_absolute(true),
+                target("unknown", "gnu", "release")
+            ),
+            @"cargo:rustc-link-arg-bins=-Wl,-rpath=/opt/foo/lib"
+        );
+    }
+
+    #[test]
+    fn apple_anchors() {
+        let mut rpath = Rpath::new(LinkArgScope::Bins);
+        rpath
+            .dir("@executable_path/../Frameworks")
+            .dir("@rpath/Plugins");
+        insta::assert_snapshot!(
+            render(&rpath, target("apple", "", "debug")),
+            @r###"
+        cargo:rustc-link-arg-bins=-Wl,-rpath,@executable_path/../Frameworks
+        cargo:rustc-link-arg-bins=-Wl,-rpath,@rpath/Plugins
+        "###
+        );
+        assert_eq!(
+            rpath.directives_with(target("unknown", "gnu", "debug")),
+            Err(Error::AppleOnly {
+                dir: "@executable_path/../Frameworks".into()
+            })
+        );
+    }
+
+    #[test]
+    fn windows() {
+        let mut rpath = Rpath::new(LinkArgScope::Bins);
+        rpath.dir("../lib").dir("$ORIGIN");
+        let msvc = render(&rpath, target("pc", "msvc", "debug"));
+        insta::assert_snapshot!(
+            msvc,
+            @r###"
+        cargo:warning=Windows has no rpath, so `../lib` is ignored
+        cargo:warning=Windows has no rpath, so `$ORIGIN` is ignored
+        "###
+        );
+        assert_eq!(render(&rpath, target("pc", "gnu", "debug")), msvc);
+    }
+
+    #[test]
+    fn not_an_anchor() {
+        assert_eq!(anchored("$ORIGINAL/lib", ORIGIN), "$ORIGIN/$ORIGINAL/lib");
+    }
+}
@@ -11,16 +11,19 @@
 /// Runtime arguments can be passed directly.
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let flag1 = // ...
 /// # "";
 /// let flag2 = // ...
 /// # "";
 /// cargo_emit::rustc_cdylib_link_arg!(flag1, flag2);
+/// # }
 /// ```
 ///
 /// or, in case you want it to emit to a custom stream:
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let flag1 = // ...
 /// # "";
 /// let flag2 = // ...
@@ -30,14 +33,18 @@
 ///     to: stdout,
 ///     flag1, flag2
 /// );
+/// # }
 /// ```
 #[macro_export]
 macro_rules! rustc_cdylib_link_arg {
-    (to: $stream:expr, $($flag:expr),+ $(,)?) => {
-        $($crate::pair!(to: $stream, "rustc-cdylib-link-arg", "{}", $flag);)+
-    };
+    (to: $stream:expr, $($flag:expr),+ $(,)?) => {{
+        $(
+            $crate::__validate!(value, $flag);
+            $crate::pair!(to: $stream, "rustc-cdylib-link-arg", "{}", $flag);
+        )+
+    }};
     ($($flag:expr),+ $(,)?) => {
-        $crate::rustc_cdylib_link_arg!(to: std::io::stdout(), $($flag),+);
+        $crate::rustc_cdylib_link_arg!(to: $crate::__private::stdout(), $($flag),+);
     };
 }
 
@@ -45,7 +52,7 @@ macro_rules! rustc_cdylib_link_arg {
 mod tests {
     #[test]
     fn single() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_cdylib_link_arg!(
                     to: output,
@@ -58,7 +65,7 @@ mod tests {
 
     #[test]
     fn multiple() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_cdylib_link_arg!(
                     to: output,
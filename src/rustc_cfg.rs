@@ -11,6 +11,7 @@
 /// Useful for conditionally enabling certain code to run.
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// # struct Cargo;
 /// # impl Cargo {
 /// #   fn can_bench(&self) -> bool { true }
@@ -19,11 +20,13 @@
 /// if cargo.can_bench() {
 ///     cargo_emit::rustc_cfg!("bench");
 /// }
+/// # }
 /// ```
 ///
 /// or, in case you want it to emit to a custom stream:
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// # struct Cargo;
 /// # impl Cargo {
 /// #   fn can_bench(&self) -> bool { true }
@@ -36,6 +39,7 @@
 ///         "bench"
 ///     );
 /// }
+/// # }
 /// ```
 ///
 /// Then outside of `build.rs`:
@@ -48,11 +52,12 @@
 /// ```
 #[macro_export]
 macro_rules! rustc_cfg {
-    (to: $stream:expr, $feature:expr $(, $($args:tt)*)?) => {
+    (to: $stream:expr, $feature:expr $(, $($args:tt)*)?) => {{
+        $crate::__validate!(cfg_option, $feature);
         $crate::pair!(to: $stream, "rustc-cfg", $feature $(, $($args)+)?);
-    };
+    }};
     ($feature:expr $(, $($args:tt)*)?) => {
-        $crate::rustc_cfg!(to: std::io::stdout(), $feature $(, $($args)+)?);
+        $crate::rustc_cfg!(to: $crate::__private::stdout(), $feature $(, $($args)+)?);
     };
 }
 
@@ -60,7 +65,7 @@ macro_rules! rustc_cfg {
 mod tests {
     #[test]
     fn literal() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_cfg!(
                     to: output,
@@ -73,7 +78,7 @@ mod tests {
 
     #[test]
     fn formatted() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_cfg!(
                     to: output,
@@ -14,14 +14,17 @@
 /// string. Formatting runtime values can be done by passing subsequent values.
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let git_rev_hash = //
 /// # "0000111122223333444455556666777788889999";
 /// cargo_emit::rustc_env!("MY_HASH", "{}", git_rev_hash);
+/// # }
 /// ```
 ///
 /// or, in case you want it to emit to a custom stream:
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let mut stdout = std::io::stdout();
 /// let git_rev_hash = // ...
 /// # "0000111122223333444455556666777788889999";
@@ -29,14 +32,17 @@
 ///     to: stdout,
 ///    "MY_HASH", "{}", git_rev_hash
 /// );
+/// # }
 /// ```
 #[macro_export]
 macro_rules! rustc_env {
-    (to: $stream:expr, $key:expr, $value:expr $(, $($args:tt)*)?) => {
+    (to: $stream:expr, $key:expr, $value:expr $(, $($args:tt)*)?) => {{
+        $crate::__validate!(env_key, $key);
+        $crate::__validate!(value, $value);
         $crate::pair!(to: $stream, "rustc-env", concat!($key, "=", $value) $(, $($args)+)?);
-    };
+    }};
     ($key:expr, $value:expr $(, $($args:tt)*)?) => {
-        $crate::rustc_env!(to: std::io::stdout(), $key, $value $(, $($args)+)?);
+        $crate::rustc_env!(to: $crate::__private::stdout(), $key, $value $(, $($args)+)?);
     };
 }
 
@@ -44,7 +50,7 @@ macro_rules! rustc_env {
 mod tests {
     #[test]
     fn single_literal() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_env!(
                     to: output,
@@ -57,7 +63,7 @@ mod tests {
 
     #[test]
     fn single_with_key_formatted_by_index() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_env!(
                     to: output,
@@ -70,7 +76,7 @@ mod tests {
 
     #[test]
     fn single_with_key_formatted_by_name() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_env!(
                     to: output,
@@ -83,7 +89,7 @@ mod tests {
 
     #[test]
     fn single_with_value_formatted_by_index() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_env!(
                     to: output,
@@ -96,7 +102,7 @@ mod tests {
 
     #[test]
     fn single_with_value_formatted_by_name() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_env!(
                     to: output,
@@ -109,7 +115,7 @@ mod tests {
 
     #[test]
     fn single_with_key_and_value_formatted_by_index() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_env!(
                     to: output,
@@ -122,7 +128,7 @@ mod tests {
 
     #[test]
     fn single_with_key_and_value_formatted_by_name() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_env!(
                     to: output,
@@ -1,6 +1,8 @@
 /// Tells Cargo to pass `$flags` to the compiler.
 ///
-/// As of this writing, only `-l` and `-L` flags are supported.
+/// As of this writing, only `-l` and `-L` flags are supported. For strings
+/// with other flags, such as the output of `llvm-config --libs`, see the
+/// [`flags`](crate::flags) module.
 ///
 /// This is equivalent to:
 ///
@@ -14,25 +16,32 @@
 /// string. Formatting runtime values can be done by passing subsequent values.
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// cargo_emit::rustc_flags!("-l pthread");
+/// # }
 /// ```
 ///
 /// or, in case you want it to emit to a custom stream:
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let mut stdout = std::io::stdout();
 /// cargo_emit::rustc_flags!(
 ///     to: stdout,
 ///     "-l pthread"
 /// );
+/// # }
 /// ```
 #[macro_export]
 macro_rules! rustc_flags {
-    (to: $stream:expr, $($flags:expr),+ $(,)?) => {
-        $($crate::pair!(to: $stream, "rustc-flags", "{}", $flags);)+
-    };
+    (to: $stream:expr, $($flags:expr),+ $(,)?) => {{
+        $(
+            $crate::__validate!(value, $flags);
+            $crate::pair!(to: $stream, "rustc-flags", "{}", $flags);
+        )+
+    }};
     ($($flags:expr),+ $(,)?) => {
-        $crate::rustc_flags!(to: std::io::stdout(), $($flags),+);
+        $crate::rustc_flags!(to: $crate::__private::stdout(), $($flags),+);
     };
 }
 
@@ -40,7 +49,7 @@ macro_rules! rustc_flags {
 mod tests {
     #[test]
     fn single() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_flags!(
                     to: output,
@@ -53,7 +62,7 @@ mod tests {
 
     #[test]
     fn multiple() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_flags!(
                     to: output,
@@ -12,6 +12,7 @@
 /// # Examples
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let flag1 = // ...
 /// # "";
 /// let flag2 = // ...
@@ -19,11 +20,13 @@
 /// cargo_emit::rustc_link_arg!(
 ///     flag1, flag2
 /// );
+/// # }
 /// ```
 ///
 /// or, in case you want it to emit to a custom stream:
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let flag1 = // ...
 /// # "";
 /// let flag2 = // ...
@@ -33,14 +36,18 @@
 ///     to: stdout,
 ///     flag1, flag2
 /// );
+/// # }
 /// ```
 #[macro_export]
 macro_rules! rustc_link_arg {
-    (to: $stream:expr, $($flag:expr),+ $(,)?) => {
-        $($crate::pair!(to: $stream, "rustc-link-arg", "{}", $flag);)+
-    };
+    (to: $stream:expr, $($flag:expr),+ $(,)?) => {{
+        $(
+            $crate::__validate!(value, $flag);
+            $crate::pair!(to: $stream, "rustc-link-arg", "{}", $flag);
+        )+
+    }};
     ($($flag:expr),+ $(,)?) => {
-        $crate::rustc_link_arg!(to: std::io::stdout(), $($flag),+);
+        $crate::rustc_link_arg!(to: $crate::__private::stdout(), $($flag),+);
     };
 }
 
@@ -48,7 +55,7 @@ macro_rules! rustc_link_arg {
 mod tests {
     #[test]
     fn single() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_link_arg!(
                     to: output,
@@ -62,7 +69,7 @@ mod tests {
 
     #[test]
     fn multiple() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_link_arg!(
                     to: output,
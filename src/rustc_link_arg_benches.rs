@@ -0,0 +1,86 @@
+/// Tells Cargo to pass the `-C link-arg=$flag` option to the compiler,
+/// but only when building a benchmark target.
+/// Its usage is highly platform specific.
+/// It is useful to set a linker script or other linker options.
+///
+/// This is equivalent to:
+///
+/// ```
+/// println!("cargo:rustc-link-arg-benches=$flag");
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// let flag1 = // ...
+/// # "";
+/// let flag2 = // ...
+/// # "";
+/// cargo_emit::rustc_link_arg_benches!(
+///     flag1, flag2
+/// );
+/// # }
+/// ```
+///
+/// or, in case you want it to emit to a custom stream:
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// let flag1 = // ...
+/// # "";
+/// let flag2 = // ...
+/// # "";
+/// let mut stdout = std::io::stdout();
+/// cargo_emit::rustc_link_arg_benches!(
+///     to: stdout,
+///     flag1, flag2
+/// );
+/// # }
+/// ```
+#[macro_export]
+macro_rules! rustc_link_arg_benches {
+    (to: $stream:expr, $($flag:expr),+ $(,)?) => {{
+        $(
+            $crate::__validate!(value, $flag);
+            $crate::pair!(to: $stream, "rustc-link-arg-benches", "{}", $flag);
+        )+
+    }};
+    ($($flag:expr),+ $(,)?) => {
+        $crate::rustc_link_arg_benches!(to: $crate::__private::stdout(), $($flag),+);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn single() {
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                crate::rustc_link_arg_benches!(
+                    to: output,
+                    "ARG"
+                );
+            }),
+            @"cargo:rustc-link-arg-benches=ARG
+        "
+        );
+    }
+
+    #[test]
+    fn multiple() {
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                crate::rustc_link_arg_benches!(
+                    to: output,
+                    "ARG1",
+                    "ARG2"
+                );
+            }),
+            @r###"
+        cargo:rustc-link-arg-benches=ARG1
+        cargo:rustc-link-arg-benches=ARG2
+        "###
+        );
+    }
+}
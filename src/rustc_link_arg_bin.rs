@@ -9,36 +9,45 @@
 /// println!("cargo:rustc-link-arg-bin=$bin=$flag");
 /// ```
 ///
+/// Cargo fails the build if `$bin` is not a binary target of the package.
+/// The `bin_targets` module can check the name first.
+///
 /// # Examples
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// cargo_emit::rustc_link_arg_bin!(
 ///     "hello_world" => "-Wall"
 /// );
+/// # }
 /// ```
 ///
 /// or, in case you want it to emit to a custom stream:
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let mut stdout = std::io::stdout();
 /// cargo_emit::rustc_link_arg_bin!(
 ///     to: stdout,
 ///     "hello_world" => "-Wall"
 /// );
+/// # }
 /// ```
 #[macro_export]
 macro_rules! rustc_link_arg_bin {
-    (to: $stream:expr, $bin:expr => $flags:expr $(,)?) => {
+    (to: $stream:expr, $bin:expr => $flags:expr $(,)?) => {{
+        $crate::__validate!(bin_name, $bin);
+        $crate::__validate!(value, $flags);
         $crate::pair!(to: $stream, "rustc-link-arg-bin", "{}={}", $bin, $flags);
-    };
+    }};
     (to: $stream:expr, $($bin:expr=> $flags:expr),+ $(,)?) => { {
         $($crate::rustc_link_arg_bin!(to: $stream, $bin => $flags);)+
     } };
     ($bin:expr => $flags:expr $(,)?) => {
-        $crate::rustc_link_arg_bin!(to: std::io::stdout(), $bin => $flags);
+        $crate::rustc_link_arg_bin!(to: $crate::__private::stdout(), $bin => $flags);
     };
     ($($bin:expr=> $flags:expr),+ $(,)?) => { {
-        $crate::rustc_link_arg_bin!(to: std::io::stdout(), $($bin=> $flags),+);
+        $crate::rustc_link_arg_bin!(to: $crate::__private::stdout(), $($bin=> $flags),+);
     } };
 }
 
@@ -46,7 +55,7 @@ macro_rules! rustc_link_arg_bin {
 mod tests {
     #[test]
     fn single() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_link_arg_bin!(
                     to: output,
@@ -60,7 +69,7 @@ mod tests {
 
     #[test]
     fn multiple() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_link_arg_bin!(
                     to: output,
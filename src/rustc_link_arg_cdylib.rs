@@ -0,0 +1,90 @@
+/// Tells Cargo to pass the `-C link-arg=$flag` option to the compiler,
+/// but only when building a `cdylib` target.
+/// Its usage is highly platform specific.
+/// It is useful to set a linker script or other linker options.
+///
+/// This is equivalent to:
+///
+/// ```
+/// println!("cargo:rustc-link-arg-cdylib=$flag");
+/// ```
+///
+/// This is the newer spelling of
+/// [`rustc_cdylib_link_arg!`](crate::rustc_cdylib_link_arg), which is
+/// understood by older versions of Cargo.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// let flag1 = // ...
+/// # "";
+/// let flag2 = // ...
+/// # "";
+/// cargo_emit::rustc_link_arg_cdylib!(
+///     flag1, flag2
+/// );
+/// # }
+/// ```
+///
+/// or, in case you want it to emit to a custom stream:
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// let flag1 = // ...
+/// # "";
+/// let flag2 = // ...
+/// # "";
+/// let mut stdout = std::io::stdout();
+/// cargo_emit::rustc_link_arg_cdylib!(
+///     to: stdout,
+///     flag1, flag2
+/// );
+/// # }
+/// ```
+#[macro_export]
+macro_rules! rustc_link_arg_cdylib {
+    (to: $stream:expr, $($flag:expr),+ $(,)?) => {{
+        $(
+            $crate::__validate!(value, $flag);
+            $crate::pair!(to: $stream, "rustc-link-arg-cdylib", "{}", $flag);
+        )+
+    }};
+    ($($flag:expr),+ $(,)?) => {
+        $crate::rustc_link_arg_cdylib!(to: $crate::__private::stdout(), $($flag),+);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn single() {
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                crate::rustc_link_arg_cdylib!(
+                    to: output,
+                    "ARG"
+                );
+            }),
+            @"cargo:rustc-link-arg-cdylib=ARG
+        "
+        );
+    }
+
+    #[test]
+    fn multiple() {
+        insta::assert_snapshot!(
+            crate::capture_output(|output| {
+                crate::rustc_link_arg_cdylib!(
+                    to: output,
+                    "ARG1",
+                    "ARG2"
+                );
+            }),
+            @r###"
+        cargo:rustc-link-arg-cdylib=ARG1
+        cargo:rustc-link-arg-cdylib=ARG2
+        "###
+        );
+    }
+}
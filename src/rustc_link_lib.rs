@@ -11,16 +11,19 @@
 /// Useful for telling the linker what libraries should be linked.
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// cargo_emit::rustc_link_lib!(
 ///     "ssl", // same as `=> "dylib"`
 ///     "ruby" => "static",
 ///     "CoreFoundation" => "framework",
 /// );
+/// # }
 /// ```
 ///
 /// or, in case you want it to emit to a custom stream:
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let mut stdout = std::io::stdout();
 /// cargo_emit::rustc_link_lib!(
 ///     to: stdout,
@@ -28,26 +31,30 @@
 ///     "ruby" => "static",
 ///     "CoreFoundation" => "framework",
 /// );
+/// # }
 /// ```
 #[macro_export]
 macro_rules! rustc_link_lib {
-    (to: $stream:expr, $name:expr $(,)?) => {
+    (to: $stream:expr, $name:expr $(,)?) => {{
+        $crate::__validate!(link_name, $name);
         $crate::pair!(to: $stream, "rustc-link-lib", "{}", $name);
-    };
-    (to: $stream:expr, $name:expr => $kind:expr $(,)?) => {
+    }};
+    (to: $stream:expr, $name:expr => $kind:expr $(,)?) => {{
+        $crate::__validate!(link_name, $name);
+        $crate::__validate!(link_kind, $kind);
         $crate::pair!(to: $stream, "rustc-link-lib", "{}={}", $kind, $name);
-    };
+    }};
     (to: $stream:expr, $($name:expr $(=> $kind:expr)?),+ $(,)?) => { {
         $($crate::rustc_link_lib!(to: $stream, $name $(=> $kind)?);)+
     } };
     ($name:expr $(,)?) => {
-        $crate::rustc_link_lib!(to: std::io::stdout(), $name);
+        $crate::rustc_link_lib!(to: $crate::__private::stdout(), $name);
     };
     ($name:expr => $kind:expr $(,)?) => {
-        $crate::rustc_link_lib!(to: std::io::stdout(), $name => $kind);
+        $crate::rustc_link_lib!(to: $crate::__private::stdout(), $name => $kind);
     };
     ($($name:expr $(=> $kind:expr)?),+ $(,)?) => { {
-        $crate::rustc_link_lib!(to: std::io::stdout(), $($name $(=> $kind)?),+);
+        $crate::rustc_link_lib!(to: $crate::__private::stdout(), $($name $(=> $kind)?),+);
     } };
 }
 
@@ -55,7 +62,7 @@ macro_rules! rustc_link_lib {
 mod tests {
     #[test]
     fn single_name_literal() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_link_lib!(
                     to: output,
@@ -68,7 +75,7 @@ mod tests {
 
     #[test]
     fn single_name_expression() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 let name = "NAME";
                 crate::rustc_link_lib!(
@@ -82,20 +89,20 @@ mod tests {
 
     #[test]
     fn single_name_literal_with_kind() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_link_lib!(
                     to: output,
-                    "NAME" => "KIND"
+                    "NAME" => "static"
                 );
             }),
-            @"cargo:rustc-link-lib=KIND=NAME\n"
+            @"cargo:rustc-link-lib=static=NAME\n"
         );
     }
 
     #[test]
     fn single_name_expression_with_kind() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 let name = "NAME";
                 let kind = "KIND";
@@ -110,17 +117,17 @@ mod tests {
 
     #[test]
     fn multiple_name_expression_with_kind() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 let name2 = "NAME2";
                 let kind2 = "KIND2";
                 crate::rustc_link_lib!(
                     to: output,
-                    "NAME1" => "KIND1",
+                    "NAME1" => "static",
                     name2 => kind2,
                 );
             }),
-            @"cargo:rustc-link-lib=KIND1=NAME1\n\
+            @"cargo:rustc-link-lib=static=NAME1\n\
               cargo:rustc-link-lib=KIND2=NAME2\n"
         );
     }
@@ -11,41 +11,48 @@
 /// Useful for telling the linker where a path can be found.
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// cargo_emit::rustc_link_search!(
 ///     "path/to/ssl/lib/", // same as `=> "all"`
 ///     "path/to/ruby/lib/" => "native",
 /// );
+/// # }
 /// ```
 ///
 /// or, in case you want it to emit to a custom stream:
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let mut stdout = std::io::stdout();
 /// cargo_emit::rustc_link_search!(
 ///     to: stdout,
 ///     "path/to/ssl/lib/", // same as `=> "all"`
 ///     "path/to/ruby/lib/" => "native",
 /// );
+/// # }
 /// ```
 #[macro_export]
 macro_rules! rustc_link_search {
-    (to: $stream:expr, $path:expr $(,)?) => {
+    (to: $stream:expr, $path:expr $(,)?) => {{
+        $crate::__validate!(value, $path);
         $crate::pair!(to: $stream, "rustc-link-search", "{}", $path);
-    };
-    (to: $stream:expr, $path:expr => $kind:expr $(,)?) => {
+    }};
+    (to: $stream:expr, $path:expr => $kind:expr $(,)?) => {{
+        $crate::__validate!(value, $path);
+        $crate::__validate!(search_kind, $kind);
         $crate::pair!(to: $stream, "rustc-link-search", "{}={}", $kind, $path);
-    };
+    }};
     (to: $stream:expr, $($path:expr $(=> $kind:expr)?),+ $(,)?) => { {
         $($crate::rustc_link_search!(to: $stream, $path $(=> $kind)?);)+
     } };
     ($path:expr $(,)?) => {
-        $crate::rustc_link_search!(to: std::io::stdout(), $path);
+        $crate::rustc_link_search!(to: $crate::__private::stdout(), $path);
     };
     ($path:expr => $kind:expr $(,)?) => {
-        $crate::rustc_link_search!(to: std::io::stdout(), $path => $kind);
+        $crate::rustc_link_search!(to: $crate::__private::stdout(), $path => $kind);
     };
     ($($path:expr $(=> $kind:expr)?),+ $(,)?) => { {
-        $crate::rustc_link_search!(to: std::io::stdout(), $($path $(=> $kind)?),+);
+        $crate::rustc_link_search!(to: $crate::__private::stdout(), $($path $(=> $kind)?),+);
     } };
 }
 
@@ -53,7 +60,7 @@ macro_rules! rustc_link_search {
 mod tests {
     #[test]
     fn single_name_literal() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_link_search!(
                     to: output,
@@ -66,7 +73,7 @@ mod tests {
 
     #[test]
     fn single_name_expression() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 let path = "PATH";
                 crate::rustc_link_search!(
@@ -80,20 +87,20 @@ mod tests {
 
     #[test]
     fn single_name_literal_with_kind() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::rustc_link_search!(
                     to: output,
-                    "PATH" => "KIND"
+                    "PATH" => "native"
                 );
             }),
-            @"cargo:rustc-link-search=KIND=PATH\n"
+            @"cargo:rustc-link-search=native=PATH\n"
         );
     }
 
     #[test]
     fn single_name_expression_with_kind() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 let path = "PATH";
                 let kind = "KIND";
@@ -108,17 +115,17 @@ mod tests {
 
     #[test]
     fn multiple_name_expression_with_kind() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 let path2 = "PATH2";
                 let kind2 = "KIND2";
                 crate::rustc_link_search!(
                     to: output,
-                    "PATH1" => "KIND1",
+                    "PATH1" => "native",
                     path2 => kind2,
                 );
             }),
-            @"cargo:rustc-link-search=KIND1=PATH1\n\
+            @"cargo:rustc-link-search=native=PATH1\n\
               cargo:rustc-link-search=KIND2=PATH2\n"
         );
     }
@@ -0,0 +1,481 @@
+//! The environment variable overrides of `-sys` crates.
+//!
+//! Build scripts that link a native library `foo` conventionally let whoever
+//! runs the build override how it is found:
+//!
+//! | Variable          | Effect                                                |
+//! |-------------------|-------------------------------------------------------|
+//! | `FOO_LIB_DIR`     | links the library in this directory                   |
+//! | `FOO_INCLUDE_DIR` | the headers of the library, exported as `include`     |
+//! | `FOO_LIB_NAME`    | the name passed to `-l`, instead of `foo`             |
+//! | `FOO_STATIC`      | links the library statically                          |
+//! | `FOO_DYNAMIC`     | links the library dynamically                         |
+//! | `FOO_NO_VENDOR`   | uses the system library even if a vendored one exists |
+//!
+//! [`SysLib::resolve`] reads them and decides on a [`Mode`]: an explicit
+//! `FOO_LIB_DIR` wins, then a vendored copy if the crate has one and
+//! `FOO_NO_VENDOR` is not set, and otherwise the system library. Each variable
+//! is looked up with the per-target precedence of
+//! [`target_env_var`](fn@crate::target_env_var), and, as with that function,
+//! Cargo is told right away to rerun the build script if any of the keys
+//! change, even if resolving fails. Flags count as set unless their value is
+//! `0`.
+//!
+//! The [`Resolved`] library emits its `rustc_link_search!` and
+//! `rustc_link_lib!` lines, and its `include` and `root` directories as
+//! metadata, which crates depending on the package see as `DEP_FOO_INCLUDE`
+//! and `DEP_FOO_ROOT` if it sets `links = "foo"`. A system library found
+//! another way, such as with [`pkg_config`](crate::pkg_config), which emits
+//! its own `rustc_link_lib!`, can skip the line with
+//! [`SysLib::link_system`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cargo_emit::sys_lib::{Mode, SysLib};
+//! # fn build_vendored() -> std::path::PathBuf { unimplemented!() }
+//!
+//! let mut foo = SysLib::new("foo")
+//!     .vendored(cfg!(feature = "vendored"))
+//!     .resolve()
+//!     .unwrap();
+//!
+//! if foo.mode == Mode::Vendored {
+//!     foo.root(build_vendored());
+//! }
+//! foo.emit();
+//! ```
+
+use crate::{
+    find_library::env_var_name,
+    target_env_var::{self, TargetEnvVar},
+    Directive, LinkKind, SearchKind,
+};
+use std::{
+    env, error,
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// How the library is found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// From `FOO_LIB_DIR`.
+    Explicit,
+    /// Built from a copy vendored with the crate.
+    Vendored,
+    /// Installed on the system, to be found by the linker or with
+    /// [`pkg_config`](crate::pkg_config).
+    System,
+}
+
+/// A builder for the overrides of a native library.
+#[derive(Clone, Debug)]
+pub struct SysLib {
+    name: String,
+    vendored: bool,
+    kind: Option<LinkKind>,
+    link_system: bool,
+}
+
+impl SysLib {
+    /// Creates the overrides of the library `name`, whose variables start with
+    /// `name` in uppercase with `-` and `.` replaced by `_`.
+    pub fn new(name: &str) -> Self {
+        SysLib {
+            name: name.to_owned(),
+            vendored: false,
+            kind: None,
+            link_system: true,
+        }
+    }
+
+    /// Whether the crate can build a vendored copy of the library, usually
+    /// `cfg!(feature = "vendored")`.
+    ///
+    /// Defaults to `false`.
+    pub fn vendored(&mut self, vendored: bool) -> &mut Self {
+        self.vendored = vendored;
+        self
+    }
+
+    /// Sets how the library is linked when neither `FOO_STATIC` nor
+    /// `FOO_DYNAMIC` is set.
+    ///
+    /// Defaults to leaving it up to the compiler.
+    pub fn kind(&mut self, kind: LinkKind) -> &mut Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Whether a library found in [`Mode::System`] is linked with
+    /// `rustc_link_lib!`.
+    ///
+    /// Defaults to `true`. Set it to `false` when the library is found in a
+    /// way that links it already, such as with
+    /// [`pkg_config`](crate::pkg_config).
+    pub fn link_system(&mut self, link: bool) -> &mut Self {
+        self.link_system = link;
+        self
+    }
+
+    /// Reads the variables and decides how to find the library, telling
+    /// Cargo to rerun the build script if any of them change.
+    pub fn resolve(&self) -> Result<Resolved, Error> {
+        self.resolve_with(|key| env::var_os(key))
+    }
+
+    fn resolve_with<F>(&self, var: F) -> Result<Resolved, Error>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let mut env_vars = Vec::new();
+        let mut lookup = |suffix: &str| {
+            let keys = target_env_var::keys(&env_var_name(&self.name, suffix), &var);
+            env_vars.extend(keys.iter().cloned());
+            target_env_var::find(keys, &var)
+        };
+        let lib_dir = lookup("LIB_DIR");
+        let include_dir = lookup("INCLUDE_DIR");
+        let lib_name = lookup("LIB_NAME");
+        let static_ = lookup("STATIC").filter(is_set);
+        let dynamic = lookup("DYNAMIC").filter(is_set);
+        let no_vendor = lookup("NO_VENDOR").filter(is_set);
+        for key in &env_vars {
+            crate::rerun_if_env_changed!(key);
+        }
+
+        let kind = match (static_, dynamic) {
+            (Some(static_), Some(dynamic)) => {
+                return Err(Error::Conflict {
+                    static_key: static_.key,
+                    dynamic_key: dynamic.key,
+                })
+            }
+            (Some(_), None) => Some(LinkKind::Static),
+            (None, Some(_)) => Some(LinkKind::Dylib),
+            (None, None) => self.kind,
+        };
+
+        let lib_dir = lib_dir.map(dir).transpose()?;
+        let mode = if lib_dir.is_some() {
+            Mode::Explicit
+        } else if self.vendored && no_vendor.is_none() {
+            Mode::Vendored
+        } else {
+            Mode::System
+        };
+
+        Ok(Resolved {
+            mode,
+            lib_name: match lib_name {
+                Some(TargetEnvVar { key, value }) => {
+                    value.into_string().map_err(|_| Error::NotUnicode { key })?
+                }
+                None => self.name.clone(),
+            },
+            kind,
+            link: mode != Mode::System || self.link_system,
+            lib_dir,
+            include_dir: include_dir.map(dir).transpose()?,
+            root: None,
+            env_vars,
+        })
+    }
+}
+
+/// Returns whether a flag variable is set to anything but `0`.
+fn is_set(var: &TargetEnvVar) -> bool {
+    var.value != "0"
+}
+
+/// Checks that a variable names an existing directory.
+fn dir(var: TargetEnvVar) -> Result<PathBuf, Error> {
+    let path = PathBuf::from(var.value);
+    if path.is_dir() {
+        Ok(path)
+    } else {
+        Err(Error::NotADirectory { key: var.key, path })
+    }
+}
+
+/// A native library resolved by [`SysLib::resolve`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Resolved {
+    /// How the library is found.
+    pub mode: Mode,
+    /// The name of the library, as passed to `-l`.
+    pub lib_name: String,
+    /// How the library is linked, if not left up to the compiler.
+    pub kind: Option<LinkKind>,
+    /// Whether the library is linked with `rustc_link_lib!`, which is only
+    /// skipped in [`Mode::System`] if [`SysLib::link_system`] is `false`.
+    pub link: bool,
+    /// The directory containing the library, if known.
+    pub lib_dir: Option<PathBuf>,
+    /// The directory containing the headers of the library, if known.
+    pub include_dir: Option<PathBuf>,
+    /// The directory the library was installed into, if known.
+    pub root: Option<PathBuf>,
+    /// Every environment variable that was consulted, which
+    /// [`SysLib::resolve`] has told Cargo to rerun the build script on.
+    pub env_vars: Vec<String>,
+}
+
+impl Resolved {
+    /// Sets the directory a vendored or system library was installed into,
+    /// using its `lib` and `include` directories unless they were overridden.
+    pub fn root<P: AsRef<Path>>(&mut self, root: P) -> &mut Self {
+        let root = root.as_ref();
+        if self.lib_dir.is_none() {
+            self.lib_dir = Some(root.join("lib"));
+        }
+        if self.include_dir.is_none() {
+            self.include_dir = Some(root.join("include"));
+        }
+        self.root = Some(root.to_owned());
+        self
+    }
+
+    /// Returns the directives needed to link the library and export its
+    /// directories, in order.
+    pub fn directives(&self) -> Vec<Directive> {
+        let mut directives = Vec::new();
+        if let Some(lib_dir) = &self.lib_dir {
+            directives.push(Directive::RustcLinkSearch {
+                path: lib_dir.display().to_string(),
+                kind: Some(SearchKind::Native),
+            });
+        }
+        if self.link {
+            directives.push(Directive::RustcLinkLib {
+                name: self.lib_name.clone(),
+                kind: self.kind,
+                modifiers: Vec::new(),
+            });
+        }
+        for (key, dir) in [("include", &self.include_dir), ("root", &self.root)] {
+            if let Some(dir) = dir {
+                directives.push(Directive::Pair {
+                    key: key.to_owned(),
+                    value: dir.display().to_string(),
+                });
+            }
+        }
+        directives
+    }
+
+    /// Emits the directives to Cargo.
+    pub fn emit(&self) {
+        for directive in self.directives() {
+            directive.emit();
+        }
+    }
+
+    /// Emits the directives to `stream`.
+    pub fn emit_to<W: fmt::Write + ?Sized>(&self, stream: &mut W) {
+        for directive in self.directives() {
+            directive.emit_to(stream);
+        }
+    }
+}
+
+/// The error returned by [`SysLib::resolve`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Both `FOO_STATIC` and `FOO_DYNAMIC` are set.
+    Conflict {
+        /// The key `FOO_STATIC` was read from.
+        static_key: String,
+        /// The key `FOO_DYNAMIC` was read from.
+        dynamic_key: String,
+    },
+    /// A directory variable does not name an existing directory.
+    NotADirectory {
+        /// The key the directory was read from.
+        key: String,
+        /// The directory.
+        path: PathBuf,
+    },
+    /// A variable is not valid Unicode.
+    NotUnicode {
+        /// The key of the variable.
+        key: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Conflict {
+                static_key,
+                dynamic_key,
+            } => write!(
+                f,
+                "`{}` and `{}` are both set; unset one of them",
+                static_key, dynamic_key
+            ),
+            Error::NotADirectory { key, path } => {
+                write!(f, "`{}` is not a directory: {}", key, path.display())
+            }
+            Error::NotUnicode { key } => write!(f, "`{}` is not valid Unicode", key),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Resolves `lib`, returning the reruns it emitted as well.
+    fn resolve_reruns(
+        lib: &SysLib,
+        vars: &[(&str, &OsString)],
+    ) -> (Result<Resolved, Error>, String) {
+        let mut resolved = None;
+        let output = crate::output::capture(|| {
+            resolved = Some(lib.resolve_with(|key| {
+                vars.iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, value)| (*value).clone())
+            }))
+        });
+        (resolved.unwrap(), output)
+    }
+
+    fn resolve(lib: &SysLib, vars: &[(&str, &OsString)]) -> Result<Resolved, Error> {
+        resolve_reruns(lib, vars).0
+    }
+
+    fn render(resolved: &Resolved) -> String {
+        crate::capture_output(|output| resolved.emit_to(output))
+    }
+
+    #[test]
+    fn explicit() {
+        let dir = crate::temp_dir("sys-lib-explicit").into_os_string();
+        let resolved = resolve(
+            SysLib::new("foo-bar").vendored(true),
+            &[
+                ("FOO_BAR_LIB_DIR", &dir),
+                ("FOO_BAR_INCLUDE_DIR", &dir),
+                ("FOO_BAR_LIB_NAME", &"foobar".into()),
+                ("FOO_BAR_STATIC", &"1".into()),
+            ],
+        )
+        .unwrap();
+        assert_eq!(resolved.mode, Mode::Explicit);
+        let dir = dir.to_string_lossy();
+        assert_eq!(
+            render(&resolved),
+            format!(
+                "cargo:rustc-link-search=native={dir}\n\
+                 cargo:rustc-link-lib=static=foobar\n\
+                 cargo:include={dir}\n",
+                dir = dir
+            )
+        );
+    }
+
+    #[test]
+    fn vendored() {
+        let mut resolved = resolve(
+            SysLib::new("foo").vendored(true).kind(LinkKind::Static),
+            &[("FOO_STATIC", &"0".into())],
+        )
+        .unwrap();
+        assert_eq!(resolved.mode, Mode::Vendored);
+        resolved.root("/out/foo");
+        insta::assert_snapshot!(render(&resolved), @r###"
+        cargo:rustc-link-search=native=/out/foo/lib
+        cargo:rustc-link-lib=static=foo
+        cargo:include=/out/foo/include
+        cargo:root=/out/foo
+        "###);
+    }
+
+    #[test]
+    fn system() {
+        let no_vendor = resolve(
+            SysLib::new("foo").vendored(true),
+            &[("FOO_NO_VENDOR", &"1".into()), ("FOO_DYNAMIC", &"1".into())],
+        )
+        .unwrap();
+        assert_eq!(no_vendor.mode, Mode::System);
+        insta::assert_snapshot!(render(&no_vendor), @"cargo:rustc-link-lib=dylib=foo");
+
+        let not_vendored = resolve(&SysLib::new("foo"), &[]).unwrap();
+        assert_eq!(not_vendored.mode, Mode::System);
+        assert_eq!(not_vendored.kind, None);
+
+        let not_linked = resolve(SysLib::new("foo").link_system(false), &[]).unwrap();
+        assert!(!not_linked.link);
+        assert_eq!(render(&not_linked), "");
+
+        let dir = crate::temp_dir("sys-lib-system").into_os_string();
+        let explicit = resolve(
+            SysLib::new("foo").link_system(false),
+            &[("FOO_LIB_DIR", &dir)],
+        )
+        .unwrap();
+        assert!(explicit.link);
+    }
+
+    #[test]
+    fn tracked() {
+        let (resolved, reruns) = resolve_reruns(
+            &SysLib::new("foo"),
+            &[
+                ("TARGET", &"aarch64-unknown-linux-gnu".into()),
+                ("HOST", &"x86_64-unknown-linux-gnu".into()),
+                ("FOO_STATIC_aarch64_unknown_linux_gnu", &"1".into()),
+            ],
+        );
+        let resolved = resolved.unwrap();
+        assert_eq!(resolved.kind, Some(LinkKind::Static));
+        assert_eq!(resolved.env_vars.len(), 24);
+        assert_eq!(
+            &resolved.env_vars[..4],
+            [
+                "FOO_LIB_DIR_aarch64-unknown-linux-gnu",
+                "FOO_LIB_DIR_aarch64_unknown_linux_gnu",
+                "TARGET_FOO_LIB_DIR",
+                "FOO_LIB_DIR",
+            ]
+        );
+        assert_eq!(
+            reruns.lines().nth(23),
+            Some("cargo:rerun-if-env-changed=FOO_NO_VENDOR")
+        );
+        assert_eq!(reruns.lines().count(), 24);
+        assert!(!resolved
+            .directives()
+            .iter()
+            .any(|directive| matches!(directive, Directive::RerunIfEnvChanged(_))));
+
+        let (error, reruns) = resolve_reruns(
+            &SysLib::new("foo"),
+            &[("FOO_STATIC", &"1".into()), ("FOO_DYNAMIC", &"1".into())],
+        );
+        assert!(error.is_err());
+        assert_eq!(reruns.lines().count(), 12);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |vars: &[(&str, &OsString)]| {
+            resolve(&SysLib::new("foo"), vars).unwrap_err().to_string()
+        };
+        insta::assert_snapshot!(
+            error(&[("FOO_STATIC", &"1".into()), ("FOO_DYNAMIC", &"yes".into())]),
+            @"`FOO_STATIC` and `FOO_DYNAMIC` are both set; unset one of them"
+        );
+        insta::assert_snapshot!(
+            error(&[("FOO_LIB_DIR", &"/nonexistent/cargo-emit".into())]),
+            @"`FOO_LIB_DIR` is not a directory: /nonexistent/cargo-emit"
+        );
+    }
+}
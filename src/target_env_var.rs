@@ -0,0 +1,167 @@
+//! Reading environment variables that can be set per target, like `cc` does.
+//!
+//! Cross-compiling build scripts let each tunable be set for one target, or
+//! for the host or target side of the build, before falling back to the plain
+//! variable. [`target_env_var`] checks the keys in the same order as the `cc`
+//! crate, for `TARGET=aarch64-unknown-linux-gnu`:
+//!
+//! 1. `FOO_aarch64-unknown-linux-gnu`
+//! 2. `FOO_aarch64_unknown_linux_gnu`
+//! 3. `TARGET_FOO`, or `HOST_FOO` if `TARGET` is the same as `HOST`
+//! 4. `FOO`
+//!
+//! A [`rerun_if_env_changed!`](crate::rerun_if_env_changed) is emitted for
+//! every key, whether it is set or not, so that setting a key with a higher
+//! precedence later reruns the build script.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! if let Some(var) = cargo_emit::target_env_var("FOO_LIB_DIR") {
+//!     cargo_emit::warning!("using {} from {}", var.value.to_string_lossy(), var.key);
+//! }
+//! ```
+
+use std::{env, ffi::OsString};
+
+/// Looks up `name` for the target being built, and emits a
+/// `rerun_if_env_changed!` for every key that was considered.
+///
+/// Returns `None` if none of the keys are set. See the
+/// [module documentation](mod@crate::target_env_var) for the order.
+pub fn target_env_var(name: &str) -> Option<TargetEnvVar> {
+    target_env_var_with(name, |key| env::var_os(key))
+}
+
+fn target_env_var_with<F>(name: &str, var: F) -> Option<TargetEnvVar>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    let keys = keys(name, &var);
+    for key in &keys {
+        crate::rerun_if_env_changed!(key);
+    }
+    find(keys, &var)
+}
+
+/// Returns the first of `keys` that is set.
+pub(crate) fn find<F>(keys: Vec<String>, var: &F) -> Option<TargetEnvVar>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    keys.into_iter()
+        .find_map(|key| var(&key).map(|value| TargetEnvVar { key, value }))
+}
+
+/// Returns the keys for `name`, from the highest precedence to the lowest.
+pub(crate) fn keys<F>(name: &str, var: &F) -> Vec<String>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    let target = var("TARGET").and_then(|target| target.into_string().ok());
+    let host = var("HOST").and_then(|host| host.into_string().ok());
+
+    let mut keys = Vec::new();
+    if let Some(target) = &target {
+        keys.push(format!("{}_{}", name, target));
+        keys.push(format!("{}_{}", name, target.replace('-', "_")));
+    }
+    let kind = if target == host { "HOST" } else { "TARGET" };
+    keys.push(format!("{}_{}", kind, name));
+    keys.push(name.to_owned());
+    keys
+}
+
+/// A variable found by [`target_env_var`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TargetEnvVar {
+    /// The key the value was read from, such as `TARGET_FOO`.
+    pub key: String,
+    /// The value of the variable.
+    pub value: OsString,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::BuildEnv, Directive};
+
+    fn lookup(name: &str, vars: &[(&str, &str)]) -> Option<TargetEnvVar> {
+        let mut found = None;
+        crate::output::capture(|| {
+            found = target_env_var_with(name, |key| {
+                vars.iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, value)| value.into())
+            });
+        });
+        found
+    }
+
+    #[test]
+    fn precedence() {
+        let mut vars = vec![
+            ("TARGET", "aarch64-unknown-linux-gnu"),
+            ("HOST", "x86_64-unknown-linux-gnu"),
+            ("CC", "cc"),
+            ("TARGET_CC", "target-cc"),
+            ("CC_aarch64_unknown_linux_gnu", "underscored-cc"),
+            ("CC_aarch64-unknown-linux-gnu", "triple-cc"),
+        ];
+        let mut found = Vec::new();
+        while vars.len() > 2 {
+            let var = lookup("CC", &vars).unwrap();
+            found.push(format!("{}={}", var.key, var.value.to_string_lossy()));
+            vars.pop();
+        }
+        assert_eq!(
+            found,
+            [
+                "CC_aarch64-unknown-linux-gnu=triple-cc",
+                "CC_aarch64_unknown_linux_gnu=underscored-cc",
+                "TARGET_CC=target-cc",
+                "CC=cc",
+            ]
+        );
+        assert_eq!(lookup("CC", &vars), None);
+    }
+
+    #[test]
+    fn host() {
+        let vars = [
+            ("TARGET", "x86_64-unknown-linux-gnu"),
+            ("HOST", "x86_64-unknown-linux-gnu"),
+            ("TARGET_CC", "target-cc"),
+            ("HOST_CC", "host-cc"),
+        ];
+        assert_eq!(
+            lookup("CC", &vars),
+            Some(TargetEnvVar {
+                key: "HOST_CC".to_owned(),
+                value: "host-cc".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn reruns() {
+        let output = BuildEnv::new()
+            .target("aarch64-unknown-linux-gnu")
+            .host("x86_64-unknown-linux-gnu")
+            .run(|| {
+                target_env_var("CARGO_EMIT_TEST_CC");
+            });
+        assert_eq!(
+            output.directives,
+            [
+                "CARGO_EMIT_TEST_CC_aarch64-unknown-linux-gnu",
+                "CARGO_EMIT_TEST_CC_aarch64_unknown_linux_gnu",
+                "TARGET_CARGO_EMIT_TEST_CC",
+                "CARGO_EMIT_TEST_CC",
+            ]
+            .iter()
+            .map(|key| Directive::RerunIfEnvChanged(key.to_string()))
+            .collect::<Vec<_>>()
+        );
+    }
+}
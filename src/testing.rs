@@ -0,0 +1,663 @@
+//! Unit-testing build scripts against simulated targets.
+//!
+//! [`BuildEnv::run`] calls a build function with the environment Cargo would
+//! give a build script — `TARGET`, `HOST`, `PROFILE`, an empty `OUT_DIR`,
+//! the `CARGO_CFG_*` variables derived from the target triple, and a
+//! `CARGO_FEATURE_*` variable per feature — and collects every directive it
+//! emits through this crate into a [`BuildOutput`].
+//!
+//! Only output written by this crate's macros and types on the calling
+//! thread is collected. Since the environment is process-wide, runs are
+//! serialized with each other, but not with other code that reads or writes
+//! environment variables concurrently.
+//!
+//! # Examples
+//!
+//! ```
+//! use cargo_emit::{testing::BuildEnv, LinkKind};
+//!
+//! fn build() {
+//!     let target_os = std::env::var("CARGO_CFG_TARGET_OS").unwrap();
+//!     if target_os == "windows" {
+//!         cargo_emit::rustc_link_lib!("libssl" => "static");
+//!     } else {
+//!         cargo_emit::rustc_link_lib!("ssl");
+//!     }
+//!     if std::env::var_os("CARGO_FEATURE_LEGACY").is_some() {
+//!         cargo_emit::rustc_cfg!("ossl_legacy");
+//!     }
+//! }
+//!
+//! BuildEnv::new()
+//!     .target("x86_64-unknown-linux-gnu")
+//!     .run(build)
+//!     .assert_links("ssl", LinkKind::Dylib);
+//!
+//! BuildEnv::new()
+//!     .target("x86_64-pc-windows-msvc")
+//!     .feature("legacy")
+//!     .run(build)
+//!     .assert_links("libssl", LinkKind::Static)
+//!     .assert_cfg("ossl_legacy");
+//! ```
+
+use crate::{Directive, LinkArgScope, LinkKind, Location, SearchKind};
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+/// Serializes runs, since they modify the process environment.
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Distinguishes the `OUT_DIR` of each run within this process.
+static RUN_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A simulated build script environment.
+#[derive(Clone, Debug)]
+pub struct BuildEnv {
+    target: String,
+    host: Option<String>,
+    profile: String,
+    features: Vec<String>,
+    cfgs: BTreeMap<String, String>,
+    vars: BTreeMap<String, Option<OsString>>,
+    out_dir: Option<PathBuf>,
+}
+
+impl Default for BuildEnv {
+    fn default() -> Self {
+        BuildEnv {
+            target: "x86_64-unknown-linux-gnu".to_owned(),
+            host: None,
+            profile: "debug".to_owned(),
+            features: Vec::new(),
+            cfgs: BTreeMap::new(),
+            vars: BTreeMap::new(),
+            out_dir: None,
+        }
+    }
+}
+
+impl BuildEnv {
+    /// Creates an environment for a debug build targeting
+    /// `x86_64-unknown-linux-gnu`, with no features enabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `TARGET` and the `CARGO_CFG_TARGET_*` variables derived from it.
+    pub fn target(&mut self, target: &str) -> &mut Self {
+        self.target = target.to_owned();
+        self
+    }
+
+    /// Sets `HOST`, which defaults to the target.
+    pub fn host(&mut self, host: &str) -> &mut Self {
+        self.host = Some(host.to_owned());
+        self
+    }
+
+    /// Sets `PROFILE`, along with `DEBUG` and `OPT_LEVEL` to match.
+    pub fn profile(&mut self, profile: &str) -> &mut Self {
+        self.profile = profile.to_owned();
+        self
+    }
+
+    /// Enables the feature `name`, setting `CARGO_FEATURE_$NAME`.
+    pub fn feature(&mut self, name: &str) -> &mut Self {
+        self.features.push(name.to_owned());
+        self
+    }
+
+    /// Sets `CARGO_CFG_$KEY` to `value`, overriding anything derived from the
+    /// target.
+    ///
+    /// `key` is given as it appears in `cfg`, such as `target_feature`.
+    pub fn cfg(&mut self, key: &str, value: &str) -> &mut Self {
+        self.cfgs.insert(key.to_owned(), value.to_owned());
+        self
+    }
+
+    /// Sets the environment variable `key` for the run.
+    pub fn env<V: Into<OsString>>(&mut self, key: &str, value: V) -> &mut Self {
+        self.vars.insert(key.to_owned(), Some(value.into()));
+        self
+    }
+
+    /// Removes the environment variable `key` for the run.
+    pub fn env_remove(&mut self, key: &str) -> &mut Self {
+        self.vars.insert(key.to_owned(), None);
+        self
+    }
+
+    /// Uses `dir` as `OUT_DIR` instead of a new temporary directory.
+    pub fn out_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.out_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Returns every variable this environment sets, and `None` for those it
+    /// removes.
+    fn vars(&self, out_dir: &Path) -> BTreeMap<String, Option<OsString>> {
+        let mut vars = BTreeMap::new();
+        let mut set = |key: String, value: &str| {
+            vars.insert(key, Some(OsString::from(value)));
+        };
+
+        set("TARGET".to_owned(), &self.target);
+        set(
+            "HOST".to_owned(),
+            self.host.as_ref().unwrap_or(&self.target),
+        );
+        set("PROFILE".to_owned(), &self.profile);
+        let release = self.profile == "release";
+        set("DEBUG".to_owned(), if release { "false" } else { "true" });
+        set("OPT_LEVEL".to_owned(), if release { "3" } else { "0" });
+        set("NUM_JOBS".to_owned(), "1");
+        set("OUT_DIR".to_owned(), &out_dir.display().to_string());
+
+        let mut cfgs = target_cfgs(&self.target);
+        cfgs.extend(self.cfgs.clone());
+        for (key, value) in &cfgs {
+            set(format!("CARGO_CFG_{}", key.to_uppercase()), value);
+        }
+        for feature in &self.features {
+            let name = feature.to_uppercase().replace('-', "_");
+            set(format!("CARGO_FEATURE_{}", name), "1");
+        }
+
+        vars.extend(self.vars.clone());
+        vars
+    }
+
+    /// Runs `build` in this environment, collecting what it emits.
+    ///
+    /// Any `CARGO_CFG_*` and `CARGO_FEATURE_*` variables already set in the
+    /// process are removed for the run, and the environment is restored
+    /// afterwards, even if `build` panics.
+    pub fn run<F: FnOnce()>(&self, build: F) -> BuildOutput {
+        let _lock = ENV_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+
+        let out_dir = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => {
+                let run = RUN_COUNT.fetch_add(1, Ordering::Relaxed);
+                let dir = env::temp_dir().join(format!(
+                    "cargo-emit-testing-{}-{}",
+                    std::process::id(),
+                    run
+                ));
+                let _ = fs::remove_dir_all(&dir);
+                dir
+            }
+        };
+        fs::create_dir_all(&out_dir).expect("could not create OUT_DIR");
+
+        let mut vars = self.vars(&out_dir);
+        for (key, _) in env::vars_os() {
+            if let Some(key) = key.to_str() {
+                if key.starts_with("CARGO_CFG_") || key.starts_with("CARGO_FEATURE_") {
+                    vars.entry(key.to_owned()).or_insert(None);
+                }
+            }
+        }
+
+        let _restore = Restore(
+            vars.iter()
+                .map(|(key, value)| {
+                    let previous = env::var_os(key);
+                    set_var(key, value.as_ref());
+                    (key.clone(), previous)
+                })
+                .collect(),
+        );
+
+        let (directives, locations) = crate::output::capture_lines(build)
+            .into_iter()
+            .filter_map(|(line, location)| Some((Directive::parse(&line)?, location)))
+            .unzip();
+        BuildOutput {
+            directives,
+            locations,
+            out_dir,
+        }
+    }
+}
+
+/// Restores environment variables when dropped.
+struct Restore(Vec<(String, Option<OsString>)>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        for (key, value) in &self.0 {
+            set_var(key, value.as_ref());
+        }
+    }
+}
+
+fn set_var(key: &str, value: Option<&OsString>) {
+    match value {
+        Some(value) => env::set_var(key, value),
+        None => env::remove_var(key),
+    }
+}
+
+/// Returns the `cfg` values rustc reports for `target`, keyed like
+/// `target_os`.
+fn target_cfgs(target: &str) -> BTreeMap<String, String> {
+    let parts: Vec<&str> = target.split('-').collect();
+    let arch = parts[0];
+    // The vendor is left out of triples like `thumbv7em-none-eabihf` and
+    // `aarch64-linux-android`.
+    let (vendor, rest) = match parts.get(1) {
+        Some(part) if is_os(part) => ("unknown", &parts[1..]),
+        Some(vendor) => (*vendor, &parts[2..]),
+        None => ("unknown", &parts[1..]),
+    };
+    let (os, suffix) = match rest {
+        ["linux", suffix, ..] if suffix.starts_with("android") => ("android", *suffix),
+        [os, suffix, ..] => (*os, *suffix),
+        [os] => (*os, ""),
+        [] => ("none", ""),
+    };
+    let (os, target_env, target_abi) = match os {
+        "darwin" => ("macos", "", ""),
+        _ if os.starts_with("wasi") => ("wasi", &os[4..], ""),
+        _ => {
+            let (target_env, target_abi) = env_and_abi(suffix);
+            (os, target_env, target_abi)
+        }
+    };
+
+    let arch = match arch {
+        "i386" | "i586" | "i686" => "x86",
+        "arm64" => "aarch64",
+        arch if arch.starts_with("thumbv") => "arm",
+        arch if arch.starts_with("arm") => "arm",
+        arch if arch.starts_with("riscv64") => "riscv64",
+        arch if arch.starts_with("riscv32") => "riscv32",
+        arch => arch,
+    };
+
+    let pointer_width = match arch {
+        "x86_64" | "aarch64" | "powerpc64" | "powerpc64le" | "riscv64" | "s390x" | "mips64"
+        | "mips64el" | "sparc64" | "loongarch64" | "wasm64" => "64",
+        "avr" | "msp430" => "16",
+        _ => "32",
+    };
+    let endian = match arch {
+        "s390x" | "powerpc" | "powerpc64" | "mips" | "mips64" | "sparc" | "sparc64" => "big",
+        _ => "little",
+    };
+    let family = match os {
+        "windows" => "windows",
+        _ if arch.starts_with("wasm") => "wasm",
+        "none" | "uefi" => "",
+        _ => "unix",
+    };
+
+    let mut cfgs = BTreeMap::new();
+    let mut set = |key: &str, value: &str| {
+        cfgs.insert(key.to_owned(), value.to_owned());
+    };
+    set("target_arch", arch);
+    set("target_vendor", vendor);
+    set("target_os", os);
+    set("target_env", target_env);
+    set("target_abi", target_abi);
+    set("target_pointer_width", pointer_width);
+    set("target_endian", endian);
+    if !family.is_empty() {
+        set("target_family", family);
+        set(family, "");
+    }
+    cfgs
+}
+
+/// Returns whether `part` of a triple names an operating system, rather
+/// than a vendor.
+fn is_os(part: &str) -> bool {
+    match part {
+        "linux" | "android" | "windows" | "none" | "darwin" | "ios" | "freebsd" | "netbsd"
+        | "openbsd" | "fuchsia" | "cuda" | "uefi" => true,
+        _ => part.starts_with("wasi"),
+    }
+}
+
+/// Splits the last part of a triple, like `gnueabihf`, into `target_env`
+/// and `target_abi`.
+fn env_and_abi(suffix: &str) -> (&str, &str) {
+    for &env in &["gnu", "musl", "uclibc", "msvc", "ohos", "sgx", "newlib"] {
+        if let Some(abi) = suffix.strip_prefix(env) {
+            return (env, abi);
+        }
+    }
+    match suffix {
+        // rustc reports Apple simulator and Mac Catalyst targets as both.
+        "sim" | "macabi" => (suffix, suffix),
+        // An object format, rather than an ABI.
+        "elf" => ("", ""),
+        _ => ("", suffix.trim_start_matches("android")),
+    }
+}
+
+/// The directives emitted by a build function, collected by
+/// [`BuildEnv::run`].
+///
+/// The assertion methods panic with the full list of directives if they
+/// fail, and return `self` so that they can be chained.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildOutput {
+    /// Every directive emitted, in order.
+    pub directives: Vec<Directive>,
+    /// The location of the macro call that emitted each of the
+    /// [`directives`](Self::directives), at the same index, or `None` for
+    /// those emitted with [`Directive::emit`] or other functions.
+    pub locations: Vec<Option<Location>>,
+    /// The `OUT_DIR` the build function ran with.
+    pub out_dir: PathBuf,
+}
+
+impl BuildOutput {
+    /// Returns whether any directive satisfies `predicate`.
+    pub fn contains<F: Fn(&Directive) -> bool>(&self, predicate: F) -> bool {
+        self.directives.iter().any(predicate)
+    }
+
+    fn assert<F: Fn(&Directive) -> bool>(&self, predicate: F, expected: String) -> &Self {
+        if !self.contains(predicate) {
+            let mut message = format!("expected {}, but got:", expected);
+            if self.directives.is_empty() {
+                message.push_str(" nothing");
+            }
+            for directive in &self.directives {
+                message.push_str("\n    ");
+                message.push_str(&directive.to_string());
+            }
+            panic!("{}", message);
+        }
+        self
+    }
+
+    /// Asserts that `directive` was emitted.
+    pub fn assert_emits(&self, directive: &Directive) -> &Self {
+        self.assert(|d| d == directive, format!("`{}`", directive))
+    }
+
+    /// Asserts that the library `name` is linked as `kind`.
+    ///
+    /// A library emitted without a kind is linked dynamically by default, so
+    /// it satisfies [`LinkKind::Dylib`].
+    pub fn assert_links(&self, name: &str, kind: LinkKind) -> &Self {
+        self.assert(
+            |directive| match directive {
+                Directive::RustcLinkLib {
+                    name: n,
+                    kind: Some(k),
+                    ..
+                } => n == name && *k == kind,
+                Directive::RustcLinkLib {
+                    name: n,
+                    kind: None,
+                    ..
+                } => n == name && kind == LinkKind::Dylib,
+                _ => false,
+            },
+            format!("`{}` to be linked as `{}`", name, kind),
+        )
+    }
+
+    /// Asserts that the library `name` is not linked at all.
+    pub fn assert_not_links(&self, name: &str) -> &Self {
+        let linked = self.contains(|directive| match directive {
+            Directive::RustcLinkLib { name: n, .. } => n == name,
+            _ => false,
+        });
+        assert!(!linked, "expected `{}` not to be linked", name);
+        self
+    }
+
+    /// Asserts that `path` is searched for libraries, as `kind` if given.
+    pub fn assert_link_search(&self, path: &str, kind: Option<SearchKind>) -> &Self {
+        self.assert(
+            |directive| match directive {
+                Directive::RustcLinkSearch { path: p, kind: k } => {
+                    p == path && (kind.is_none() || *k == kind)
+                }
+                _ => false,
+            },
+            format!("`{}` to be searched", path),
+        )
+    }
+
+    /// Asserts that `arg` is passed to the linker for the targets in `scope`.
+    ///
+    /// A [`Cdylib`](LinkArgScope::Cdylib) argument matches either spelling
+    /// of its key.
+    pub fn assert_link_arg(&self, scope: LinkArgScope, arg: &str) -> &Self {
+        let matches = |s: &LinkArgScope| match (s, &scope) {
+            (LinkArgScope::Cdylib { .. }, LinkArgScope::Cdylib { .. }) => true,
+            (s, scope) => s == scope,
+        };
+        self.assert(
+            |directive| match directive {
+                Directive::RustcLinkArg { scope: s, arg: a } => matches(s) && a == arg,
+                _ => false,
+            },
+            format!("`{}` to be passed to `{}`", arg, scope.key()),
+        )
+    }
+
+    /// Asserts that `cfg` is enabled with `rustc_cfg!`.
+    pub fn assert_cfg(&self, cfg: &str) -> &Self {
+        self.assert_emits(&Directive::RustcCfg(cfg.to_owned()))
+    }
+
+    /// Asserts that the environment variable `key` is set to `value` with
+    /// `rustc_env!`.
+    pub fn assert_env(&self, key: &str, value: &str) -> &Self {
+        self.assert_emits(&Directive::RustcEnv {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+
+    /// Asserts that the build script reruns when `path` changes.
+    pub fn assert_rerun_if_changed(&self, path: &str) -> &Self {
+        self.assert_emits(&Directive::RerunIfChanged(path.to_owned()))
+    }
+
+    /// Asserts that the build script reruns when the environment variable
+    /// `key` changes.
+    pub fn assert_rerun_if_env_changed(&self, key: &str) -> &Self {
+        self.assert_emits(&Directive::RerunIfEnvChanged(key.to_owned()))
+    }
+
+    /// Asserts that a warning containing `text` was emitted.
+    pub fn assert_warns(&self, text: &str) -> &Self {
+        self.assert(
+            |directive| match directive {
+                Directive::Warning(message) => message.contains(text),
+                _ => false,
+            },
+            format!("a warning containing `{}`", text),
+        )
+    }
+
+    /// Asserts that no warnings were emitted.
+    pub fn assert_no_warnings(&self) -> &Self {
+        let warnings: Vec<&Directive> = self
+            .directives
+            .iter()
+            .filter(|directive| matches!(directive, Directive::Warning(_)))
+            .collect();
+        assert!(
+            warnings.is_empty(),
+            "expected no warnings, but got {:?}",
+            warnings
+        );
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_cfgs() {
+        insta::assert_debug_snapshot!(
+            super::target_cfgs("aarch64-apple-darwin"),
+            @r###"
+        {
+            "target_abi": "",
+            "target_arch": "aarch64",
+            "target_endian": "little",
+            "target_env": "",
+            "target_family": "unix",
+            "target_os": "macos",
+            "target_pointer_width": "64",
+            "target_vendor": "apple",
+            "unix": "",
+        }
+        "###
+        );
+        assert_eq!(
+            super::target_cfgs("i686-pc-windows-msvc")["target_arch"],
+            "x86"
+        );
+        assert_eq!(
+            super::target_cfgs("i686-pc-windows-msvc")["target_env"],
+            "msvc"
+        );
+        assert_eq!(
+            super::target_cfgs("wasm32-unknown-unknown")["target_family"],
+            "wasm"
+        );
+    }
+
+    #[test]
+    fn triples() {
+        let parts = |target| {
+            let cfgs = super::target_cfgs(target);
+            ["target_vendor", "target_os", "target_env", "target_abi"]
+                .iter()
+                .map(|key| cfgs[*key].clone())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        // Embedded
+        assert_eq!(parts("thumbv7em-none-eabihf"), "unknown none  eabihf");
+        assert_eq!(parts("riscv64gc-unknown-none-elf"), "unknown none  ");
+        assert_eq!(
+            parts("aarch64-unknown-none-softfloat"),
+            "unknown none  softfloat"
+        );
+        // Android
+        assert_eq!(parts("aarch64-linux-android"), "unknown android  ");
+        assert_eq!(parts("arm-linux-androideabi"), "unknown android  eabi");
+        // Simulators
+        assert_eq!(parts("aarch64-apple-ios-sim"), "apple ios sim sim");
+        assert_eq!(parts("aarch64-apple-ios-macabi"), "apple ios macabi macabi");
+        // ABIs appended to the environment
+        assert_eq!(
+            parts("armv7-unknown-linux-gnueabihf"),
+            "unknown linux gnu eabihf"
+        );
+        assert_eq!(parts("x86_64-pc-windows-gnullvm"), "pc windows gnu llvm");
+        assert_eq!(parts("x86_64-unknown-linux-musl"), "unknown linux musl ");
+        assert_eq!(parts("wasm32-wasip1"), "unknown wasi p1 ");
+    }
+
+    #[test]
+    fn environment() {
+        let output = BuildEnv::new()
+            .target("x86_64-pc-windows-gnu")
+            .profile("release")
+            .feature("vendored-ssl")
+            .env("MY_VAR", "value")
+            .run(|| {
+                let var = |key| env::var(key).unwrap();
+                crate::rustc_env!("TARGET_OS", "{}", var("CARGO_CFG_TARGET_OS"));
+                crate::rustc_env!("TARGET_ENV", "{}", var("CARGO_CFG_TARGET_ENV"));
+                crate::rustc_env!("OPT_LEVEL", "{}", var("OPT_LEVEL"));
+                crate::rustc_env!("FEATURE", "{}", var("CARGO_FEATURE_VENDORED_SSL"));
+                crate::rustc_env!("MY_VAR", "{}", var("MY_VAR"));
+                assert!(Path::new(&var("OUT_DIR")).is_dir());
+            });
+
+        output
+            .assert_env("TARGET_OS", "windows")
+            .assert_env("TARGET_ENV", "gnu")
+            .assert_env("OPT_LEVEL", "3")
+            .assert_env("FEATURE", "1")
+            .assert_env("MY_VAR", "value");
+        assert_eq!(env::var_os("MY_VAR"), None);
+    }
+
+    #[test]
+    fn collects_library_output() {
+        let output = BuildEnv::new().run(|| {
+            crate::flags::emit("-L/opt/lib -Wl,-Bstatic -lfoo -Wl,-Bdynamic -lbar");
+            crate::rerun_if_env_changed!("FOO_DIR");
+        });
+
+        output
+            .assert_link_search("/opt/lib", Some(SearchKind::Native))
+            .assert_links("foo", LinkKind::Static)
+            .assert_links("bar", LinkKind::Dylib)
+            .assert_not_links("baz")
+            .assert_rerun_if_env_changed("FOO_DIR")
+            .assert_no_warnings();
+        assert_eq!(output.directives.len(), 4, "{:?}", output.directives);
+    }
+
+    #[test]
+    fn link_args() {
+        BuildEnv::new()
+            .run(|| {
+                crate::rustc_link_arg_bins!("-s");
+                crate::rustc_cdylib_link_arg!("-Wl,--version-script=foo.map");
+            })
+            .assert_link_arg(LinkArgScope::Bins, "-s")
+            .assert_link_arg(
+                LinkArgScope::Cdylib { legacy: false },
+                "-Wl,--version-script=foo.map",
+            );
+    }
+
+    #[test]
+    #[should_panic(expected = "expected `ssl` to be linked as `static`, but got:\n    \
+                               cargo:rustc-link-lib=ssl")]
+    fn failed_assertion() {
+        BuildEnv::new()
+            .run(|| {
+                crate::rustc_link_lib!("ssl");
+            })
+            .assert_links("ssl", LinkKind::Static);
+    }
+
+    #[test]
+    fn restores_after_panic() {
+        let result = std::panic::catch_unwind(|| {
+            BuildEnv::new()
+                .env("RESTORED_VAR", "1")
+                .run(|| panic!("oops"));
+        });
+        assert!(result.is_err());
+        assert_eq!(env::var_os("RESTORED_VAR"), None);
+
+        let output = BuildEnv::new().run(|| {
+            crate::rustc_cfg!("after");
+        });
+        output.assert_cfg("after");
+    }
+}
@@ -0,0 +1,323 @@
+//! Showing [`tracing`] events during `cargo build`.
+//!
+//! [`Layer`] is a [`tracing_subscriber::Layer`] that emits each event at or
+//! above its level as a [`warning!`](crate::warning), or as an
+//! [`error!`](crate::error) for [`ERROR`](Level::ERROR) events, prefixed with
+//! the event's level, target and spans:
+//!
+//! ```text
+//! warning: my-crate@0.1.0: [WARN cmake] configure{dir="vendor/zlib"}: no C++ compiler found
+//! ```
+//!
+//! `ERROR` events pass every level, so they fail the build unless
+//! [`fatal_errors`](Layer::fatal_errors) is turned off, which emits them as
+//! warnings instead.
+//!
+//! Alternatively, the layer can write every event to a file in `OUT_DIR`,
+//! emitting a single warning with the path of the file instead.
+//!
+//! # Examples
+//!
+//! ```
+//! use cargo_emit::trace::Layer;
+//! use tracing_subscriber::layer::SubscriberExt;
+//!
+//! let subscriber = tracing_subscriber::registry().with(Layer::new());
+//! tracing::subscriber::set_global_default(subscriber).unwrap();
+//!
+//! tracing::warn!(dir = "vendor/zlib", "no C++ compiler found");
+//! ```
+//!
+//! [`tracing`]: https://docs.rs/tracing
+
+use std::{
+    env,
+    fmt::{self, Write as _},
+    fs::File,
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing_core::{
+    field::{Field, Visit},
+    span, Event, Level, Metadata, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan};
+
+/// A [`tracing_subscriber::Layer`] that surfaces events in Cargo's output.
+///
+/// Unlike the other builders in this crate, its methods take `self` by value,
+/// since the layer is moved into its subscriber.
+#[derive(Debug)]
+pub struct Layer {
+    level: Level,
+    fatal_errors: bool,
+    file: Option<TraceFile>,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Layer {
+            level: Level::WARN,
+            fatal_errors: true,
+            file: None,
+        }
+    }
+}
+
+impl Layer {
+    /// Creates a layer that emits events at [`WARN`](Level::WARN) level and
+    /// above.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the most verbose level that is emitted.
+    ///
+    /// This has no effect when writing to a [file](Self::file).
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Whether [`ERROR`](Level::ERROR) events are emitted as errors, which
+    /// fail the build, rather than as warnings.
+    ///
+    /// Defaults to `true`. Turn it off if a dependency logs problems that the
+    /// build recovers from at `ERROR` level.
+    pub fn fatal_errors(mut self, fatal: bool) -> Self {
+        self.fatal_errors = fatal;
+        self
+    }
+
+    /// Writes every event to `path` instead of emitting them, and emits a
+    /// single warning with the path once the file is created.
+    ///
+    /// A relative `path` is resolved against `OUT_DIR`.
+    pub fn file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.file = Some(TraceFile {
+            path: path.as_ref().to_owned(),
+            state: Mutex::new(FileState::Unopened),
+        });
+        self
+    }
+}
+
+impl<S> tracing_subscriber::Layer<S> for Layer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn enabled(&self, metadata: &Metadata<'_>, _: Context<'_, S>) -> bool {
+        // Spans are always enabled so that events within them have context.
+        self.file.is_some() || metadata.is_span() || *metadata.level() <= self.level
+    }
+
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(fields) = span.extensions_mut().get_mut::<Fields>() {
+                values.record(fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if self.file.is_none() && *metadata.level() > self.level {
+            return;
+        }
+
+        let mut prefix = format!("[{} {}]", metadata.level(), metadata.target());
+        if let Some(scope) = ctx.event_scope(event) {
+            let mut separator = " ";
+            for span in scope.from_root() {
+                prefix.push_str(separator);
+                prefix.push_str(span.name());
+                if let Some(fields) = span.extensions().get::<Fields>() {
+                    if !fields.0.is_empty() {
+                        let _ = write!(prefix, "{{{}}}", fields.0);
+                    }
+                }
+                separator = ":";
+            }
+            if separator == ":" {
+                prefix.push(':');
+            }
+        }
+
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+
+        if let Some(file) = &self.file {
+            file.write(&format!("{} {}\n", prefix, fields.0));
+            return;
+        }
+
+        for line in fields.0.lines() {
+            if self.fatal_errors && *metadata.level() == Level::ERROR {
+                crate::error!("{} {}", prefix, line);
+            } else {
+                crate::warning!("{} {}", prefix, line);
+            }
+        }
+    }
+}
+
+/// The fields of a span or event, rendered as `message key=value ...`.
+#[derive(Default)]
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        let _ = match field.name() {
+            "message" => write!(self.0, "{:?}", value),
+            name => write!(self.0, "{}={:?}", name, value),
+        };
+    }
+}
+
+/// The file that a [`Layer`] writes every event to.
+#[derive(Debug)]
+struct TraceFile {
+    path: PathBuf,
+    state: Mutex<FileState>,
+}
+
+#[derive(Debug)]
+enum FileState {
+    Unopened,
+    Open(File),
+    Failed,
+}
+
+impl TraceFile {
+    fn write(&self, line: &str) {
+        let mut state = self.state.lock().unwrap_or_else(|error| error.into_inner());
+        if let FileState::Unopened = *state {
+            *state = match self.create() {
+                Ok((path, file)) => {
+                    crate::warning!("trace written to {}", path.display());
+                    FileState::Open(file)
+                }
+                Err(error) => {
+                    crate::warning!(
+                        "could not write trace to {}: {}",
+                        self.path.display(),
+                        error
+                    );
+                    FileState::Failed
+                }
+            };
+        }
+        if let FileState::Open(file) = &mut *state {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+
+    fn create(&self) -> io::Result<(PathBuf, File)> {
+        let path = if self.path.is_absolute() {
+            self.path.clone()
+        } else {
+            let out_dir = env::var_os("OUT_DIR")
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is not set"))?;
+            Path::new(&out_dir).join(&self.path)
+        };
+        let file = File::create(&path)?;
+        Ok((path, file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::BuildEnv;
+    use std::fs;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn events() {
+        let span = tracing::info_span!("configure", dir = "vendor/zlib");
+        let _span = span.enter();
+        tracing::debug!("probing");
+        tracing::warn!(compiler = "c++", "compiler not found");
+        tracing::info_span!("build").in_scope(|| {
+            tracing::error!("make failed:\nline 1\nline 2");
+        });
+    }
+
+    #[test]
+    fn warnings() {
+        let layer = Layer::new();
+        let output = crate::output::capture(|| {
+            tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), events);
+        });
+        insta::assert_snapshot!(output, @r###"
+        cargo:warning=[WARN cargo_emit::trace::tests] configure{dir="vendor/zlib"}: compiler not found compiler="c++"
+        cargo::error=[ERROR cargo_emit::trace::tests] configure{dir="vendor/zlib"}:build: make failed:
+        cargo::error=[ERROR cargo_emit::trace::tests] configure{dir="vendor/zlib"}:build: line 1
+        cargo::error=[ERROR cargo_emit::trace::tests] configure{dir="vendor/zlib"}:build: line 2
+        "###);
+    }
+
+    #[test]
+    fn non_fatal_errors() {
+        let layer = Layer::new().fatal_errors(false);
+        let output = crate::output::capture(|| {
+            tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+                tracing::error!("retrying");
+            });
+        });
+        insta::assert_snapshot!(
+            output,
+            @"cargo:warning=[ERROR cargo_emit::trace::tests] retrying\n"
+        );
+    }
+
+    #[test]
+    fn level() {
+        let layer = Layer::new().level(Level::DEBUG);
+        let output = crate::output::capture(|| {
+            tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+                tracing::trace!("hidden");
+                tracing::debug!(answer = 42);
+            });
+        });
+        insta::assert_snapshot!(
+            output,
+            @"cargo:warning=[DEBUG cargo_emit::trace::tests] answer=42\n"
+        );
+    }
+
+    #[test]
+    fn file() {
+        let output = BuildEnv::new().run(|| {
+            let layer = Layer::new().file("trace.log");
+            tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), events);
+        });
+
+        let path = output.out_dir.join("trace.log");
+        assert_eq!(
+            output.directives,
+            [crate::Directive::Warning(format!(
+                "trace written to {}",
+                path.display()
+            ))]
+        );
+        insta::assert_snapshot!(fs::read_to_string(&path).unwrap(), @r###"
+        [DEBUG cargo_emit::trace::tests] configure{dir="vendor/zlib"}: probing
+        [WARN cargo_emit::trace::tests] configure{dir="vendor/zlib"}: compiler not found compiler="c++"
+        [ERROR cargo_emit::trace::tests] configure{dir="vendor/zlib"}:build: make failed:
+        line 1
+        line 2
+        "###);
+    }
+}
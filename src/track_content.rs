@@ -0,0 +1,271 @@
+//! Skipping expensive work when tracked inputs have the same contents.
+//!
+//! Cargo reruns a build script when the modification time of a path emitted
+//! with [`rerun_if_changed!`](crate::rerun_if_changed) changes, even if its
+//! contents did not, such as after a `git checkout` that switches back and
+//! forth. [`track_content`] hashes the inputs instead, and compares them with
+//! a stamp in `OUT_DIR` from the last successful run:
+//!
+//! ```no_run
+//! # fn build_libfoo() {}
+//! let inputs = cargo_emit::track_content("libfoo")
+//!     .path("vendor/libfoo")
+//!     .path("build.rs")
+//!     .check()
+//!     .unwrap();
+//!
+//! if inputs.changed() {
+//!     build_libfoo();
+//!     inputs.save().unwrap();
+//! }
+//! ```
+//!
+//! [`check`](TrackContent::check) still emits a `rerun_if_changed!` for each
+//! path, so that Cargo reruns the build script to do the comparison.
+//! Directories are hashed recursively. The stamp is only written by
+//! [`save`](Tracked::save), so that a failed build is retried on the next run.
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Creates a tracker for the inputs of the work called `name`, whose stamp is
+/// stored at `$OUT_DIR/$name.stamp`.
+///
+/// See the [module documentation](mod@crate::track_content) for details.
+pub fn track_content(name: &str) -> TrackContent {
+    TrackContent {
+        name: name.to_owned(),
+        paths: Vec::new(),
+        out_dir: None,
+    }
+}
+
+/// A set of inputs to hash, created by [`track_content`].
+#[derive(Clone, Debug)]
+pub struct TrackContent {
+    name: String,
+    paths: Vec<PathBuf>,
+    out_dir: Option<PathBuf>,
+}
+
+impl TrackContent {
+    /// Tracks the file or directory at `path`.
+    pub fn path<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.paths.push(path.as_ref().to_owned());
+        self
+    }
+
+    /// Tracks each file or directory in `paths`.
+    pub fn paths<I>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        for path in paths {
+            self.path(path);
+        }
+        self
+    }
+
+    /// Stores the stamp in `dir` instead of `OUT_DIR`.
+    pub fn out_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.out_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Emits `rerun_if_changed!` for each path, then hashes their contents and
+    /// compares them with the saved stamp.
+    pub fn check(&self) -> io::Result<Tracked> {
+        for path in &self.paths {
+            crate::rerun_if_changed!(path.display());
+        }
+
+        let dir = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => env::var_os("OUT_DIR")
+                .map(PathBuf::from)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is not set"))?,
+        };
+        let stamp_path = dir.join(format!("{}.stamp", self.name));
+
+        let mut files = Vec::new();
+        for path in &self.paths {
+            collect_files(path, &mut files)?;
+        }
+        let mut stamp = String::new();
+        for file in &files {
+            let digest = crate::build_info::sha1(&fs::read(file)?);
+            for byte in &digest {
+                let _ = write!(stamp, "{:02x}", byte);
+            }
+            let _ = writeln!(stamp, "  {}", file.display());
+        }
+
+        let changed_paths = match fs::read_to_string(&stamp_path) {
+            Ok(previous) => changed_paths(&previous, &stamp),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => files,
+            Err(error) => return Err(error),
+        };
+
+        Ok(Tracked {
+            stamp_path,
+            stamp,
+            changed_paths,
+        })
+    }
+}
+
+/// Appends `path`, or every file under it if it is a directory, sorted.
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !fs::metadata(path)?.is_dir() {
+        files.push(path.to_owned());
+        return Ok(());
+    }
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        collect_files(&entry, files)?;
+    }
+    Ok(())
+}
+
+/// Returns the files that were added, removed or modified between two stamps.
+fn changed_paths(previous: &str, current: &str) -> Vec<PathBuf> {
+    let entries = |stamp: &str| -> Vec<(String, String)> {
+        stamp
+            .lines()
+            .filter_map(|line| line.split_once("  "))
+            .map(|(hash, path)| (path.to_owned(), hash.to_owned()))
+            .collect()
+    };
+    let previous = entries(previous);
+    let current = entries(current);
+
+    let mut changed: Vec<PathBuf> = current
+        .iter()
+        .filter(|entry| !previous.contains(entry))
+        .map(|(path, _)| PathBuf::from(path))
+        .collect();
+    for (path, _) in &previous {
+        if !current.iter().any(|(current, _)| current == path) {
+            changed.push(PathBuf::from(path));
+        }
+    }
+    changed
+}
+
+/// The result of [`TrackContent::check`].
+#[derive(Clone, Debug)]
+pub struct Tracked {
+    stamp_path: PathBuf,
+    stamp: String,
+    changed_paths: Vec<PathBuf>,
+}
+
+impl Tracked {
+    /// Returns whether any input changed since the stamp was saved, or there
+    /// is no stamp yet.
+    pub fn changed(&self) -> bool {
+        !self.changed_paths.is_empty()
+    }
+
+    /// Returns the files that were added, removed or modified since the stamp
+    /// was saved, or every file if there is no stamp yet.
+    pub fn changed_paths(&self) -> &[PathBuf] {
+        &self.changed_paths
+    }
+
+    /// Returns the path of the stamp.
+    pub fn stamp_path(&self) -> &Path {
+        &self.stamp_path
+    }
+
+    /// Saves the hashes of the inputs, so that the next run sees them as
+    /// unchanged.
+    ///
+    /// Call this once the work that depends on the inputs has succeeded.
+    pub fn save(&self) -> io::Result<()> {
+        crate::constants::write_if_changed(&self.stamp_path, self.stamp.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn track_content() {
+        let dir = crate::temp_dir("track-content");
+        let src = dir.join("src");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(src.join("a.c"), "int a;").unwrap();
+        fs::write(src.join("nested/b.c"), "int b;").unwrap();
+        fs::write(dir.join("config.h"), "#define X 1").unwrap();
+
+        let mut tracker = super::track_content("libfoo");
+        tracker.out_dir(&dir).paths([&src, &dir.join("config.h")]);
+        let check = || {
+            let mut tracked = None;
+            let output = crate::output::capture(|| tracked = Some(tracker.check().unwrap()));
+            (
+                tracked.unwrap(),
+                output.replace(&dir.display().to_string(), "$DIR"),
+            )
+        };
+
+        let (tracked, output) = check();
+        insta::assert_snapshot!(output, @r###"
+        cargo:rerun-if-changed=$DIR/src
+        cargo:rerun-if-changed=$DIR/config.h
+        "###);
+        assert_eq!(
+            tracked.changed_paths(),
+            [
+                src.join("a.c"),
+                src.join("nested/b.c"),
+                dir.join("config.h")
+            ]
+        );
+        tracked.save().unwrap();
+        assert_eq!(tracked.stamp_path(), dir.join("libfoo.stamp"));
+
+        // Rewriting a file with the same contents is not a change.
+        fs::write(src.join("a.c"), "int a;").unwrap();
+        assert!(!check().0.changed());
+
+        fs::write(src.join("nested/b.c"), "int b = 1;").unwrap();
+        fs::remove_file(src.join("a.c")).unwrap();
+        fs::write(src.join("c.c"), "int c;").unwrap();
+        let (tracked, _) = check();
+        assert_eq!(
+            tracked.changed_paths(),
+            [src.join("c.c"), src.join("nested/b.c"), src.join("a.c")]
+        );
+
+        // Without saving, the next run still sees the changes.
+        assert!(check().0.changed());
+        tracked.save().unwrap();
+        assert!(!check().0.changed());
+    }
+
+    #[test]
+    fn missing_input() {
+        let dir = crate::temp_dir("track-content-missing");
+        let output = crate::output::capture(|| {
+            let error = super::track_content("missing")
+                .out_dir(&dir)
+                .path(dir.join("missing.h"))
+                .check()
+                .unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        });
+        assert!(output.starts_with("cargo:rerun-if-changed="));
+    }
+}
@@ -0,0 +1,109 @@
+//! Hooks for the `validate` feature, which checks literal macro arguments at
+//! compile time with the `cargo-emit-macros` crate.
+//!
+//! The feature is selected here rather than in the exported macros, since a
+//! `#[cfg]` in their expansion would refer to the features of the calling
+//! crate.
+
+/// Checks `$arg` with the `$check` validator of `cargo-emit-macros`.
+#[cfg(feature = "validate")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __validate {
+    ($check:ident, $($arg:tt)+) => {
+        $crate::__private::validate::$check!($($arg)+);
+    };
+}
+
+/// Does nothing, since the `validate` feature is disabled.
+#[cfg(not(feature = "validate"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __validate {
+    ($check:ident, $($arg:tt)+) => {};
+}
+
+/// The arguments that the `validate` feature rejects, one `compile_fail`
+/// example each, next to valid ones that compile.
+///
+/// ```
+/// let mut out = String::new();
+/// cargo_emit::rustc_link_lib!(to: out, "foo" => "static:+whole-archive");
+/// cargo_emit::rustc_link_search!(to: out, "/usr/lib" => "native");
+/// cargo_emit::rustc_cfg!(to: out, "feature=\"foo\"");
+/// cargo_emit::rustc_env!(to: out, "FOO", "bar");
+/// cargo_emit::rustc_link_arg_bin!(to: out, "foo" => "-lbar");
+/// cargo_emit::pair!(to: out, "key", "value");
+/// ```
+///
+/// An unknown link kind:
+///
+/// ```compile_fail
+/// let mut out = String::new();
+/// cargo_emit::rustc_link_lib!(to: out, "foo" => "statik");
+/// ```
+///
+/// An unknown link modifier:
+///
+/// ```compile_fail
+/// let mut out = String::new();
+/// cargo_emit::rustc_link_lib!(to: out, "foo" => "static:whole-archive");
+/// ```
+///
+/// A library name with the kind inlined:
+///
+/// ```compile_fail
+/// let mut out = String::new();
+/// cargo_emit::rustc_link_lib!(to: out, "static=foo");
+/// ```
+///
+/// An unknown search kind:
+///
+/// ```compile_fail
+/// let mut out = String::new();
+/// cargo_emit::rustc_link_search!(to: out, "/usr/lib" => "system");
+/// ```
+///
+/// A cfg name that is not an identifier:
+///
+/// ```compile_fail
+/// let mut out = String::new();
+/// cargo_emit::rustc_cfg!(to: out, "has-foo");
+/// ```
+///
+/// An unquoted cfg value:
+///
+/// ```compile_fail
+/// let mut out = String::new();
+/// cargo_emit::rustc_cfg!(to: out, "feature=foo");
+/// ```
+///
+/// An environment variable name with whitespace:
+///
+/// ```compile_fail
+/// let mut out = String::new();
+/// cargo_emit::rustc_env!(to: out, "FOO BAR", "bar");
+/// ```
+///
+/// A binary name with whitespace:
+///
+/// ```compile_fail
+/// let mut out = String::new();
+/// cargo_emit::rustc_link_arg_bin!(to: out, "foo bar" => "-lbar");
+/// ```
+///
+/// An empty key:
+///
+/// ```compile_fail
+/// let mut out = String::new();
+/// cargo_emit::pair!(to: out, "", "value");
+/// ```
+///
+/// A value with a newline:
+///
+/// ```compile_fail
+/// let mut out = String::new();
+/// cargo_emit::pair!(to: out, "key", "a\nb");
+/// ```
+#[cfg(all(doctest, feature = "validate"))]
+pub struct CompileFail;
@@ -11,6 +11,7 @@
 /// Useful for showing when something expected (but not critical) has failed.
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// match std::env::current_dir() {
 ///     Ok(dir) => { /* ... */ }
 ///     Err(error) => cargo_emit::warning!(
@@ -18,11 +19,13 @@
 ///         error,
 ///     ),
 /// }
+/// # }
 /// ```
 ///
 /// or, in case you want it to emit to a custom stream:
 ///
 /// ```
+/// # #[cfg(feature = "std")] {
 /// let mut stdout = std::io::stdout();
 /// match std::env::current_dir() {
 ///     Ok(dir) => { /* ... */ }
@@ -32,6 +35,7 @@
 ///         error,
 ///     ),
 /// }
+/// # }
 /// ```
 ///
 /// Assuming you're building `my-crate`, you will see:
@@ -47,7 +51,7 @@ macro_rules! warning {
         $crate::pair!(to: $stream, "warning", $($args)+)
     };
     ($($args:tt)+) => {
-        $crate::warning!(to: std::io::stdout(), $($args)+)
+        $crate::warning!(to: $crate::__private::stdout(), $($args)+)
     };
 }
 
@@ -55,7 +59,7 @@ macro_rules! warning {
 mod tests {
     #[test]
     fn single_literal() {
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::warning!(
                     to: output,
@@ -69,7 +73,7 @@ mod tests {
     #[test]
     fn single_formatted_by_index() {
         // Formatted argument:
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::warning!(
                     to: output,
@@ -83,7 +87,7 @@ mod tests {
     #[test]
     fn single_formatted_by_key() {
         // Formatted argument:
-        insta::assert_display_snapshot!(
+        insta::assert_snapshot!(
             crate::capture_output(|output| {
                 crate::warning!(
                     to: output,